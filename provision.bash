@@ -0,0 +1,25 @@
+#!/usr/bin/env bash
+# pass provision - manage a multi-device password store
+#
+# Install as $SYSTEM_EXTENSION_DIR/provision.bash (usually
+# /usr/lib/password-store/extensions/) or, with
+# PASSWORD_STORE_ENABLE_EXTENSIONS=true, as
+# $PASSWORD_STORE_DIR/.extensions/provision.bash.
+#
+# pass sources this file with the arguments following `provision`, so the
+# store location and gpg settings pass has already worked out are handed to
+# the pass-provision binary instead of being re-derived.
+
+PASS_PROVISION_BIN="${PASS_PROVISION_BIN:-pass-provision}"
+
+command -v "$PASS_PROVISION_BIN" >/dev/null 2>&1 ||
+	die "Error: $PASS_PROVISION_BIN is not installed or not in PATH."
+
+exec env \
+	PASS_PROVISION_EXTENSION=1 \
+	PREFIX="$PREFIX" \
+	GPG="$GPG" \
+	GPG_OPTS="$(printf '%s\n' "${GPG_OPTS[@]}")" \
+	PASSWORD_STORE_GPG_OPTS="$PASSWORD_STORE_GPG_OPTS" \
+	PASSWORD_STORE_SIGNING_KEY="$PASSWORD_STORE_SIGNING_KEY" \
+	"$PASS_PROVISION_BIN" "$@"
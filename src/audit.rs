@@ -0,0 +1,441 @@
+use crate::config::Config;
+use crate::key;
+use crate::key::backend::KeyBackend;
+use crate::key::graph::TrustGraph;
+use crate::prompt;
+use crate::store;
+use serde_json::json;
+use serde_json::Value;
+use sha2::Digest;
+use sha2::Sha256;
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+pub const STORE_LOG_PATH: &str = ".keys/audit.log";
+const LOCAL_LOG_NAME: &str = "audit.log";
+const SIGNATURE_FIELD: &str = "signature";
+const PREVIOUS_FIELD: &str = "previous";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AuditLog {
+    Off,
+    Local,
+    Store,
+}
+
+impl AuditLog {
+    pub fn parse(mode: &str) -> Option<AuditLog> {
+        match mode {
+            "off" => Some(AuditLog::Off),
+            "local" => Some(AuditLog::Local),
+            "store" => Some(AuditLog::Store),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AuditLog::Off => "off",
+            AuditLog::Local => "local",
+            AuditLog::Store => "store",
+        }
+    }
+}
+
+pub struct Event {
+    action: &'static str,
+    target: Option<String>,
+    identity: Option<String>,
+    trusted_by: Vec<String>,
+    paths: Vec<String>,
+    reason: Option<String>,
+}
+
+impl Event {
+    pub fn new(action: &'static str) -> Event {
+        Event {
+            action,
+            target: None,
+            identity: None,
+            trusted_by: Vec::new(),
+            paths: Vec::new(),
+            reason: None,
+        }
+    }
+
+    pub fn target(mut self, fingerprint: &str) -> Event {
+        self.target = Some(fingerprint.to_string());
+        self
+    }
+
+    pub fn identity(mut self, identity: &str) -> Event {
+        self.identity = Some(identity.to_string());
+        self
+    }
+
+    pub fn trusted_by(mut self, signers: Vec<String>) -> Event {
+        self.trusted_by = signers;
+        self
+    }
+
+    pub fn path(mut self, path: &str) -> Event {
+        self.paths.push(path.to_string());
+        self
+    }
+
+    pub fn reason(mut self, reason: &str) -> Event {
+        self.reason = Some(reason.to_string());
+        self
+    }
+}
+
+pub struct VerifiedEntry {
+    entry: Value,
+    error: Option<String>,
+}
+
+impl VerifiedEntry {
+    pub fn get_entry(&self) -> &Value {
+        &self.entry
+    }
+
+    pub fn get_error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+pub fn log_path(config: &Config) -> Result<Option<PathBuf>, String> {
+    match config.get_audit_log() {
+        AuditLog::Off => Ok(None),
+        AuditLog::Store => Ok(Some(key::get_pass_dir().join(STORE_LOG_PATH))),
+        AuditLog::Local => {
+            let data_dir = dirs::data_dir();
+            if data_dir.is_none() {
+                return Err("Unable to locate a data directory for the audit log".to_string());
+            }
+            Ok(Some(
+                data_dir
+                    .unwrap()
+                    .join("pass-provision")
+                    .join(LOCAL_LOG_NAME),
+            ))
+        }
+    }
+}
+
+/// Starts an audited action. Answers given before it don't belong to it, so
+/// they're dropped rather than recorded with its entry.
+pub fn begin() {
+    prompt::clear_answers();
+}
+
+pub fn record(config: &Config, event: Event) -> Result<(), String> {
+    let answers = prompt::take_answers();
+    let path = log_path(config)?;
+    if path.is_none() {
+        return Ok(());
+    }
+    let path = path.unwrap();
+
+    let res = append_entry(config, &path, event, answers);
+    if let Err(err) = &res {
+        return Err(format!(
+            "Unable to write audit log {}: {}",
+            path.display(),
+            err
+        ));
+    }
+    Ok(())
+}
+
+/// The devices whose entries are accepted: this device and every device key
+/// in `.keys/` it trusts through a chain of signatures.
+pub fn trusted_devices(backend: &mut dyn KeyBackend, config: &Config) -> BTreeSet<String> {
+    let own_key = key::get_key(backend, config.get_default_key());
+    if own_key.is_none() {
+        return BTreeSet::new();
+    }
+    let own_fingerprint = own_key.unwrap().get_fingerprint().to_string();
+    TrustGraph::load(backend, &own_fingerprint).reachable_from(&own_fingerprint)
+}
+
+fn append_entry(
+    config: &Config,
+    path: &Path,
+    event: Event,
+    answers: Vec<(String, String)>,
+) -> Result<(), String> {
+    let mut contents = if store::exists(path) {
+        let read_res = store::read_to_string(path);
+        if let Err(err) = &read_res {
+            return Err(err.to_string());
+        }
+        read_res.unwrap()
+    } else {
+        let create_res = store::create_dir_all(path.parent().unwrap());
+        if let Err(err) = &create_res {
+            return Err(err.to_string());
+        }
+        String::new()
+    };
+    let lines: Vec<&str> = contents.lines().filter(|line| !line.is_empty()).collect();
+    let seq = lines.len() + 1;
+    let previous = lines.last().map(|line| hash_line(line));
+
+    let mut backend = key::backend::open_default()?;
+    let device = key::get_key(backend.as_mut(), config.get_default_key());
+    if device.is_none() {
+        return Err(format!(
+            "Unable to find key: {} in keyring",
+            config.get_default_key()
+        ));
+    }
+    let device = device.unwrap().get_fingerprint().to_string();
+
+    let answers: Vec<Value> = answers
+        .iter()
+        .map(|(question, answer)| json!({ "question": question, "answer": answer }))
+        .collect();
+    let mut entry = json!({
+        "seq": seq,
+        "timestamp": timestamp(),
+        "device": device,
+        "action": event.action,
+        "target": event.target,
+        "identity": event.identity,
+        "trusted-by": event.trusted_by,
+        "paths": event.paths,
+        "reason": event.reason,
+        "answers": answers,
+        PREVIOUS_FIELD: previous,
+    });
+
+    backend.add_signer(&device)?;
+    let signature = backend.sign_detached(entry.to_string().as_bytes())?;
+    entry[SIGNATURE_FIELD] = Value::from(to_hex(&signature));
+
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    contents += &entry.to_string();
+    contents.push('\n');
+    let write_res = store::write(path, contents);
+    if let Err(err) = &write_res {
+        return Err(err.to_string());
+    }
+    Ok(())
+}
+
+pub fn verify_log(
+    backend: &mut dyn KeyBackend,
+    path: &Path,
+    trusted: &BTreeSet<String>,
+) -> Result<Vec<VerifiedEntry>, String> {
+    let contents = store::read_to_string(path);
+    if let Err(err) = &contents {
+        return Err(format!(
+            "Unable to read audit log {}: {}",
+            path.display(),
+            err
+        ));
+    }
+
+    let mut entries = Vec::new();
+    let mut previous = None;
+    for (index, line) in contents.unwrap().lines().enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+        let entry = serde_json::from_str::<Value>(line);
+        let expected_previous = previous.replace(hash_line(line));
+        if entry.is_err() {
+            entries.push(VerifiedEntry {
+                entry: Value::from(line),
+                error: Some(format!("line {} isn't a valid entry", index + 1)),
+            });
+            continue;
+        }
+        let entry = entry.unwrap();
+        let error = verify_entry(
+            backend,
+            &entry,
+            entries.len() + 1,
+            expected_previous,
+            trusted,
+        );
+        entries.push(VerifiedEntry {
+            entry,
+            error: error.err(),
+        });
+    }
+    Ok(entries)
+}
+
+fn verify_entry(
+    backend: &mut dyn KeyBackend,
+    entry: &Value,
+    expected_seq: usize,
+    expected_previous: Option<String>,
+    trusted: &BTreeSet<String>,
+) -> Result<(), String> {
+    if entry[PREVIOUS_FIELD].as_str() != expected_previous.as_deref() {
+        if expected_previous.is_none() {
+            return Err("entry follows one that's missing from the log".to_string());
+        }
+        return Err("entry doesn't follow the one before it".to_string());
+    }
+    if entry["seq"].as_u64() != Some(expected_seq as u64) {
+        return Err(format!(
+            "expected entry {}, found {}",
+            expected_seq, entry["seq"]
+        ));
+    }
+    let device = entry["device"].as_str();
+    if device.is_none() {
+        return Err("entry doesn't name a device".to_string());
+    }
+    if !trusted
+        .iter()
+        .any(|fingerprint| fingerprint.eq_ignore_ascii_case(device.unwrap()))
+    {
+        return Err(format!("{} isn't a trusted device", device.unwrap()));
+    }
+    let signature = entry[SIGNATURE_FIELD].as_str().and_then(from_hex);
+    if signature.is_none() {
+        return Err("entry isn't signed".to_string());
+    }
+
+    let mut signed = entry.clone();
+    signed.as_object_mut().unwrap().remove(SIGNATURE_FIELD);
+    let signers = backend.verify_detached(&signature.unwrap(), signed.to_string().as_bytes())?;
+    if !signers
+        .iter()
+        .any(|signer| signer.eq_ignore_ascii_case(device.unwrap()))
+    {
+        return Err(format!(
+            "signature isn't a valid one by {}",
+            device.unwrap()
+        ));
+    }
+    Ok(())
+}
+
+pub fn describe(verified: &VerifiedEntry) -> String {
+    let entry = &verified.entry;
+    let status = if let Some(error) = &verified.error {
+        format!("FAILED: {}", error)
+    } else {
+        "verified".to_string()
+    };
+    if !entry.is_object() {
+        return format!("{}\n    {}", entry, status);
+    }
+
+    let mut subject = Vec::new();
+    if entry["identity"].is_string() {
+        subject.push(format!("<{}>", entry["identity"].as_str().unwrap()));
+    }
+    if entry["target"].is_string() {
+        subject.push(format!("({})", entry["target"].as_str().unwrap()));
+    }
+    let mut description = format!(
+        "#{} {} {} {}",
+        entry["seq"],
+        entry["timestamp"].as_str().unwrap_or("?"),
+        entry["action"].as_str().unwrap_or("?"),
+        subject.join(" ")
+    );
+    description = description.trim_end().to_string();
+    description += &format!(
+        " by {}: {}",
+        entry["device"].as_str().unwrap_or("?"),
+        status
+    );
+
+    let list = |field: &str| -> Vec<String> {
+        entry[field]
+            .as_array()
+            .map(|values| {
+                values
+                    .iter()
+                    .filter_map(|value| value.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let paths = list("paths");
+    if !paths.is_empty() {
+        description += &format!("\n    paths: {}", paths.join(", "));
+    }
+    let trusted_by = list("trusted-by");
+    if !trusted_by.is_empty() {
+        description += &format!("\n    trusted by: {}", trusted_by.join(", "));
+    }
+    if entry["reason"].is_string() {
+        description += &format!("\n    reason: {}", entry["reason"].as_str().unwrap());
+    }
+    for answer in entry["answers"].as_array().unwrap_or(&Vec::new()) {
+        description += &format!(
+            "\n    answered: {} > {}",
+            answer["question"].as_str().unwrap_or("?"),
+            answer["answer"].as_str().unwrap_or("?")
+        );
+    }
+    description
+}
+
+fn timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    let days = (secs / 86400) as i64;
+    let time = secs % 86400;
+
+    // Civil date from days since the epoch (Howard Hinnant's algorithm).
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+fn hash_line(line: &str) -> String {
+    to_hex(&Sha256::digest(line.as_bytes()))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    let mut bytes = Vec::new();
+    for index in (0..hex.len()).step_by(2) {
+        let byte = u8::from_str_radix(hex.get(index..index + 2)?, 16);
+        if byte.is_err() {
+            return None;
+        }
+        bytes.push(byte.unwrap());
+    }
+    Some(bytes)
+}
@@ -0,0 +1,838 @@
+use crate::config;
+use crate::key::backend;
+use crate::prompt;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+
+pub const PROGRAM_NAME: &str = "pass-provision";
+pub const EXTENSION_NAME: &str = "provision";
+pub const FORMATS: &[&str] = &["text", "json", "dot"];
+pub const SHELLS: &[&str] = &["bash", "zsh", "fish"];
+pub const CONFIG_ACTIONS: &[&str] = &["show", "get", "set", "validate"];
+
+pub struct Flag {
+    pub long: &'static str,
+    pub short: Option<char>,
+    pub value: Option<&'static str>,
+    pub help: &'static str,
+}
+
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub args: &'static str,
+    pub min_args: usize,
+    pub max_args: Option<usize>,
+    pub about: &'static str,
+    pub flags: &'static [Flag],
+}
+
+pub const GLOBAL_FLAGS: &[Flag] = &[
+    Flag {
+        long: "store",
+        short: None,
+        value: Some("dir"),
+        help: "Password store to operate on",
+    },
+    Flag {
+        long: "config",
+        short: None,
+        value: Some("file"),
+        help: "Config file to read instead of the default",
+    },
+    Flag {
+        long: "gnupghome",
+        short: None,
+        value: Some("dir"),
+        help: "GnuPG home directory to use",
+    },
+    Flag {
+        long: "backend",
+        short: None,
+        value: Some("name"),
+        help: "Key backend to use (gpgme, or sequoia when built with it)",
+    },
+    Flag {
+        long: "profile",
+        short: None,
+        value: Some("name"),
+        help: "Config profile to use instead of matching by store path",
+    },
+    Flag {
+        long: "format",
+        short: None,
+        value: Some("text|json|dot"),
+        help: "Output format",
+    },
+    Flag {
+        long: "yes",
+        short: Some('y'),
+        value: None,
+        help: "Answer yes to every prompt",
+    },
+    Flag {
+        long: "prompter",
+        short: None,
+        value: Some("terminal|yes|no|json-rpc"),
+        help: "How to answer prompts",
+    },
+    Flag {
+        long: "answers",
+        short: None,
+        value: Some("file"),
+        help: "Answer prompts from a file, one answer per line",
+    },
+    Flag {
+        long: "help",
+        short: Some('h'),
+        value: None,
+        help: "Show usage",
+    },
+];
+
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "sync",
+        args: "",
+        min_args: 0,
+        max_args: Some(0),
+        about: "Import, sign and export the device keys in .keys/ (default command)",
+        flags: &[Flag {
+            long: "prune",
+            short: None,
+            value: None,
+            help: "Rewrite exported keys even if that drops published signatures",
+        }],
+    },
+    CommandSpec {
+        name: "gpg-add",
+        args: "[gpg-id...]",
+        min_args: 0,
+        max_args: None,
+        about: "Add recipients to a .gpg-id file and re-encrypt (defaults to the default key)",
+        flags: &[Flag {
+            long: "path",
+            short: Some('p'),
+            value: Some("subfolder"),
+            help: "Subfolder whose .gpg-id should be edited",
+        }],
+    },
+    CommandSpec {
+        name: "reencrypt",
+        args: "[subfolder...]",
+        min_args: 0,
+        max_args: None,
+        about: "Re-encrypt the store or subfolders for their current .gpg-id",
+        flags: &[],
+    },
+    CommandSpec {
+        name: "apply-policy",
+        args: "",
+        min_args: 0,
+        max_args: Some(0),
+        about: "Write the .gpg-id files .keys/policy.toml asks for and re-encrypt them",
+        flags: &[Flag {
+            long: "dry-run",
+            short: Some('n'),
+            value: None,
+            help: "Only show the .gpg-id changes",
+        }],
+    },
+    CommandSpec {
+        name: "init",
+        args: "",
+        min_args: 0,
+        max_args: Some(0),
+        about: "Pick or generate this device's key, write the config and export the key",
+        flags: &[
+            Flag {
+                long: "key",
+                short: None,
+                value: Some("fingerprint"),
+                help: "Use an existing secret key",
+            },
+            Flag {
+                long: "generate",
+                short: None,
+                value: None,
+                help: "Generate a new key through gpgme",
+            },
+            Flag {
+                long: "name",
+                short: None,
+                value: Some("name"),
+                help: "Real name for the generated key",
+            },
+            Flag {
+                long: "email",
+                short: None,
+                value: Some("email"),
+                help: "Email address for the generated key",
+            },
+            Flag {
+                long: "algo",
+                short: None,
+                value: Some("primary[/subkey]"),
+                help: "Key algorithms (default: ed25519/cv25519)",
+            },
+            Flag {
+                long: "expire",
+                short: None,
+                value: Some("period"),
+                help: "Key expiry like 2y, 6m, 30d or never (default: never)",
+            },
+            Flag {
+                long: "no-passphrase",
+                short: None,
+                value: None,
+                help: "Don't protect the generated key with a passphrase",
+            },
+            Flag {
+                long: "add-gpg-id",
+                short: None,
+                value: None,
+                help: "Add the key to the root .gpg-id and re-encrypt",
+            },
+        ],
+    },
+    CommandSpec {
+        name: "init-store",
+        args: "[path]",
+        min_args: 0,
+        max_args: Some(1),
+        about: "Create a new password store for the default key",
+        flags: &[
+            Flag {
+                long: "fingerprint",
+                short: None,
+                value: None,
+                help: "Write the key fingerprint to .gpg-id instead of its email",
+            },
+            Flag {
+                long: "git",
+                short: None,
+                value: None,
+                help: "Initialize a git repository with a first commit",
+            },
+        ],
+    },
+    CommandSpec {
+        name: "graph",
+        args: "",
+        min_args: 0,
+        max_args: Some(0),
+        about: "Print the web-of-trust between device keys (dot or json)",
+        flags: &[],
+    },
+    CommandSpec {
+        name: "access",
+        args: "",
+        min_args: 0,
+        max_args: Some(0),
+        about: "Show which devices can read which folders (text or json)",
+        flags: &[
+            Flag {
+                long: "who",
+                short: None,
+                value: Some("id"),
+                help: "Only show this identity, fingerprint, key id or @person",
+            },
+            Flag {
+                long: "path",
+                short: Some('p'),
+                value: Some("subfolder"),
+                help: "Only show this subfolder and the folders below it",
+            },
+            Flag {
+                long: "check",
+                short: None,
+                value: None,
+                help: "Decrypt every password and compare its recipients with .gpg-id",
+            },
+        ],
+    },
+    CommandSpec {
+        name: "lint",
+        args: "",
+        min_args: 0,
+        max_args: Some(0),
+        about: "Check .gpg-id files for unknown, ambiguous or unusable recipients (text or json)",
+        flags: &[Flag {
+            long: "fix",
+            short: None,
+            value: None,
+            help: "Remove duplicate entries and stray whitespace, and export missing keys",
+        }],
+    },
+    CommandSpec {
+        name: "reconcile",
+        args: "",
+        min_args: 0,
+        max_args: Some(0),
+        about:
+            "Compare .keys/ with the keys .gpg-id files use and retire unused ones (text or json)",
+        flags: &[],
+    },
+    CommandSpec {
+        name: "compromise",
+        args: "<fingerprint>",
+        min_args: 1,
+        max_args: Some(1),
+        about: "Remove a lost device's key everywhere, re-encrypt the store and block the key",
+        flags: &[Flag {
+            long: "revocation-cert",
+            short: None,
+            value: Some("file"),
+            help: "Import this revocation certificate for the key first",
+        }],
+    },
+    CommandSpec {
+        name: "audit",
+        args: "",
+        min_args: 0,
+        max_args: Some(0),
+        about: "Verify and print the audit log (text or json)",
+        flags: &[],
+    },
+    CommandSpec {
+        name: "config",
+        args: "<show|get|set|validate> [key] [value]",
+        min_args: 1,
+        max_args: Some(3),
+        about: "Show, read, change or check the config (honors --profile)",
+        flags: &[],
+    },
+    CommandSpec {
+        name: "completions",
+        args: "<bash|zsh|fish>",
+        min_args: 1,
+        max_args: Some(1),
+        about: "Print a shell completion script",
+        flags: &[],
+    },
+    CommandSpec {
+        name: "help",
+        args: "[command]",
+        min_args: 0,
+        max_args: Some(1),
+        about: "Show usage for pass-provision or one of its commands",
+        flags: &[],
+    },
+];
+
+pub struct Matches {
+    command: &'static CommandSpec,
+    values: BTreeMap<&'static str, String>,
+    switches: BTreeSet<&'static str>,
+    positionals: Vec<String>,
+}
+
+impl Matches {
+    pub fn command(&self) -> &'static str {
+        self.command.name
+    }
+
+    pub fn value(&self, long: &str) -> Option<&str> {
+        self.values.get(long).map(String::as_str)
+    }
+
+    pub fn has(&self, long: &str) -> bool {
+        self.switches.contains(long)
+    }
+
+    pub fn positionals(&self) -> &[String] {
+        &self.positionals
+    }
+
+    pub fn positional(&self, index: usize) -> Option<&str> {
+        self.positionals.get(index).map(String::as_str)
+    }
+}
+
+pub fn find_command(name: &str) -> Option<&'static CommandSpec> {
+    COMMANDS.iter().find(|spec| spec.name == name)
+}
+
+pub fn parse_args(args: &[String]) -> Result<Matches, String> {
+    let mut command = None;
+    let mut values = BTreeMap::new();
+    let mut switches = BTreeSet::new();
+    let mut positionals = Vec::new();
+    let mut only_positionals = false;
+
+    let mut index = 0;
+    while index < args.len() {
+        let arg = &args[index];
+        index += 1;
+
+        if only_positionals || !arg.starts_with('-') || arg == "-" {
+            if command.is_none() {
+                let spec = find_command(arg);
+                if spec.is_none() {
+                    return Err(format!("Unknown command: {}", arg));
+                }
+                command = spec;
+            } else {
+                positionals.push(arg.clone());
+            }
+            continue;
+        }
+        if arg == "--" {
+            only_positionals = true;
+            continue;
+        }
+
+        let long_body = arg.strip_prefix("--");
+        let (name, inline_value) = match long_body {
+            Some(body) => match body.find('=') {
+                Some(pos) => (&body[..pos], Some(body[pos + 1..].to_string())),
+                None => (body, None),
+            },
+            None => (&arg[1..], None),
+        };
+
+        let flag = find_flag(command.map(|spec| spec.flags), name, long_body.is_some());
+        if flag.is_none() {
+            return Err(format!("Unknown flag: {}", arg));
+        }
+        let flag = flag.unwrap();
+
+        if flag.value.is_none() {
+            if inline_value.is_some() {
+                return Err(format!("Flag --{} doesn't take a value", flag.long));
+            }
+            switches.insert(flag.long);
+            continue;
+        }
+
+        let value = if let Some(inline_value) = inline_value {
+            inline_value
+        } else if index < args.len() {
+            index += 1;
+            args[index - 1].clone()
+        } else {
+            return Err(format!("Flag --{} requires a value", flag.long));
+        };
+        values.insert(flag.long, value);
+    }
+
+    let command = command.unwrap_or_else(|| find_command("sync").unwrap());
+    if positionals.len() < command.min_args
+        || (command.max_args.is_some() && positionals.len() > command.max_args.unwrap())
+    {
+        return Err(format!(
+            "Wrong number of arguments\nUsage: {}",
+            command_usage_line(command)
+        ));
+    }
+
+    let format = values.get("format");
+    if let Some(format) = format.filter(|format| !FORMATS.contains(&format.as_str())) {
+        return Err(format!(
+            "Unknown format: {}, expected one of: {}",
+            format,
+            FORMATS.join(", ")
+        ));
+    }
+
+    let prompter = values.get("prompter");
+    if let Some(prompter) =
+        prompter.filter(|prompter| !prompt::PROMPTERS.contains(&prompter.as_str()))
+    {
+        return Err(format!(
+            "Unknown prompter: {}, expected one of: {}",
+            prompter,
+            prompt::PROMPTERS.join(", ")
+        ));
+    }
+
+    Ok(Matches {
+        command,
+        values,
+        switches,
+        positionals,
+    })
+}
+
+fn find_flag(
+    command_flags: Option<&'static [Flag]>,
+    name: &str,
+    is_long: bool,
+) -> Option<&'static Flag> {
+    let command_flags = command_flags.unwrap_or(&[]);
+    for flag in GLOBAL_FLAGS.iter().chain(command_flags.iter()) {
+        let matches = if is_long {
+            flag.long == name
+        } else {
+            let mut chars = name.chars();
+            flag.short.is_some() && chars.next() == flag.short && chars.next().is_none()
+        };
+        if matches {
+            return Some(flag);
+        }
+    }
+    None
+}
+
+fn flag_usage(flag: &Flag) -> String {
+    let mut usage = match flag.short {
+        Some(short) => format!("-{}, --{}", short, flag.long),
+        None => format!("    --{}", flag.long),
+    };
+    if let Some(value) = &flag.value {
+        usage += &format!(" <{}>", value);
+    }
+    usage
+}
+
+fn flags_usage(flags: &[Flag]) -> String {
+    let mut usage = String::new();
+    for flag in flags {
+        usage += &format!("  {:<32}{}\n", flag_usage(flag), flag.help);
+    }
+    usage
+}
+
+fn command_usage_line(command: &CommandSpec) -> String {
+    let mut line = format!("{} {}", PROGRAM_NAME, command.name);
+    if !command.flags.is_empty() {
+        line += " [flags]";
+    }
+    if !command.args.is_empty() {
+        line += &format!(" {}", command.args);
+    }
+    line
+}
+
+pub fn usage(command: Option<&str>) -> Result<String, String> {
+    if let Some(command) = command {
+        let spec = find_command(command);
+        if spec.is_none() {
+            return Err(format!("Unknown command: {}", command));
+        }
+        let spec = spec.unwrap();
+        let mut usage = format!("Usage: {}\n\n{}\n", command_usage_line(spec), spec.about);
+        if !spec.flags.is_empty() {
+            usage += &format!("\nFlags:\n{}", flags_usage(spec.flags));
+        }
+        usage += &format!("\nGlobal flags:\n{}", flags_usage(GLOBAL_FLAGS));
+        return Ok(usage);
+    }
+
+    let mut usage = format!(
+        "Usage: {} [global flags] [command] [flags] [args]\n\
+         \x20      pass {} [command] [flags] [args]\n\nCommands:\n",
+        PROGRAM_NAME, EXTENSION_NAME
+    );
+    for spec in COMMANDS {
+        usage += &format!("  {:<32}{}\n", spec.name, spec.about);
+    }
+    usage += &format!("\nGlobal flags:\n{}", flags_usage(GLOBAL_FLAGS));
+    usage += &format!(
+        "\nRun '{} help <command>' for the flags of a command.\n",
+        PROGRAM_NAME
+    );
+    Ok(usage)
+}
+
+fn long_flags(flags: &[Flag]) -> Vec<String> {
+    flags
+        .iter()
+        .map(|flag| format!("--{}", flag.long))
+        .collect()
+}
+
+fn command_names() -> Vec<&'static str> {
+    COMMANDS.iter().map(|spec| spec.name).collect()
+}
+
+pub fn completions(shell: &str) -> Result<String, String> {
+    match shell {
+        "bash" => Ok(bash_completions()),
+        "zsh" => Ok(zsh_completions()),
+        "fish" => Ok(fish_completions()),
+        _ => Err(format!(
+            "Unknown shell: {}, expected one of: {}",
+            shell,
+            SHELLS.join(", ")
+        )),
+    }
+}
+
+fn bash_completions() -> String {
+    let mut cases = String::new();
+    for spec in COMMANDS {
+        let mut words = long_flags(spec.flags);
+        if spec.name == "completions" {
+            words.extend(SHELLS.iter().map(|shell| shell.to_string()));
+        } else if spec.name == "help" {
+            words.extend(command_names().iter().map(|name| name.to_string()));
+        } else if spec.name == "config" {
+            words.extend(CONFIG_ACTIONS.iter().map(|action| action.to_string()));
+            words.extend(config::SETTINGS.iter().map(|key| key.to_string()));
+        }
+        cases += &format!("\t\t{}) words=\"{}\" ;;\n", spec.name, words.join(" "));
+    }
+
+    format!(
+        r#"# bash completion for {program}
+# Works both for the standalone binary and as `pass {ext}`.
+
+__pass_provision_complete() {{
+	local offset="$1"
+	local cur="${{COMP_WORDS[COMP_CWORD]}}"
+	local prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+	local global="{global}"
+	case "$prev" in
+		--store|--gnupghome) COMPREPLY+=($(compgen -d -- "$cur")); return ;;
+		--config|--answers) COMPREPLY+=($(compgen -f -- "$cur")); return ;;
+		--format) COMPREPLY+=($(compgen -W "{formats}" -- "$cur")); return ;;
+		--backend) COMPREPLY+=($(compgen -W "{backends}" -- "$cur")); return ;;
+		--prompter) COMPREPLY+=($(compgen -W "{prompters}" -- "$cur")); return ;;
+	esac
+
+	local cmd="" i
+	for ((i = offset; i < COMP_CWORD; i++)); do
+		case "${{COMP_WORDS[i]}}" in
+			{value_flags}) ((i++)) ;;
+			-*) ;;
+			*) cmd="${{COMP_WORDS[i]}}"; break ;;
+		esac
+	done
+
+	if [[ -z $cmd ]]; then
+		COMPREPLY+=($(compgen -W "{commands} $global" -- "$cur"))
+		return
+	fi
+
+	local words=""
+	case "$cmd" in
+{cases}	esac
+	COMPREPLY+=($(compgen -W "$words $global" -- "$cur"))
+}}
+
+_pass_provision() {{
+	COMPREPLY=()
+	__pass_provision_complete 1
+}}
+complete -F _pass_provision {program}
+
+# Hook into pass's extension completion.
+__password_store_extension_complete_{ext}() {{
+	__pass_provision_complete 2
+}}
+PASSWORD_STORE_EXTENSION_COMMANDS+=({ext})
+"#,
+        program = PROGRAM_NAME,
+        ext = EXTENSION_NAME,
+        global = long_flags(GLOBAL_FLAGS).join(" "),
+        formats = FORMATS.join(" "),
+        backends = backend::backends().join(" "),
+        prompters = prompt::PROMPTERS.join(" "),
+        commands = command_names().join(" "),
+        value_flags = value_flags().join("|"),
+        cases = cases,
+    )
+}
+
+fn value_flags() -> Vec<String> {
+    let mut flags = Vec::new();
+    let command_flags = COMMANDS.iter().flat_map(|spec| spec.flags.iter());
+    for flag in GLOBAL_FLAGS.iter().chain(command_flags) {
+        if flag.value.is_none() {
+            continue;
+        }
+        let long = format!("--{}", flag.long);
+        if !flags.contains(&long) {
+            flags.push(long);
+        }
+        if let Some(short) = &flag.short {
+            flags.push(format!("-{}", short));
+        }
+    }
+    flags
+}
+
+fn zsh_describe(items: &[(&str, &str)]) -> String {
+    let mut described = Vec::new();
+    for (name, help) in items {
+        described.push(format!("'{}:{}'", name, help.replace('\'', "")));
+    }
+    described.join(" ")
+}
+
+fn zsh_flag_specs(flags: &[Flag]) -> String {
+    let mut specs = Vec::new();
+    for flag in flags {
+        let value = if let Some(value) = &flag.value {
+            format!(":{}:", value)
+        } else {
+            String::new()
+        };
+        specs.push(format!(
+            "'--{}[{}]{}'",
+            flag.long,
+            flag.help.replace('\'', ""),
+            value
+        ));
+    }
+    specs.join(" ")
+}
+
+fn zsh_completions() -> String {
+    let commands: Vec<(&str, &str)> = COMMANDS
+        .iter()
+        .map(|spec| (spec.name, spec.about))
+        .collect();
+    let mut cases = String::new();
+    for spec in COMMANDS {
+        let extra = if spec.name == "completions" {
+            format!(" '1:shell:({})'", SHELLS.join(" "))
+        } else if spec.name == "help" {
+            format!(" '1:command:({})'", command_names().join(" "))
+        } else if spec.name == "config" {
+            format!(
+                " '1:action:({})' '2:key:({})'",
+                CONFIG_ACTIONS.join(" "),
+                config::SETTINGS.join(" ")
+            )
+        } else if spec.name == "reencrypt" || spec.name == "gpg-add" || spec.name == "init-store" {
+            " '*:argument:_files'".to_string()
+        } else {
+            String::new()
+        };
+        cases += &format!(
+            "\t\t\t{}) _arguments $global {}{} ;;\n",
+            spec.name,
+            zsh_flag_specs(spec.flags),
+            extra
+        );
+    }
+
+    format!(
+        r#"#compdef {program}
+# zsh completion for {program}
+# Also usable as `pass {ext}` through _pass_cmd_{ext}.
+
+_pass_provision_commands() {{
+	local -a commands
+	commands=({commands})
+	_describe -t commands '{program} command' commands
+}}
+
+_{program_ident}() {{
+	local -a global
+	global=({global})
+	local curcontext="$curcontext" state line
+	_arguments -C $global '1: :_pass_provision_commands' '*:: :->args'
+	case $state in
+		args)
+			case $words[1] in
+{cases}			esac
+			;;
+	esac
+}}
+
+_pass_cmd_{ext}() {{
+	_{program_ident} "$@"
+}}
+
+if [[ $zsh_eval_context[-1] == loadautofunc ]]; then
+	_{program_ident} "$@"
+else
+	compdef _{program_ident} {program}
+fi
+"#,
+        program = PROGRAM_NAME,
+        program_ident = PROGRAM_NAME.replace('-', "_"),
+        ext = EXTENSION_NAME,
+        commands = zsh_describe(&commands),
+        global = zsh_flag_specs(GLOBAL_FLAGS),
+        cases = cases,
+    )
+}
+
+fn fish_completions() -> String {
+    let mut script = format!(
+        "# fish completion for {program}\n\
+         # Completes both `{program}` and `pass {ext}`.\n\n\
+         function __fish_pass_provision_needs_command\n\
+         \tset -l cmd (commandline -opc)\n\
+         \tif test \"$cmd[1]\" = pass\n\
+         \t\tset -e cmd[1]\n\
+         \tend\n\
+         \tset -e cmd[1]\n\
+         \tfor word in $cmd\n\
+         \t\tif not string match -q -- '-*' $word\n\
+         \t\t\treturn 1\n\
+         \t\tend\n\
+         \tend\n\
+         \treturn 0\n\
+         end\n\n\
+         function __fish_pass_provision_using_command\n\
+         \tset -l cmd (commandline -opc)\n\
+         \tif test \"$cmd[1]\" = pass\n\
+         \t\tset -e cmd[1]\n\
+         \tend\n\
+         \tcontains -- $argv[1] $cmd[2..-1]\n\
+         end\n\n\
+         complete -c pass -f -n '__fish_pass_needs_command' -a {ext} -d 'Manage device keys for a multi-device store'\n",
+        program = PROGRAM_NAME,
+        ext = EXTENSION_NAME,
+    );
+
+    let targets = [
+        format!("-c {}", PROGRAM_NAME),
+        format!("-c pass -n '__fish_pass_uses_command {}'", EXTENSION_NAME),
+    ];
+    for target in &targets {
+        script += "\n";
+        for flag in GLOBAL_FLAGS {
+            script += &fish_flag(target, None, flag);
+        }
+        for spec in COMMANDS {
+            script += &format!(
+                "complete {} -f -n '__fish_pass_provision_needs_command' -a {} -d '{}'\n",
+                target,
+                spec.name,
+                spec.about.replace('\'', "")
+            );
+            for flag in spec.flags {
+                script += &fish_flag(target, Some(spec.name), flag);
+            }
+        }
+        script += &format!(
+            "complete {} -f -n '__fish_pass_provision_using_command completions' -a '{}'\n",
+            target,
+            SHELLS.join(" ")
+        );
+        script += &format!(
+            "complete {} -f -n '__fish_pass_provision_using_command help' -a '{}'\n",
+            target,
+            command_names().join(" ")
+        );
+        script += &format!(
+            "complete {} -f -n '__fish_pass_provision_using_command config' -a '{} {}'\n",
+            target,
+            CONFIG_ACTIONS.join(" "),
+            config::SETTINGS.join(" ")
+        );
+    }
+
+    script
+}
+
+fn fish_flag(target: &str, command: Option<&str>, flag: &Flag) -> String {
+    let mut line = format!("complete {}", target);
+    if let Some(command) = command {
+        line += &format!(" -n '__fish_pass_provision_using_command {}'", command);
+    }
+    line += &format!(" -l {}", flag.long);
+    if let Some(short) = &flag.short {
+        line += &format!(" -s {}", short);
+    }
+    if flag.value.is_some() {
+        line += " -r";
+    }
+    line += &format!(" -d '{}'\n", flag.help.replace('\'', ""));
+    line
+}
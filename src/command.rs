@@ -1,25 +1,331 @@
-use std::io::Error;
-use std::io::ErrorKind;
-use std::io::Result;
+use std::cell::RefCell;
+use std::fmt;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::os::unix::process::CommandExt;
+use std::process::Child;
 use std::process::Command;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use std::time::Instant;
 
-pub fn oneshot_command(command: &str, args: &[&str]) -> Result<()> {
-    let cmd = Command::new(command).args(args).output();
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
 
-    if cmd.is_err() {
-        return Err(cmd.unwrap_err());
+thread_local! {
+    static RUNNER: RefCell<Option<Arc<dyn CommandRunner>>> = const { RefCell::new(None) };
+}
+
+pub trait CommandRunner: Send + Sync {
+    fn run(&self, invocation: &Invocation) -> Result<CommandOutput, CommandError>;
+}
+
+#[derive(Clone, Debug)]
+pub struct Invocation {
+    program: String,
+    args: Vec<String>,
+    timeout: Option<Duration>,
+    stream: bool,
+    interactive: bool,
+}
+
+impl Invocation {
+    pub fn new(program: &str, args: &[&str]) -> Invocation {
+        Invocation {
+            program: program.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            timeout: None,
+            stream: false,
+            interactive: false,
+        }
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Invocation {
+        self.timeout = Some(timeout);
+        self
     }
 
-    let cmd_output = cmd.unwrap();
-    if !cmd_output.status.success() {
-        return Err(Error::new(
-            ErrorKind::Other,
-            format!(
-                "Command: {} exited with status: {}",
-                command, cmd_output.status
+    pub fn stream(mut self, stream: bool) -> Invocation {
+        self.stream = stream;
+        self
+    }
+
+    pub fn interactive(mut self) -> Invocation {
+        self.interactive = true;
+        self.stream = true;
+        self
+    }
+
+    pub fn command_line(&self) -> String {
+        let mut line = self.program.clone();
+        for arg in &self.args {
+            line.push(' ');
+            line.push_str(arg);
+        }
+        line
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CommandOutput {
+    code: Option<i32>,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+}
+
+impl CommandOutput {
+    pub fn new(code: Option<i32>, stdout: Vec<u8>, stderr: Vec<u8>) -> CommandOutput {
+        CommandOutput {
+            code,
+            stdout,
+            stderr,
+        }
+    }
+
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+}
+
+#[derive(Debug)]
+pub enum CommandError {
+    Spawn {
+        command: String,
+        error: io::Error,
+    },
+    Failed {
+        command: String,
+        code: Option<i32>,
+        stderr: String,
+    },
+    TimedOut {
+        command: String,
+        timeout: Duration,
+    },
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CommandError::Spawn { command, error } => {
+                write!(f, "Unable to run command: {}: {}", command, error)
+            }
+            CommandError::Failed {
+                command,
+                code,
+                stderr,
+            } => {
+                match code {
+                    Some(code) => write!(f, "Command: {} exited with status: {}", command, code)?,
+                    None => write!(f, "Command: {} was killed by a signal", command)?,
+                }
+                let stderr = stderr.trim();
+                if !stderr.is_empty() {
+                    write!(f, "\n{}", stderr)?;
+                }
+                Ok(())
+            }
+            CommandError::TimedOut { command, timeout } => write!(
+                f,
+                "Command: {} timed out after {} seconds",
+                command,
+                timeout.as_secs_f32()
             ),
-        ));
+        }
+    }
+}
+
+pub struct SystemRunner;
+
+impl CommandRunner for SystemRunner {
+    fn run(&self, invocation: &Invocation) -> Result<CommandOutput, CommandError> {
+        let mut command = Command::new(&invocation.program);
+        command.args(&invocation.args);
+        if invocation.interactive {
+            command.stdin(Stdio::inherit());
+        } else {
+            command.stdin(Stdio::null());
+        }
+        command.stdout(Stdio::piped());
+        command.stderr(Stdio::piped());
+        if invocation.timeout.is_some() {
+            // Its own process group, so a timeout also kills whatever it spawned
+            command.process_group(0);
+        }
+
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            Err(error) => {
+                return Err(CommandError::Spawn {
+                    command: invocation.command_line(),
+                    error,
+                })
+            }
+        };
+
+        let stdout = child.stdout.take().unwrap();
+        let stderr = child.stderr.take().unwrap();
+        let stdout_reader = spawn_reader(stdout, invocation.stream, false);
+        let stderr_reader = spawn_reader(stderr, invocation.stream, true);
+
+        let status = match wait_for_child(&mut child, invocation.timeout) {
+            Ok(status) => status,
+            Err(error) => {
+                return Err(CommandError::Spawn {
+                    command: invocation.command_line(),
+                    error,
+                })
+            }
+        };
+        if status.is_none() {
+            // Don't join the readers, anything that escaped the kill may still
+            // hold the pipes open
+            return Err(CommandError::TimedOut {
+                command: invocation.command_line(),
+                timeout: invocation.timeout.unwrap(),
+            });
+        }
+        let stdout = stdout_reader.join().unwrap_or_default();
+        let stderr = stderr_reader.join().unwrap_or_default();
+
+        Ok(CommandOutput::new(status.unwrap().code(), stdout, stderr))
     }
+}
 
+fn spawn_reader<R: Read + Send + 'static>(
+    mut source: R,
+    stream: bool,
+    is_stderr: bool,
+) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut captured = Vec::new();
+        let mut buf = [0; 4096];
+        loop {
+            let read = source.read(&mut buf);
+            if read.is_err() || *read.as_ref().unwrap() == 0 {
+                break;
+            }
+            let chunk = &buf[..read.unwrap()];
+            if stream {
+                if is_stderr {
+                    let _ = io::stderr().write_all(chunk);
+                } else {
+                    let mut stdout = io::stdout();
+                    let _ = stdout.write_all(chunk);
+                    let _ = stdout.flush();
+                }
+            }
+            captured.extend_from_slice(chunk);
+        }
+        captured
+    })
+}
+
+fn wait_for_child(
+    child: &mut Child,
+    timeout: Option<Duration>,
+) -> io::Result<Option<std::process::ExitStatus>> {
+    if timeout.is_none() {
+        return child.wait().map(Some);
+    }
+
+    let deadline = Instant::now() + timeout.unwrap();
+    loop {
+        let status = child.try_wait()?;
+        if status.is_some() {
+            return Ok(status);
+        }
+        if Instant::now() >= deadline {
+            kill_process_group(child);
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(None);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn kill_process_group(child: &Child) {
+    let group = format!("-{}", child.id());
+    let _ = Command::new("kill")
+        .args(["-KILL", "--", &group])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+}
+
+pub fn current_runner() -> Arc<dyn CommandRunner> {
+    let runner = RUNNER.with(|runner| runner.borrow().clone());
+    if let Some(runner) = runner {
+        return runner;
+    }
+    Arc::new(SystemRunner)
+}
+
+pub fn set_runner(runner: Arc<dyn CommandRunner>) {
+    RUNNER.with(|current| *current.borrow_mut() = Some(runner));
+}
+
+pub fn run(invocation: &Invocation) -> Result<CommandOutput, CommandError> {
+    let output = current_runner().run(invocation)?;
+    if !output.success() {
+        return Err(CommandError::Failed {
+            command: invocation.command_line(),
+            code: output.code,
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        });
+    }
+    Ok(output)
+}
+
+pub fn oneshot_command(command: &str, args: &[&str]) -> Result<(), CommandError> {
+    run(&Invocation::new(command, args))?;
     Ok(())
 }
+
+pub fn output_command(command: &str, args: &[&str]) -> Result<Vec<u8>, CommandError> {
+    let output = run(&Invocation::new(command, args))?;
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+type FakeHandler = dyn Fn(&Invocation) -> Result<CommandOutput, CommandError> + Send + Sync;
+
+#[cfg(test)]
+pub struct FakeRunner {
+    handler: Box<FakeHandler>,
+    calls: std::sync::Mutex<Vec<Invocation>>,
+}
+
+#[cfg(test)]
+impl FakeRunner {
+    pub fn new<F>(handler: F) -> FakeRunner
+    where
+        F: Fn(&Invocation) -> Result<CommandOutput, CommandError> + Send + Sync + 'static,
+    {
+        FakeRunner {
+            handler: Box::new(handler),
+            calls: std::sync::Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn succeeding() -> FakeRunner {
+        FakeRunner::new(|_| Ok(CommandOutput::new(Some(0), Vec::new(), Vec::new())))
+    }
+
+    pub fn calls(&self) -> Vec<Invocation> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+impl CommandRunner for FakeRunner {
+    fn run(&self, invocation: &Invocation) -> Result<CommandOutput, CommandError> {
+        self.calls.lock().unwrap().push(invocation.clone());
+        (self.handler)(invocation)
+    }
+}
@@ -1,82 +1,718 @@
-use dirs;
+use crate::audit::AuditLog;
+use crate::cli;
+use crate::key;
+use crate::key::export::ExportMode;
+use crate::pass_env;
+use std::env;
+use std::fmt;
 use std::fs;
+use std::path::Path;
 use std::path::PathBuf;
-use toml::map::Map;
-use toml::Value;
+use toml_edit::DocumentMut;
+use toml_edit::ImDocument;
+use toml_edit::Item;
+use toml_edit::TableLike;
+use toml_edit::Value;
 
+pub const CONFIG_FILE_VAR_NAME: &str = "PASS_PROVISION_CONFIG";
+pub const PROFILE_VAR_NAME: &str = "PASS_PROVISION_PROFILE";
 const CONFIG_FILE_NAME: &str = "pass-provision.conf";
 const DEFAULT_KEY_KEY: &str = "default-key";
+const STORE_KEY: &str = "store";
+const GNUPGHOME_KEY: &str = "gnupghome";
+const BACKEND_KEY: &str = "backend";
+const KEYRING_KEY: &str = "keyring";
+const TRUST_THRESHOLD_KEY: &str = "trust.threshold";
+const TRUST_MAX_DEPTH_KEY: &str = "trust.max-depth";
+const EXPORT_MODE_KEY: &str = "export.mode";
+const GIT_COMMIT_KEY: &str = "git.commit";
+const GIT_PUSH_KEY: &str = "git.push";
+const REENCRYPT_JOBS_KEY: &str = "reencrypt.jobs";
+const OUTPUT_FORMAT_KEY: &str = "output.format";
+const AUDIT_LOG_KEY: &str = "audit.log";
+const PROFILE_KEY: &str = "profile";
+const DEFAULT_TRUST_THRESHOLD: usize = 1;
+const DEFAULT_REENCRYPT_JOBS: usize = 1;
+
+pub const SETTINGS: &[&str] = &[
+    DEFAULT_KEY_KEY,
+    STORE_KEY,
+    GNUPGHOME_KEY,
+    BACKEND_KEY,
+    KEYRING_KEY,
+    TRUST_THRESHOLD_KEY,
+    TRUST_MAX_DEPTH_KEY,
+    EXPORT_MODE_KEY,
+    GIT_COMMIT_KEY,
+    GIT_PUSH_KEY,
+    REENCRYPT_JOBS_KEY,
+    OUTPUT_FORMAT_KEY,
+    AUDIT_LOG_KEY,
+];
+
+#[derive(Debug)]
+pub struct ConfigError {
+    line: Option<usize>,
+    message: String,
+}
+
+impl ConfigError {
+    fn new(line: Option<usize>, message: String) -> ConfigError {
+        ConfigError { line, message }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(line) = &self.line {
+            write!(f, "line {}: {}", line, self.message)
+        } else {
+            write!(f, "{}", self.message)
+        }
+    }
+}
+
+/// What loading the config takes from the environment: the file, the
+/// selected profile, the store used to pick a profile and pass's signing keys.
+pub struct ConfigEnv {
+    file: PathBuf,
+    profile: Option<String>,
+    store: Option<PathBuf>,
+    signing_keys: Vec<String>,
+}
+
+impl ConfigEnv {
+    pub fn from_env() -> ConfigEnv {
+        ConfigEnv {
+            file: get_config_file_location(),
+            profile: env::var(PROFILE_VAR_NAME).ok(),
+            store: key::resolve_pass_dir(),
+            signing_keys: pass_env::signing_keys(),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn new(file: &Path) -> ConfigEnv {
+        ConfigEnv {
+            file: file.to_path_buf(),
+            profile: None,
+            store: None,
+            signing_keys: Vec::new(),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn profile(mut self, profile: &str) -> ConfigEnv {
+        self.profile = Some(profile.to_string());
+        self
+    }
+
+    #[cfg(test)]
+    pub fn store(mut self, store: &Path) -> ConfigEnv {
+        self.store = Some(store.to_path_buf());
+        self
+    }
+
+    pub fn get_file(&self) -> &Path {
+        &self.file
+    }
+}
+
+struct Entry {
+    key: String,
+    path: String,
+    value: Value,
+    line: Option<usize>,
+}
+
+struct ConfigFile {
+    settings: Vec<Entry>,
+    profiles: Vec<(String, Vec<Entry>)>,
+}
 
 pub struct Config {
     default_key: String,
+    profile: Option<String>,
+    store: Option<PathBuf>,
+    gnupghome: Option<PathBuf>,
+    backend: Option<String>,
+    keyring: Option<PathBuf>,
+    trust_threshold: usize,
+    trust_max_depth: Option<usize>,
+    export_mode: ExportMode,
+    git_commit: bool,
+    git_push: bool,
+    reencrypt_jobs: usize,
+    output_format: Option<String>,
+    audit_log: AuditLog,
+    signing_keys: Vec<String>,
 }
 
 impl Config {
     pub fn new(def_key_fingerprint: String) -> Config {
         Config {
             default_key: def_key_fingerprint,
+            profile: None,
+            store: None,
+            gnupghome: None,
+            backend: None,
+            keyring: None,
+            trust_threshold: DEFAULT_TRUST_THRESHOLD,
+            trust_max_depth: None,
+            export_mode: ExportMode::Full,
+            git_commit: false,
+            git_push: false,
+            reencrypt_jobs: DEFAULT_REENCRYPT_JOBS,
+            output_format: None,
+            audit_log: AuditLog::Off,
+            signing_keys: Vec::new(),
         }
     }
 
-    pub fn parse_config() -> Option<Config> {
-        let config_location = get_config_file_location();
-        if !config_location.exists() {
+    /// Keys .gpg-id files are signed with and must be verified against, which
+    /// `pass` takes from PASSWORD_STORE_SIGNING_KEY.
+    pub fn with_signing_keys(mut self, signing_keys: Vec<String>) -> Config {
+        self.signing_keys = signing_keys;
+        self
+    }
+
+    /// The config for `config_env`, or None when there's no usable config
+    /// yet and one should be set up.
+    pub fn parse_config(config_env: &ConfigEnv) -> Result<Option<Config>, Vec<String>> {
+        if !config_env.file.exists() {
             eprintln!("No config file found to parse");
-            return None;
+            return Ok(None);
         }
 
-        let file_contents = fs::read_to_string(config_location);
-        if file_contents.is_err() {
-            eprintln!("Unable to read config file");
-            return None;
+        let conf = read_config(config_env)?;
+        if conf.default_key.is_empty() {
+            eprintln!("Config doesn't set key: {}", DEFAULT_KEY_KEY);
+            return Ok(None);
         }
+        Ok(Some(conf))
+    }
+
+    pub fn from_toml(
+        contents: &str,
+        profile: Option<&str>,
+        store: Option<&Path>,
+    ) -> Result<Config, Vec<ConfigError>> {
+        let (config_file, mut errors) = parse_config_file(contents)?;
+
+        let mut conf = Config::new(String::new());
+        errors.extend(conf.apply_entries(&config_file.settings));
+        for (_, entries) in &config_file.profiles {
+            errors.extend(Config::new(String::new()).apply_entries(entries));
+        }
+        if !errors.is_empty() {
+            errors.sort_by_key(|err| err.line);
+            return Err(errors);
+        }
+
+        let selected = select_profile(&config_file, profile, store);
+        if selected.is_err() {
+            return Err(vec![selected.err().unwrap()]);
+        }
+        let selected = selected.unwrap();
+        if let Some(selected) = selected {
+            let (name, entries) = &config_file.profiles[selected];
+            conf.apply_entries(entries);
+            conf.profile = Some(name.clone());
+        }
+
+        Ok(conf)
+    }
 
-        let toml_value_res = file_contents.unwrap().parse::<Value>();
-        if toml_value_res.is_err() {
-            eprintln!("Unable to parse config file");
-            return None;
+    fn apply_entries(&mut self, entries: &[Entry]) -> Vec<ConfigError> {
+        let mut errors = Vec::new();
+        for entry in entries {
+            let apply_res = self.apply_setting(&entry.key, &entry.value);
+            if let Err(err) = &apply_res {
+                errors.push(ConfigError::new(
+                    entry.line,
+                    format!("{}: {}", entry.path, err),
+                ));
+            }
         }
+        errors
+    }
 
-        let toml_value = toml_value_res.unwrap();
-        if toml_value.is_table() {
-            let toml_table = toml_value.as_table().unwrap();
-            if toml_table.contains_key(DEFAULT_KEY_KEY) {
-                let default_key = toml_table.get(DEFAULT_KEY_KEY).unwrap();
-                if default_key.is_str() {
-                    let conf = Config::new(default_key.as_str().unwrap().to_string());
-                    return Some(conf);
-                } else {
-                    eprintln!("toml key: {} isn't a string", DEFAULT_KEY_KEY);
+    fn apply_setting(&mut self, key: &str, value: &Value) -> Result<(), String> {
+        match key {
+            DEFAULT_KEY_KEY => {
+                self.default_key = parse_string(value)?;
+            }
+            STORE_KEY => {
+                self.store = Some(expand_home(&parse_string(value)?));
+            }
+            GNUPGHOME_KEY => {
+                self.gnupghome = Some(expand_home(&parse_string(value)?));
+            }
+            BACKEND_KEY => {
+                let backends = key::backend::backends();
+                let backend = value.as_str();
+                if backend.is_none() || !backends.contains(&backend.unwrap()) {
+                    return Err(format!("must be one of: {}", backends.join(", ")));
                 }
-            } else {
-                eprintln!("toml doesn't have key: {}", DEFAULT_KEY_KEY);
+                self.backend = Some(backend.unwrap().to_string());
+            }
+            KEYRING_KEY => {
+                self.keyring = Some(expand_home(&parse_string(value)?));
+            }
+            TRUST_THRESHOLD_KEY => {
+                self.trust_threshold = parse_count(value)?;
+            }
+            TRUST_MAX_DEPTH_KEY => {
+                self.trust_max_depth = Some(parse_count(value)?);
+            }
+            EXPORT_MODE_KEY => {
+                let mode = value.as_str().and_then(ExportMode::parse);
+                if mode.is_none() {
+                    return Err("must be one of: full, minimal, known-signers".to_string());
+                }
+                self.export_mode = mode.unwrap();
+            }
+            GIT_COMMIT_KEY => {
+                self.git_commit = parse_bool(value)?;
+            }
+            GIT_PUSH_KEY => {
+                self.git_push = parse_bool(value)?;
+            }
+            REENCRYPT_JOBS_KEY => {
+                self.reencrypt_jobs = parse_count(value)?;
+            }
+            OUTPUT_FORMAT_KEY => {
+                let format = value.as_str();
+                if format.is_none() || !cli::FORMATS.contains(&format.unwrap()) {
+                    return Err(format!("must be one of: {}", cli::FORMATS.join(", ")));
+                }
+                self.output_format = Some(format.unwrap().to_string());
+            }
+            AUDIT_LOG_KEY => {
+                let log = value.as_str().and_then(AuditLog::parse);
+                if log.is_none() {
+                    return Err("must be one of: off, local, store".to_string());
+                }
+                self.audit_log = log.unwrap();
+            }
+            _ => {
+                return Err("unknown key".to_string());
             }
-        } else {
-            eprintln!("toml doesn't have any key value pairs");
         }
-        None
+        Ok(())
+    }
+
+    pub fn get_setting(&self, key: &str) -> Result<Option<Value>, String> {
+        let value = match key {
+            DEFAULT_KEY_KEY if self.default_key.is_empty() => None,
+            DEFAULT_KEY_KEY => Some(Value::from(self.default_key.as_str())),
+            STORE_KEY => self
+                .store
+                .as_ref()
+                .map(|store| Value::from(store.display().to_string())),
+            GNUPGHOME_KEY => self
+                .gnupghome
+                .as_ref()
+                .map(|gnupghome| Value::from(gnupghome.display().to_string())),
+            BACKEND_KEY => self.backend.as_deref().map(Value::from),
+            KEYRING_KEY => self
+                .keyring
+                .as_ref()
+                .map(|keyring| Value::from(keyring.display().to_string())),
+            TRUST_THRESHOLD_KEY => Some(Value::from(self.trust_threshold as i64)),
+            TRUST_MAX_DEPTH_KEY => self.trust_max_depth.map(|depth| Value::from(depth as i64)),
+            EXPORT_MODE_KEY => Some(Value::from(self.export_mode.as_str())),
+            GIT_COMMIT_KEY => Some(Value::from(self.git_commit)),
+            GIT_PUSH_KEY => Some(Value::from(self.git_push)),
+            REENCRYPT_JOBS_KEY => Some(Value::from(self.reencrypt_jobs as i64)),
+            OUTPUT_FORMAT_KEY => self.output_format.as_deref().map(Value::from),
+            AUDIT_LOG_KEY => Some(Value::from(self.audit_log.as_str())),
+            _ => return Err(format!("Unknown config key: {}", key)),
+        };
+        Ok(value)
     }
 
     pub fn get_default_key(&self) -> &str {
         &self.default_key
     }
 
-    pub fn write_config(&self) {
-        let config_file = get_config_file_location();
-        let mut table = Map::new();
-        let default_key_value = toml::Value::String(self.default_key.clone());
-        table.insert(DEFAULT_KEY_KEY.to_string(), default_key_value);
-        let toml = toml::Value::Table(table);
+    pub fn get_profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
 
-        let toml_string = toml::to_string(&toml).unwrap();
-        let res = fs::write(config_file, toml_string);
-        if res.is_err() {
-            eprintln!("Error writing config!");
+    pub fn get_store(&self) -> Option<&Path> {
+        self.store.as_deref()
+    }
+
+    pub fn get_gnupghome(&self) -> Option<&Path> {
+        self.gnupghome.as_deref()
+    }
+
+    pub fn get_backend(&self) -> Option<&str> {
+        self.backend.as_deref()
+    }
+
+    pub fn get_keyring(&self) -> Option<&Path> {
+        self.keyring.as_deref()
+    }
+
+    pub fn get_trust_threshold(&self) -> usize {
+        self.trust_threshold
+    }
+
+    pub fn get_trust_max_depth(&self) -> Option<usize> {
+        self.trust_max_depth
+    }
+
+    pub fn get_export_mode(&self) -> ExportMode {
+        self.export_mode
+    }
+
+    pub fn get_git_commit(&self) -> bool {
+        self.git_commit
+    }
+
+    pub fn get_git_push(&self) -> bool {
+        self.git_push
+    }
+
+    pub fn get_reencrypt_jobs(&self) -> usize {
+        self.reencrypt_jobs
+    }
+
+    pub fn get_output_format(&self) -> Option<&str> {
+        self.output_format.as_deref()
+    }
+
+    pub fn get_signing_keys(&self) -> &[String] {
+        &self.signing_keys
+    }
+
+    pub fn get_audit_log(&self) -> AuditLog {
+        self.audit_log
+    }
+
+    pub fn write_config(&self, config_env: &ConfigEnv, profile: Option<&str>) {
+        let res = set_config_value(config_env, profile, DEFAULT_KEY_KEY, &self.default_key);
+        if let Err(err) = &res {
+            eprintln!("Error writing config: {}", err);
+        }
+    }
+}
+
+pub fn read_config(config_env: &ConfigEnv) -> Result<Config, Vec<String>> {
+    let config_location = &config_env.file;
+    let file_contents = fs::read_to_string(config_location);
+    if let Err(err) = &file_contents {
+        return Err(vec![format!(
+            "Unable to read config file {}: {}",
+            config_location.display(),
+            err
+        )]);
+    }
+
+    let conf = Config::from_toml(
+        &file_contents.unwrap(),
+        config_env.profile.as_deref(),
+        config_env.store.as_deref(),
+    );
+    if conf.is_err() {
+        return Err(conf
+            .err()
+            .unwrap()
+            .iter()
+            .map(|err| format!("{}: {}", config_location.display(), err))
+            .collect());
+    }
+    Ok(conf
+        .unwrap()
+        .with_signing_keys(config_env.signing_keys.clone()))
+}
+
+pub fn set_config_value(
+    config_env: &ConfigEnv,
+    profile: Option<&str>,
+    key: &str,
+    raw_value: &str,
+) -> Result<(), String> {
+    if !SETTINGS.contains(&key) {
+        return Err(format!("Unknown config key: {}", key));
+    }
+    let value = parse_raw_value(key, raw_value);
+    let check_res = Config::new(String::new()).apply_setting(key, &value);
+    if let Err(err) = &check_res {
+        return Err(format!("{}: {}", key, err));
+    }
+
+    let config_location = &config_env.file;
+    let contents = if config_location.exists() {
+        let read_res = fs::read_to_string(config_location);
+        if let Err(err) = &read_res {
+            return Err(format!(
+                "Unable to read config file {}: {}",
+                config_location.display(),
+                err
+            ));
+        }
+        read_res.unwrap()
+    } else {
+        String::new()
+    };
+    let document = contents.parse::<DocumentMut>();
+    if let Err(err) = &document {
+        return Err(format!(
+            "Unable to parse config file {}: {}",
+            config_location.display(),
+            err.message()
+        ));
+    }
+    let mut document = document.unwrap();
+
+    let mut path = Vec::new();
+    if let Some(profile) = profile {
+        path.push(PROFILE_KEY);
+        path.push(profile);
+    }
+    path.extend(key.split('.'));
+    let (last, parents) = path.split_last().unwrap();
+
+    let mut table: &mut dyn TableLike = document.as_table_mut();
+    for parent in parents {
+        let item = table.entry(parent).or_insert_with(|| {
+            let mut new_table = toml_edit::Table::new();
+            new_table.set_implicit(true);
+            Item::Table(new_table)
+        });
+        let next = item.as_table_like_mut();
+        if next.is_none() {
+            return Err(format!("Config key: {} isn't a table", parent));
+        }
+        table = next.unwrap();
+    }
+    table.insert(last, toml_edit::value(value));
+
+    let write_res = fs::write(config_location, document.to_string());
+    if let Err(err) = &write_res {
+        return Err(format!(
+            "Unable to write config file {}: {}",
+            config_location.display(),
+            err
+        ));
+    }
+    Ok(())
+}
+
+fn parse_raw_value(key: &str, raw_value: &str) -> Value {
+    match key {
+        TRUST_THRESHOLD_KEY | TRUST_MAX_DEPTH_KEY | REENCRYPT_JOBS_KEY => {
+            let int_value = raw_value.parse::<i64>();
+            if let Ok(int_value) = int_value {
+                return Value::from(int_value);
+            }
+        }
+        GIT_COMMIT_KEY | GIT_PUSH_KEY => {
+            let bool_value = raw_value.parse::<bool>();
+            if let Ok(bool_value) = bool_value {
+                return Value::from(bool_value);
+            }
+        }
+        _ => {}
+    }
+    Value::from(raw_value)
+}
+
+fn parse_config_file(contents: &str) -> Result<(ConfigFile, Vec<ConfigError>), Vec<ConfigError>> {
+    let document = ImDocument::parse(contents);
+    if let Err(err) = &document {
+        return Err(vec![ConfigError::new(
+            line_of(contents, err.span()),
+            err.message().trim().replace('\n', ": "),
+        )]);
+    }
+    let document = document.unwrap();
+
+    let mut config_file = ConfigFile {
+        settings: Vec::new(),
+        profiles: Vec::new(),
+    };
+    let mut errors = Vec::new();
+    for (name, item) in document.as_table().iter() {
+        if name != PROFILE_KEY {
+            collect_entry(
+                contents,
+                name,
+                "",
+                item,
+                &mut config_file.settings,
+                &mut errors,
+            );
+            continue;
+        }
+
+        let profiles = item.as_table_like();
+        if profiles.is_none() {
+            errors.push(ConfigError::new(
+                line_of(contents, item.span()),
+                format!("{}: must be a table", PROFILE_KEY),
+            ));
+            continue;
+        }
+        for (profile_name, profile) in profiles.unwrap().iter() {
+            let profile_table = profile.as_table_like();
+            if profile_table.is_none() {
+                errors.push(ConfigError::new(
+                    line_of(contents, profile.span()),
+                    format!("{}.{}: must be a table", PROFILE_KEY, profile_name),
+                ));
+                continue;
+            }
+            let prefix = format!("{}.{}.", PROFILE_KEY, profile_name);
+            let mut entries = Vec::new();
+            for (name, item) in profile_table.unwrap().iter() {
+                collect_entry(contents, name, &prefix, item, &mut entries, &mut errors);
+            }
+            config_file
+                .profiles
+                .push((profile_name.to_string(), entries));
+        }
+    }
+
+    Ok((config_file, errors))
+}
+
+fn collect_entry(
+    contents: &str,
+    key: &str,
+    prefix: &str,
+    item: &Item,
+    entries: &mut Vec<Entry>,
+    errors: &mut Vec<ConfigError>,
+) {
+    let line = line_of(contents, item.span());
+    if item.is_table_like() {
+        let section = format!("{}.", key);
+        if !SETTINGS.iter().any(|setting| setting.starts_with(&section)) {
+            errors.push(ConfigError::new(
+                line,
+                format!("{}{}: unknown table", prefix, key),
+            ));
+            return;
+        }
+        for (name, child) in item.as_table_like().unwrap().iter() {
+            let child_key = format!("{}{}", section, name);
+            collect_entry(contents, &child_key, prefix, child, entries, errors);
+        }
+        return;
+    }
+
+    let value = item.as_value();
+    if value.is_none() {
+        errors.push(ConfigError::new(
+            line,
+            format!("{}{}: unexpected array of tables", prefix, key),
+        ));
+        return;
+    }
+    entries.push(Entry {
+        key: key.to_string(),
+        path: format!("{}{}", prefix, key),
+        value: value.unwrap().clone(),
+        line,
+    });
+}
+
+fn line_of(contents: &str, span: Option<std::ops::Range<usize>>) -> Option<usize> {
+    let span = span?;
+    let start = span.start.min(contents.len());
+    Some(contents[..start].matches('\n').count() + 1)
+}
+
+fn select_profile(
+    config_file: &ConfigFile,
+    requested: Option<&str>,
+    store: Option<&Path>,
+) -> Result<Option<usize>, ConfigError> {
+    if let Some(requested) = requested {
+        for (index, (name, _)) in config_file.profiles.iter().enumerate() {
+            if name == requested {
+                return Ok(Some(index));
+            }
+        }
+        return Err(ConfigError::new(
+            None,
+            format!("Unknown profile: {}", requested),
+        ));
+    }
+
+    if store.is_none() {
+        return Ok(None);
+    }
+    for (index, (_, entries)) in config_file.profiles.iter().enumerate() {
+        for entry in entries {
+            if entry.key != STORE_KEY || entry.value.as_str().is_none() {
+                continue;
+            }
+            let profile_store = expand_home(entry.value.as_str().unwrap());
+            if same_path(&profile_store, store.unwrap()) {
+                return Ok(Some(index));
+            }
         }
     }
+    Ok(None)
+}
+
+fn parse_string(value: &Value) -> Result<String, String> {
+    let string_value = value.as_str();
+    if string_value.is_none() {
+        return Err("must be a string".to_string());
+    }
+    Ok(string_value.unwrap().to_string())
+}
+
+fn parse_bool(value: &Value) -> Result<bool, String> {
+    let bool_value = value.as_bool();
+    if bool_value.is_none() {
+        return Err("must be true or false".to_string());
+    }
+    Ok(bool_value.unwrap())
+}
+
+fn parse_count(value: &Value) -> Result<usize, String> {
+    let int_value = value.as_integer();
+    if int_value.is_none() || int_value.unwrap() <= 0 {
+        return Err("must be a positive integer".to_string());
+    }
+    Ok(int_value.unwrap() as usize)
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    let home_dir = dirs::home_dir();
+    if let Some(home_dir) = home_dir {
+        if path == "~" {
+            return home_dir;
+        }
+        if let Some(relative) = path.strip_prefix("~/") {
+            return home_dir.join(relative);
+        }
+    }
+    PathBuf::from(path)
+}
+
+fn same_path(first: &Path, second: &Path) -> bool {
+    let first_canonical = first.canonicalize();
+    let second_canonical = second.canonicalize();
+    if let (Ok(first_canonical), Ok(second_canonical)) = (first_canonical, second_canonical) {
+        return first_canonical == second_canonical;
+    }
+    first == second
 }
 
 pub fn get_config_file_location() -> PathBuf {
+    let config_file_env_var = env::var(CONFIG_FILE_VAR_NAME);
+    if let Ok(config_file_env_var) = &config_file_env_var {
+        return PathBuf::from(config_file_env_var);
+    }
+
     let config_dir_opt = dirs::config_dir();
     if config_dir_opt.is_none() {
         eprintln!("Unable to get config file location");
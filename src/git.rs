@@ -0,0 +1,112 @@
+use crate::command;
+use crate::config::Config;
+use crate::key;
+use crate::store;
+use std::path::Path;
+
+const GIT_DIR_NAME: &str = ".git";
+const KEYS_PATHSPEC: &str = ".keys/*.asc";
+
+pub fn commit_changes(config: &Config, paths: &[&str], message: &str) {
+    if !config.get_git_commit() {
+        return;
+    }
+    let pass_dir = key::get_pass_dir();
+    if !store::exists(&pass_dir.join(GIT_DIR_NAME)) {
+        return;
+    }
+    let pass_dir = pass_dir.to_str().unwrap();
+
+    let mut status_args = vec!["-C", pass_dir, "status", "--porcelain", "--"];
+    status_args.extend_from_slice(paths);
+    let status = command::output_command("git", &status_args);
+    if let Err(err) = &status {
+        eprintln!("Unable to read git status: {}", err);
+        return;
+    }
+    if status.unwrap().is_empty() {
+        return;
+    }
+
+    let mut add_args = vec!["-C", pass_dir, "add", "-A", "--"];
+    add_args.extend_from_slice(paths);
+    let add_res = command::oneshot_command("git", &add_args);
+    if let Err(err) = &add_res {
+        eprintln!("Unable to stage changes: {}", err);
+        return;
+    }
+
+    let mut commit_args = vec!["-C", pass_dir, "commit", "-q", "-m", message, "--"];
+    commit_args.extend_from_slice(paths);
+    let commit_res = command::oneshot_command("git", &commit_args);
+    if let Err(err) = &commit_res {
+        eprintln!("Unable to commit changes: {}", err);
+        return;
+    }
+
+    if config.get_git_push() {
+        let push_res = command::oneshot_command("git", &["-C", pass_dir, "push", "-q"]);
+        if let Err(err) = &push_res {
+            eprintln!("Unable to push changes: {}", err);
+        }
+    }
+}
+
+pub fn init_repo(pass_dir: &Path, message: &str) -> Result<(), String> {
+    let pass_dir = pass_dir.to_str().unwrap();
+    let init_res = command::oneshot_command("git", &["-C", pass_dir, "init", "-q"]);
+    if let Err(err) = &init_res {
+        return Err(format!("Unable to initialize git repository: {}", err));
+    }
+    let add_res = command::oneshot_command("git", &["-C", pass_dir, "add", "-A"]);
+    if let Err(err) = &add_res {
+        return Err(format!("Unable to stage store: {}", err));
+    }
+    let commit_res =
+        command::oneshot_command("git", &["-C", pass_dir, "commit", "-q", "-m", message]);
+    if let Err(err) = &commit_res {
+        return Err(format!("Unable to commit store: {}", err));
+    }
+    Ok(())
+}
+
+/// Fingerprints whose `.keys/<fingerprint>.asc` some commit in the store's
+/// history deleted. Empty when the store isn't a git repository.
+pub fn deleted_key_ids() -> Vec<String> {
+    let pass_dir = key::get_pass_dir();
+    if !store::exists(&pass_dir.join(GIT_DIR_NAME)) {
+        return Vec::new();
+    }
+    let pass_dir = pass_dir.to_str().unwrap();
+    let log = command::output_command(
+        "git",
+        &[
+            "-C",
+            pass_dir,
+            "log",
+            "--diff-filter=D",
+            "--name-only",
+            "--format=",
+            "--",
+            KEYS_PATHSPEC,
+        ],
+    );
+    if let Err(err) = &log {
+        eprintln!("Unable to read git history: {}", err);
+        return Vec::new();
+    }
+
+    let mut fingerprints: Vec<String> = Vec::new();
+    for line in String::from_utf8_lossy(&log.unwrap()).lines() {
+        let fingerprint = line
+            .strip_prefix(".keys/")
+            .filter(|name| !name.contains('/'))
+            .and_then(|name| name.strip_suffix(".asc"));
+        if let Some(fingerprint) = fingerprint {
+            if !fingerprints.iter().any(|fpr| fpr == fingerprint) {
+                fingerprints.push(fingerprint.to_string());
+            }
+        }
+    }
+    fingerprints
+}
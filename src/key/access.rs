@@ -0,0 +1,321 @@
+use super::backend::KeyBackend;
+use super::folder;
+use super::folder::ROOT_FOLDER;
+use super::group;
+use super::group::People;
+use super::recipients::matches_key;
+use super::recipients::RecipientResolver;
+use super::recipients::SHORT_ID_LEN;
+use super::Key;
+use crate::store;
+use serde_json::json;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// A column of the matrix: a key from the keyring, or a recipient that no key
+/// in the keyring matches.
+pub struct Device {
+    identity: String,
+    fingerprint: Option<String>,
+}
+
+impl Device {
+    fn label(&self) -> String {
+        match &self.fingerprint {
+            Some(fingerprint) => format!(
+                "{} ({})",
+                self.identity,
+                &fingerprint[fingerprint.len() - SHORT_ID_LEN..]
+            ),
+            None => format!("{} (no key)", self.identity),
+        }
+    }
+}
+
+pub struct FolderAccess {
+    folder: String,
+    recipients: Vec<(String, BTreeSet<String>)>,
+}
+
+impl FolderAccess {
+    /// Device ids that can read the folder: fingerprints, or the recipient
+    /// itself when it doesn't match any key.
+    pub fn readers(&self) -> BTreeSet<String> {
+        self.recipients
+            .iter()
+            .flat_map(|(_, devices)| devices.iter().cloned())
+            .collect()
+    }
+}
+
+pub struct Mismatch {
+    file: String,
+    missing: Vec<String>,
+    extra: Vec<String>,
+    error: Option<String>,
+}
+
+impl Mismatch {
+    fn describe(&self) -> String {
+        if let Some(error) = &self.error {
+            return format!("{}: unable to check: {}", self.file, error);
+        }
+        let mut description = self.file.clone();
+        if !self.missing.is_empty() {
+            description += &format!(" not encrypted for: {}", self.missing.join(", "));
+        }
+        if !self.extra.is_empty() {
+            description += &format!(" also encrypted for: {}", self.extra.join(", "));
+        }
+        description
+    }
+}
+
+pub struct AccessMatrix {
+    devices: BTreeMap<String, Device>,
+    folders: Vec<FolderAccess>,
+}
+
+impl AccessMatrix {
+    /// Resolves the effective .gpg-id of every folder under `path` (the whole
+    /// store by default) to the keys it encrypts for.
+    pub fn load(keys: &[Key], path: Option<&str>) -> AccessMatrix {
+        let path = path.map(|path| path.trim_matches('/'));
+        let mut matrix = AccessMatrix {
+            devices: BTreeMap::new(),
+            folders: Vec::new(),
+        };
+        let resolver = RecipientResolver::new(keys);
+        for folder in folder::get_store_folders() {
+            if path.is_some() && !is_within(&folder, path.unwrap()) {
+                continue;
+            }
+            let resolved = resolver.resolve(Path::new(&folder)).unwrap();
+
+            let mut recipients = Vec::new();
+            for (index, gpg) in resolved.get_gpg_ids().iter().enumerate() {
+                let devices = matrix.add_devices(gpg, resolved.get_keys(index), keys);
+                recipients.push((gpg.clone(), devices));
+            }
+            matrix.folders.push(FolderAccess { folder, recipients });
+        }
+        matrix
+    }
+
+    fn add_devices(
+        &mut self,
+        recipient: &str,
+        fingerprints: &[String],
+        keys: &[Key],
+    ) -> BTreeSet<String> {
+        let mut devices = BTreeSet::new();
+        for key in keys {
+            if fingerprints.iter().any(|fpr| fpr == key.get_fingerprint()) {
+                self.devices
+                    .entry(key.get_fingerprint().to_string())
+                    .or_insert(Device {
+                        identity: key.get_identity().to_string(),
+                        fingerprint: Some(key.get_fingerprint().to_string()),
+                    });
+                devices.insert(key.get_fingerprint().to_string());
+            }
+        }
+        if devices.is_empty() {
+            self.devices.entry(recipient.to_string()).or_insert(Device {
+                identity: recipient.to_string(),
+                fingerprint: None,
+            });
+            devices.insert(recipient.to_string());
+        }
+        devices
+    }
+
+    /// Keeps only the devices `who` names, an identity, fingerprint, key id or
+    /// `@person`, and the folders they can read.
+    pub fn filter_who(&mut self, who: &str, people: &People, keys: &[Key]) -> Result<(), String> {
+        let wanted: BTreeSet<String> = if group::is_group(who) {
+            people.expand(who, keys)?
+        } else {
+            keys.iter()
+                .filter(|key| matches_key(who, key))
+                .map(|key| key.get_fingerprint().to_string())
+                .chain(std::iter::once(who.to_string()))
+                .collect()
+        };
+        self.devices.retain(|id, _| wanted.contains(id));
+        if self.devices.is_empty() {
+            return Err(format!("{} isn't a recipient of any folder", who));
+        }
+        let devices = &self.devices;
+        self.folders.retain(|folder| {
+            folder
+                .readers()
+                .iter()
+                .any(|reader| devices.contains_key(reader))
+        });
+        Ok(())
+    }
+
+    pub fn to_table(&self) -> String {
+        let labels: Vec<String> = self.devices.values().map(Device::label).collect();
+        let folder_width = self
+            .folders
+            .iter()
+            .map(|folder| folder.folder.len())
+            .chain(std::iter::once("FOLDER".len()))
+            .max()
+            .unwrap();
+
+        let mut table = format!("{:width$}", "FOLDER", width = folder_width);
+        for label in &labels {
+            table += &format!("  {}", label);
+        }
+        table += "\n";
+        for folder in &self.folders {
+            let readers = folder.readers();
+            table += &format!("{:width$}", folder.folder, width = folder_width);
+            for (id, label) in self.devices.keys().zip(&labels) {
+                let mark = if readers.contains(id) { "x" } else { "-" };
+                table += &format!("  {:width$}", mark, width = label.len());
+            }
+            table = table.trim_end().to_string();
+            table += "\n";
+        }
+        table
+    }
+
+    pub fn to_json(&self, mismatches: Option<&[Mismatch]>) -> String {
+        let devices: Vec<Value> = self
+            .devices
+            .iter()
+            .map(|(id, device)| {
+                json!({
+                    "id": id,
+                    "identity": device.identity,
+                    "fingerprint": device.fingerprint,
+                })
+            })
+            .collect();
+        let folders: Vec<Value> = self
+            .folders
+            .iter()
+            .map(|folder| {
+                let readers: Vec<String> = folder
+                    .readers()
+                    .into_iter()
+                    .filter(|reader| self.devices.contains_key(reader))
+                    .collect();
+                let recipients: Vec<&String> =
+                    folder.recipients.iter().map(|(gpg, _)| gpg).collect();
+                json!({
+                    "folder": folder.folder,
+                    "recipients": recipients,
+                    "readers": readers,
+                })
+            })
+            .collect();
+
+        let mut value = json!({ "devices": devices, "folders": folders });
+        if let Some(mismatches) = mismatches {
+            let mismatches: Vec<Value> = mismatches
+                .iter()
+                .map(|mismatch| {
+                    json!({
+                        "file": mismatch.file,
+                        "missing": mismatch.missing,
+                        "extra": mismatch.extra,
+                        "error": mismatch.error,
+                    })
+                })
+                .collect();
+            value["mismatches"] = Value::from(mismatches);
+        }
+        serde_json::to_string_pretty(&value).unwrap()
+    }
+}
+
+/// Reads who every password file in the matrix's folders is actually
+/// encrypted for, without decrypting it, and compares that with the folder's
+/// .gpg-id.
+pub fn cross_check(backend: &mut dyn KeyBackend, matrix: &AccessMatrix) -> Vec<Mismatch> {
+    let pass_dir = folder::get_pass_dir();
+    let mut mismatches = Vec::new();
+    for folder in &matrix.folders {
+        for name in folder::get_password_files(&folder.folder) {
+            let mismatch = check_file(backend, folder, &pass_dir.join(&name), name);
+            if let Some(mismatch) = mismatch {
+                mismatches.push(mismatch);
+            }
+        }
+    }
+    mismatches
+}
+
+fn check_file(
+    backend: &mut dyn KeyBackend,
+    folder: &FolderAccess,
+    file: &Path,
+    name: String,
+) -> Option<Mismatch> {
+    let failed = |error: String| Mismatch {
+        file: name.clone(),
+        missing: Vec::new(),
+        extra: Vec::new(),
+        error: Some(error),
+    };
+    let ciphertext = store::read(file);
+    if let Err(err) = &ciphertext {
+        return Some(failed(err.to_string()));
+    }
+    let recipients = backend.encrypted_to(&ciphertext.unwrap());
+    if let Err(err) = recipients {
+        return Some(failed(err));
+    }
+
+    let mut actual = BTreeSet::new();
+    for key_id in &recipients.unwrap() {
+        let record = backend.get_key(key_id);
+        if let Some(record) = record {
+            actual.insert(record.get_fingerprint().to_string());
+        } else {
+            actual.insert(key_id.to_string());
+        }
+    }
+
+    let mut missing = Vec::new();
+    for (gpg, devices) in &folder.recipients {
+        if !devices.iter().any(|device| actual.contains(device)) {
+            missing.push(gpg.clone());
+        }
+    }
+    let expected = folder.readers();
+    let extra: Vec<String> = actual
+        .into_iter()
+        .filter(|device| !expected.contains(device))
+        .collect();
+    if missing.is_empty() && extra.is_empty() {
+        return None;
+    }
+    Some(Mismatch {
+        file: name,
+        missing,
+        extra,
+        error: None,
+    })
+}
+
+pub fn describe_mismatches(mismatches: &[Mismatch]) -> String {
+    mismatches
+        .iter()
+        .map(|mismatch| mismatch.describe() + "\n")
+        .collect()
+}
+
+fn is_within(folder: &str, path: &str) -> bool {
+    path.is_empty()
+        || path == ROOT_FOLDER
+        || folder == path
+        || folder.starts_with(&format!("{}/", path))
+}
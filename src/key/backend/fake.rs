@@ -0,0 +1,471 @@
+use super::CertStatus;
+use super::Certification;
+use super::Decrypted;
+use super::ExportFilter;
+use super::ImportSummary;
+use super::ImportedKey;
+use super::KeyBackend;
+use super::KeyRecord;
+use super::UserIdRecord;
+use crate::key::generate::KeySpec;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+const BEGIN_PUBLIC: &str = "-----BEGIN FAKE PGP PUBLIC KEY BLOCK-----";
+const END_PUBLIC: &str = "-----END FAKE PGP PUBLIC KEY BLOCK-----";
+const ENCRYPTED_HEADER: &str = "FAKE-ENCRYPTED";
+const SIGNATURE_HEADER: &str = "FAKE-SIGNATURE";
+
+#[derive(Clone, PartialEq, Eq)]
+struct FakeCert {
+    signer: String,
+    bad: bool,
+}
+
+#[derive(Clone)]
+struct FakeKey {
+    fingerprint: String,
+    user_ids: Vec<String>,
+    secret: bool,
+    revoked: bool,
+    expired: bool,
+    sign_only: bool,
+    certs: Vec<FakeCert>,
+}
+
+impl FakeKey {
+    fn key_id(&self) -> &str {
+        &self.fingerprint[self.fingerprint.len() - 16..]
+    }
+
+    fn matches(&self, id: &str) -> bool {
+        let id = id.trim_start_matches("0x").to_uppercase();
+        id.len() >= 16 && self.fingerprint.ends_with(&id)
+    }
+
+    fn write(&self, filter: ExportFilter, keyring: &[FakeKey], out: &mut String) {
+        out.push_str(&format!(
+            "key {} {}",
+            self.fingerprint,
+            email_of(&self.user_ids[0])
+        ));
+        if self.revoked {
+            out.push_str(" revoked");
+        }
+        if self.expired {
+            out.push_str(" expired");
+        }
+        if self.sign_only {
+            out.push_str(" sign-only");
+        }
+        out.push('\n');
+        for user_id in &self.user_ids[1..] {
+            out.push_str(&format!("uid {}\n", user_id));
+        }
+        for cert in &self.certs {
+            let keep = match filter {
+                ExportFilter::Full => true,
+                ExportFilter::Minimal => false,
+                ExportFilter::Clean => cert_status(cert, keyring) == CertStatus::Good,
+            };
+            if keep {
+                let status = if cert.bad { "bad" } else { "good" };
+                out.push_str(&format!("cert {} {}\n", cert.signer, status));
+            }
+        }
+    }
+}
+
+/// Keyring kept in memory with a plain-text key format, for tests that
+/// shouldn't depend on a gpg install. Clones share the same keyring.
+#[derive(Clone, Default)]
+pub struct FakeBackend {
+    keyring: Arc<Mutex<Vec<FakeKey>>>,
+    signers: Vec<String>,
+    name: Option<&'static str>,
+}
+
+impl FakeBackend {
+    pub fn new() -> FakeBackend {
+        FakeBackend::default()
+    }
+
+    /// Reports `name` as the backend's name, to take the code paths that
+    /// depend on it.
+    pub fn named(mut self, name: &'static str) -> FakeBackend {
+        self.name = Some(name);
+        self
+    }
+
+    /// Generates a secret key for `email` and returns its fingerprint.
+    pub fn with_secret_key(&mut self, email: &str) -> String {
+        let spec = KeySpec::new(None, email, None, None, false).unwrap();
+        self.generate_key(&spec).unwrap()
+    }
+
+    /// Adds `user_id` to the key with `fingerprint`, after its existing ones.
+    pub fn add_user_id(&mut self, fingerprint: &str, user_id: &str) {
+        let mut keyring = self.keyring.lock().unwrap();
+        let key = keyring.iter_mut().find(|key| key.matches(fingerprint));
+        key.unwrap().user_ids.push(user_id.to_string());
+    }
+
+    fn find(&self, id: &str) -> Option<FakeKey> {
+        let keyring = self.keyring.lock().unwrap();
+        keyring.iter().find(|key| key.matches(id)).cloned()
+    }
+
+    fn find_recipient(&self, recipient: &str) -> Option<FakeKey> {
+        let keyring = self.keyring.lock().unwrap();
+        keyring
+            .iter()
+            .find(|key| {
+                key.matches(recipient)
+                    || key
+                        .user_ids
+                        .iter()
+                        .any(|user_id| email_of(user_id) == recipient)
+            })
+            .cloned()
+    }
+
+    fn record(&self, key: &FakeKey, keyring: &[FakeKey]) -> KeyRecord {
+        let certifications: Vec<Certification> = key
+            .certs
+            .iter()
+            .map(|cert| Certification {
+                signer: Some(cert.signer.clone()),
+                status: cert_status(cert, keyring),
+                revocation: false,
+                expired: false,
+            })
+            .collect();
+        let user_ids = key
+            .user_ids
+            .iter()
+            .map(|user_id| UserIdRecord {
+                user_id: user_id.clone(),
+                email: Some(email_of(user_id).to_string()),
+                certifications: certifications.clone(),
+            })
+            .collect();
+        KeyRecord {
+            fingerprint: key.fingerprint.clone(),
+            user_ids,
+            has_secret: key.secret,
+            revoked: key.revoked,
+            expired: key.expired,
+            can_encrypt: !key.sign_only,
+        }
+    }
+}
+
+fn cert_status(cert: &FakeCert, keyring: &[FakeKey]) -> CertStatus {
+    if cert.bad {
+        return CertStatus::Bad;
+    }
+    if keyring.iter().any(|key| key.matches(&cert.signer)) {
+        return CertStatus::Good;
+    }
+    CertStatus::Unknown
+}
+
+fn parse_keys(data: &[u8]) -> Result<Vec<FakeKey>, String> {
+    let text = String::from_utf8_lossy(data);
+    let mut keys: Vec<FakeKey> = Vec::new();
+    let mut in_block = false;
+    for line in text.lines() {
+        let line = line.trim();
+        if line == BEGIN_PUBLIC {
+            in_block = true;
+            continue;
+        }
+        if line == END_PUBLIC {
+            in_block = false;
+            continue;
+        }
+        if !in_block || line.is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields[0] {
+            "key" if fields.len() >= 3 => keys.push(FakeKey {
+                fingerprint: fields[1].to_uppercase(),
+                user_ids: vec![fields[2].to_string()],
+                secret: fields[3..].contains(&"secret"),
+                revoked: fields[3..].contains(&"revoked"),
+                expired: fields[3..].contains(&"expired"),
+                sign_only: fields[3..].contains(&"sign-only"),
+                certs: Vec::new(),
+            }),
+            "uid" if fields.len() >= 2 && !keys.is_empty() => {
+                let user_id = line["uid".len()..].trim().to_string();
+                keys.last_mut().unwrap().user_ids.push(user_id);
+            }
+            "cert" if fields.len() == 3 && !keys.is_empty() => {
+                keys.last_mut().unwrap().certs.push(FakeCert {
+                    signer: fields[1].to_uppercase(),
+                    bad: fields[2] == "bad",
+                });
+            }
+            _ => return Err(format!("Malformed key data: {}", line)),
+        }
+    }
+    if keys.is_empty() {
+        return Err("No keys found in key data".to_string());
+    }
+    Ok(keys)
+}
+
+fn email_of(user_id: &str) -> &str {
+    match (user_id.rfind('<'), user_id.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &user_id[start + 1..end],
+        _ => user_id,
+    }
+}
+
+fn digest(signer: &str, data: &[u8]) -> String {
+    let mut hasher = DefaultHasher::new();
+    signer.hash(&mut hasher);
+    data.hash(&mut hasher);
+    format!("{:016X}", hasher.finish())
+}
+
+fn fingerprint_for(user_id: &str) -> String {
+    let mut fingerprint = String::new();
+    let mut round: u64 = 0;
+    while fingerprint.len() < 40 {
+        let mut hasher = DefaultHasher::new();
+        user_id.hash(&mut hasher);
+        round.hash(&mut hasher);
+        fingerprint += &format!("{:016X}", hasher.finish());
+        round += 1;
+    }
+    fingerprint.truncate(40);
+    fingerprint
+}
+
+impl KeyBackend for FakeBackend {
+    fn name(&self) -> &'static str {
+        self.name.unwrap_or("fake")
+    }
+
+    fn list_keys(&mut self) -> Result<Vec<KeyRecord>, String> {
+        let keyring = self.keyring.lock().unwrap();
+        Ok(keyring
+            .iter()
+            .map(|key| self.record(key, &keyring))
+            .collect())
+    }
+
+    fn list_secret_keys(&mut self) -> Result<Vec<KeyRecord>, String> {
+        let keyring = self.keyring.lock().unwrap();
+        Ok(keyring
+            .iter()
+            .filter(|key| key.secret)
+            .map(|key| self.record(key, &keyring))
+            .collect())
+    }
+
+    fn get_key(&mut self, id: &str) -> Option<KeyRecord> {
+        let key = self.find(id)?;
+        let keyring = self.keyring.lock().unwrap();
+        Some(self.record(&key, &keyring))
+    }
+
+    fn get_secret_key(&mut self, id: &str) -> Option<KeyRecord> {
+        self.get_key(id).filter(KeyRecord::has_secret)
+    }
+
+    fn read_keys(&mut self, data: &[u8]) -> Result<Vec<KeyRecord>, String> {
+        let keys = parse_keys(data)?;
+        let keyring = self.keyring.lock().unwrap();
+        Ok(keys.iter().map(|key| self.record(key, &keyring)).collect())
+    }
+
+    fn import(&mut self, data: &[u8]) -> Result<ImportSummary, String> {
+        let mut summary = ImportSummary::default();
+        let mut keyring = self.keyring.lock().unwrap();
+        for key in parse_keys(data)? {
+            if key.secret {
+                summary.secret_considered += 1;
+            }
+            let existing = keyring
+                .iter_mut()
+                .find(|existing| existing.fingerprint == key.fingerprint);
+            if existing.is_none() {
+                summary.imports.push(ImportedKey {
+                    fingerprint: key.fingerprint.clone(),
+                    new: true,
+                });
+                keyring.push(key);
+                continue;
+            }
+            let existing = existing.unwrap();
+            for cert in key.certs {
+                if !existing.certs.contains(&cert) {
+                    existing.certs.push(cert);
+                    summary.new_signatures += 1;
+                }
+            }
+            if key.revoked && !existing.revoked {
+                existing.revoked = true;
+                summary.new_revocations += 1;
+            }
+            existing.secret |= key.secret;
+            summary.imports.push(ImportedKey {
+                fingerprint: key.fingerprint,
+                new: false,
+            });
+        }
+        Ok(summary)
+    }
+
+    fn export(&mut self, fingerprints: &[&str], filter: ExportFilter) -> Result<Vec<u8>, String> {
+        let keyring = self.keyring.lock().unwrap();
+        let mut out = String::new();
+        for fingerprint in fingerprints {
+            let key = keyring.iter().find(|key| key.matches(fingerprint));
+            if key.is_none() {
+                return Err(format!("Key {} not found in keyring", fingerprint));
+            }
+            out.push_str(BEGIN_PUBLIC);
+            out.push('\n');
+            key.unwrap().write(filter, &keyring, &mut out);
+            out.push_str(END_PUBLIC);
+            out.push('\n');
+        }
+        Ok(out.into_bytes())
+    }
+
+    fn add_signer(&mut self, fingerprint: &str) -> Result<(), String> {
+        let key = self.find(fingerprint);
+        if key.is_none() || !key.as_ref().unwrap().secret {
+            return Err(format!("No secret key for {}", fingerprint));
+        }
+        self.signers.push(key.unwrap().fingerprint);
+        Ok(())
+    }
+
+    fn signers(&self) -> Vec<String> {
+        self.signers.clone()
+    }
+
+    fn certify(&mut self, fingerprint: &str) -> Result<(), String> {
+        if self.signers.is_empty() {
+            return Err("No signing key configured".to_string());
+        }
+        let signer = self.find(&self.signers[0]).unwrap();
+        let mut keyring = self.keyring.lock().unwrap();
+        let key = keyring.iter_mut().find(|key| key.matches(fingerprint));
+        if key.is_none() {
+            return Err(format!("Key {} not found in keyring", fingerprint));
+        }
+        let cert = FakeCert {
+            signer: signer.key_id().to_string(),
+            bad: false,
+        };
+        let key = key.unwrap();
+        if !key.certs.contains(&cert) {
+            key.certs.push(cert);
+        }
+        Ok(())
+    }
+
+    fn generate_key(&mut self, spec: &KeySpec) -> Result<String, String> {
+        let user_id = spec.get_user_id();
+        let fingerprint = fingerprint_for(user_id);
+        let mut keyring = self.keyring.lock().unwrap();
+        if keyring.iter().any(|key| key.fingerprint == fingerprint) {
+            return Err(format!("Key for {} already exists", user_id));
+        }
+        keyring.push(FakeKey {
+            fingerprint: fingerprint.clone(),
+            user_ids: vec![user_id.to_string()],
+            secret: true,
+            revoked: false,
+            expired: false,
+            sign_only: false,
+            certs: Vec::new(),
+        });
+        Ok(fingerprint)
+    }
+
+    fn generate_key_interactively(&mut self) -> Result<(), String> {
+        Err("Interactive key generation isn't supported by the fake backend".to_string())
+    }
+
+    fn sign_detached(&mut self, data: &[u8]) -> Result<Vec<u8>, String> {
+        if self.signers.is_empty() {
+            return Err("No signing key configured".to_string());
+        }
+        let mut out = String::new();
+        for signer in &self.signers {
+            out += &format!("{} {} {}\n", SIGNATURE_HEADER, signer, digest(signer, data));
+        }
+        Ok(out.into_bytes())
+    }
+
+    fn verify_detached(&mut self, signature: &[u8], data: &[u8]) -> Result<Vec<String>, String> {
+        let mut signers = Vec::new();
+        for line in String::from_utf8_lossy(signature).lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() != 3 || fields[0] != SIGNATURE_HEADER {
+                return Err("Malformed signature".to_string());
+            }
+            if fields[2] == digest(fields[1], data) && self.find(fields[1]).is_some() {
+                signers.push(fields[1].to_string());
+            }
+        }
+        Ok(signers)
+    }
+
+    fn encrypt(&mut self, recipients: &[&str], plaintext: &[u8]) -> Result<Vec<u8>, String> {
+        let mut out = format!("{}\n", ENCRYPTED_HEADER).into_bytes();
+        for recipient in recipients {
+            let key = self.find_recipient(recipient);
+            if key.is_none() {
+                return Err(format!("No public key for {}", recipient));
+            }
+            out.extend(format!("recipient {}\n", key.unwrap().key_id()).into_bytes());
+        }
+        out.push(b'\n');
+        out.extend_from_slice(plaintext);
+        Ok(out)
+    }
+
+    fn decrypt(&mut self, ciphertext: &[u8]) -> Result<Decrypted, String> {
+        let recipients = self.encrypted_to(ciphertext)?;
+        let decryptable = recipients.iter().any(|recipient| {
+            let key = self.find(recipient);
+            key.is_some() && key.unwrap().secret
+        });
+        if !decryptable {
+            return Err("No secret key available to decrypt".to_string());
+        }
+        let header_end = ciphertext.windows(2).position(|window| window == b"\n\n");
+        Ok(Decrypted {
+            plaintext: ciphertext[header_end.unwrap() + 2..].to_vec(),
+            recipients,
+        })
+    }
+
+    fn encrypted_to(&mut self, ciphertext: &[u8]) -> Result<Vec<String>, String> {
+        let header_end = ciphertext.windows(2).position(|window| window == b"\n\n");
+        let header = String::from_utf8_lossy(&ciphertext[..header_end.unwrap_or(0)]).to_string();
+        let mut lines = header.lines();
+        if header_end.is_none() || lines.next() != Some(ENCRYPTED_HEADER) {
+            return Err("Not a fake encrypted message".to_string());
+        }
+        Ok(lines
+            .map(|line| line.trim_start_matches("recipient ").to_string())
+            .collect())
+    }
+
+    fn staging(&self) -> Result<Box<dyn KeyBackend>, String> {
+        Ok(Box::new(FakeBackend::new()))
+    }
+}
@@ -0,0 +1,508 @@
+use super::CertStatus;
+use super::Certification;
+use super::Decrypted;
+use super::ExportFilter;
+use super::ImportSummary;
+use super::ImportedKey;
+use super::KeyBackend;
+use super::KeyRecord;
+use super::UserIdRecord;
+use crate::command;
+use crate::key::generate::KeySpec;
+use crate::pass_env;
+use gpgme::Context;
+use gpgme::CreateKeyFlags;
+use gpgme::KeyListMode;
+use std::env;
+use std::fs;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use std::time::UNIX_EPOCH;
+
+const GPGCONF_TIMEOUT: Duration = Duration::from_secs(10);
+static STAGING_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+pub struct GpgmeBackend {
+    context: Context,
+    staging_home: Option<PathBuf>,
+}
+
+impl GpgmeBackend {
+    pub fn new() -> Result<GpgmeBackend, String> {
+        let context = create_context(None);
+        if context.is_err() {
+            return Err(format!(
+                "{}. Do you have gpgme installed?",
+                context.err().unwrap()
+            ));
+        }
+
+        Ok(GpgmeBackend {
+            context: context.unwrap(),
+            staging_home: None,
+        })
+    }
+
+    fn new_staging() -> Result<GpgmeBackend, String> {
+        let home_dir = env::temp_dir().join(format!(
+            "pass-provision-{}-{}",
+            process::id(),
+            STAGING_COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        let create_res = fs::DirBuilder::new().mode(0o700).create(&home_dir);
+        if let Err(err) = &create_res {
+            return Err(format!(
+                "Unable to create staging keyring at {}: {}",
+                home_dir.display(),
+                err
+            ));
+        }
+
+        let context = create_context(Some(&home_dir));
+        if context.is_err() {
+            let _ = fs::remove_dir_all(&home_dir);
+            return Err(context.err().unwrap());
+        }
+
+        Ok(GpgmeBackend {
+            context: context.unwrap(),
+            staging_home: Some(home_dir),
+        })
+    }
+
+    fn find_gpg_keys(&mut self, fingerprints: &[&str]) -> Result<Vec<gpgme::Key>, String> {
+        let mut keys = Vec::new();
+        for fingerprint in fingerprints {
+            let key = self.context.get_key(*fingerprint);
+            if key.is_err() {
+                return Err(format!(
+                    "Unable to locate key for exporting for fingerprint: {}",
+                    fingerprint
+                ));
+            }
+            keys.push(key.unwrap());
+        }
+        Ok(keys)
+    }
+
+    fn export_clean(&mut self, fingerprints: &[&str]) -> Result<Vec<u8>, String> {
+        let mut args = Vec::new();
+        let home_dir = self
+            .staging_home
+            .as_ref()
+            .map(|home| home.to_str().unwrap().to_string());
+        if let Some(home_dir) = &home_dir {
+            args.push("--homedir");
+            args.push(home_dir.as_str());
+        }
+        args.extend_from_slice(&["--batch", "--armor", "--export-options", "export-clean"]);
+        args.push("--export");
+        args.extend_from_slice(fingerprints);
+
+        let exported = pass_env::gpg_output_command(&args);
+        if let Err(err) = &exported {
+            return Err(format!(
+                "Unable to export key for fingerprint: {}: {}",
+                fingerprints.join(" "),
+                err
+            ));
+        }
+        Ok(exported.unwrap())
+    }
+}
+
+impl KeyBackend for GpgmeBackend {
+    fn name(&self) -> &'static str {
+        "gpgme"
+    }
+
+    fn list_keys(&mut self) -> Result<Vec<KeyRecord>, String> {
+        let key_iterator = self.context.keys();
+        if key_iterator.is_err() {
+            return Err(key_iterator.err().unwrap().description().to_string());
+        }
+        Ok(collect_records(key_iterator.unwrap(), "key"))
+    }
+
+    fn list_secret_keys(&mut self) -> Result<Vec<KeyRecord>, String> {
+        let key_iterator = self.context.secret_keys();
+        if key_iterator.is_err() {
+            return Err(key_iterator.err().unwrap().description().to_string());
+        }
+        Ok(collect_records(key_iterator.unwrap(), "secret key"))
+    }
+
+    fn get_key(&mut self, id: &str) -> Option<KeyRecord> {
+        let key = self.context.get_key(id);
+        if key.is_err() {
+            return None;
+        }
+        key_record(&key.unwrap()).ok()
+    }
+
+    fn get_secret_key(&mut self, id: &str) -> Option<KeyRecord> {
+        let key = self.context.get_secret_key(id);
+        if key.is_err() {
+            return None;
+        }
+        key_record(&key.unwrap()).ok()
+    }
+
+    fn read_keys(&mut self, data: &[u8]) -> Result<Vec<KeyRecord>, String> {
+        let keys = self.context.read_keys(data);
+        if keys.is_err() {
+            return Err(keys.err().unwrap().description().to_string());
+        }
+
+        let mut records = Vec::new();
+        for key in keys.unwrap() {
+            if key.is_err() {
+                return Err(key.err().unwrap().description().to_string());
+            }
+            records.push(key_record(&key.unwrap())?);
+        }
+        Ok(records)
+    }
+
+    fn import(&mut self, data: &[u8]) -> Result<ImportSummary, String> {
+        let import_result = self.context.import(data);
+        if import_result.is_err() {
+            return Err(import_result.err().unwrap().description().to_string());
+        }
+        let import_result = import_result.unwrap();
+
+        let mut imports = Vec::new();
+        for key_import in import_result.imports() {
+            let fingerprint = key_import.fingerprint();
+            if fingerprint.is_err() {
+                return Err("imported key has no readable fingerprint".to_string());
+            }
+            imports.push(ImportedKey {
+                fingerprint: fingerprint.unwrap().to_string(),
+                new: key_import.status().contains(gpgme::ImportFlags::NEW),
+            });
+        }
+
+        Ok(ImportSummary {
+            imports,
+            secret_considered: import_result.secret_considered(),
+            new_signatures: import_result.new_signatures(),
+            new_subkeys: import_result.new_subkeys(),
+            new_user_ids: import_result.new_user_ids(),
+            new_revocations: import_result.new_revocations(),
+        })
+    }
+
+    fn export(&mut self, fingerprints: &[&str], filter: ExportFilter) -> Result<Vec<u8>, String> {
+        let keys = self.find_gpg_keys(fingerprints)?;
+        let mode = match filter {
+            ExportFilter::Full => gpgme::ExportMode::empty(),
+            ExportFilter::Minimal => gpgme::ExportMode::MINIMAL,
+            ExportFilter::Clean => return self.export_clean(fingerprints),
+        };
+
+        let mut exported_bytes = Vec::new();
+        let export_res = self.context.export_keys(&keys, mode, &mut exported_bytes);
+        if export_res.is_err() {
+            return Err(format!(
+                "Unable to export key for fingerprint: {}",
+                fingerprints.join(" ")
+            ));
+        }
+        Ok(exported_bytes)
+    }
+
+    fn add_signer(&mut self, fingerprint: &str) -> Result<(), String> {
+        let key = self.context.get_secret_key(fingerprint);
+        if key.is_err() {
+            return Err(format!("No secret key found for: {}", fingerprint));
+        }
+        let signer_res = self.context.add_signer(&key.unwrap());
+        if let Err(err) = &signer_res {
+            return Err(format!(
+                "Unable to add {} as a signer: {}",
+                fingerprint, err
+            ));
+        }
+        Ok(())
+    }
+
+    fn signers(&self) -> Vec<String> {
+        let mut fingerprints = Vec::new();
+        for signer in self.context.signers() {
+            if signer.fingerprint().is_ok() {
+                fingerprints.push(signer.fingerprint().unwrap().to_string());
+            }
+        }
+        fingerprints
+    }
+
+    fn certify(&mut self, fingerprint: &str) -> Result<(), String> {
+        let key = self.context.get_key(fingerprint);
+        if key.is_err() {
+            return Err(format!("Unable to find key: {}", fingerprint));
+        }
+        let all_uids: Vec<&str> = Vec::new();
+        let res = self.context.sign_key(&key.unwrap(), all_uids, None);
+        if res.is_err() {
+            return Err(res.err().unwrap().description().to_string());
+        }
+        Ok(())
+    }
+
+    fn generate_key(&mut self, spec: &KeySpec) -> Result<String, String> {
+        let mut flags = CreateKeyFlags::CERT | CreateKeyFlags::SIGN;
+        if !spec.has_passphrase() {
+            flags |= CreateKeyFlags::NOPASSWD;
+        }
+        if spec.get_expire().is_none() {
+            flags |= CreateKeyFlags::NOEXPIRE;
+        }
+        // gpgme takes the expiry as seconds from now, and the bindings pass the
+        // time since the epoch straight through
+        let expires = spec
+            .get_expire()
+            .map(|seconds| UNIX_EPOCH + Duration::from_secs(seconds));
+
+        let created =
+            self.context
+                .create_key_with_flags(spec.get_user_id(), spec.get_algo(), expires, flags);
+        if let Err(err) = &created {
+            return Err(format!("Unable to generate key: {}", err));
+        }
+        let created = created.unwrap();
+        let fingerprint = created.fingerprint();
+        if fingerprint.is_err() {
+            return Err("Unable to read fingerprint of generated key".to_string());
+        }
+        let fingerprint = fingerprint.unwrap().to_string();
+
+        let key = self.context.get_secret_key(fingerprint.as_str());
+        if key.is_err() {
+            return Err(format!("Unable to find generated key: {}", fingerprint));
+        }
+        let mut subkey_flags = CreateKeyFlags::ENCR;
+        if !spec.has_passphrase() {
+            subkey_flags |= CreateKeyFlags::NOPASSWD;
+        }
+        if spec.get_expire().is_none() {
+            subkey_flags |= CreateKeyFlags::NOEXPIRE;
+        }
+        let subkey = self.context.create_subkey_with_flags(
+            &key.unwrap(),
+            spec.get_subkey_algo(),
+            expires,
+            subkey_flags,
+        );
+        if let Err(err) = &subkey {
+            return Err(format!(
+                "Unable to add encryption subkey to {}: {}",
+                fingerprint, err
+            ));
+        }
+
+        Ok(fingerprint)
+    }
+
+    fn generate_key_interactively(&mut self) -> Result<(), String> {
+        let res = pass_env::gpg_interactive_command(&["--full-gen-key"]);
+        if let Err(err) = &res {
+            return Err(err.to_string());
+        }
+        Ok(())
+    }
+
+    fn sign_detached(&mut self, data: &[u8]) -> Result<Vec<u8>, String> {
+        let mut signature = Vec::new();
+        let armor = self.context.armor();
+        self.context.set_armor(false);
+        let sign_res = self.context.sign_detached(data, &mut signature);
+        self.context.set_armor(armor);
+        if let Err(err) = &sign_res {
+            return Err(err.to_string());
+        }
+        Ok(signature)
+    }
+
+    fn verify_detached(&mut self, signature: &[u8], data: &[u8]) -> Result<Vec<String>, String> {
+        let verify_res = self.context.verify_detached(signature, data);
+        if let Err(err) = &verify_res {
+            return Err(err.to_string());
+        }
+
+        let mut sig_fingerprints = Vec::new();
+        for sig in verify_res.unwrap().signatures() {
+            if sig.status().is_err() || sig.fingerprint().is_err() {
+                continue;
+            }
+            sig_fingerprints.push(sig.fingerprint().unwrap().to_string());
+        }
+        let mut signers = Vec::new();
+        for sig_fingerprint in sig_fingerprints {
+            let signer_key = self.context.get_key(sig_fingerprint.as_str());
+            if let Ok(Ok(signer_fingerprint)) = signer_key.as_ref().map(|key| key.fingerprint()) {
+                signers.push(signer_fingerprint.to_string());
+            }
+            signers.push(sig_fingerprint);
+        }
+        Ok(signers)
+    }
+
+    fn encrypt(&mut self, recipients: &[&str], plaintext: &[u8]) -> Result<Vec<u8>, String> {
+        let mut keys = Vec::new();
+        for recipient in recipients {
+            let found = self.context.find_keys(vec![*recipient]);
+            if found.is_err() {
+                return Err(format!("Unable to find key for recipient: {}", recipient));
+            }
+            let key = found.unwrap().find_map(Result::ok);
+            if key.is_none() {
+                return Err(format!("Unable to find key for recipient: {}", recipient));
+            }
+            keys.push(key.unwrap());
+        }
+
+        let mut ciphertext = Vec::new();
+        let armor = self.context.armor();
+        self.context.set_armor(false);
+        let encrypt_res = self.context.encrypt_with_flags(
+            &keys,
+            plaintext,
+            &mut ciphertext,
+            gpgme::EncryptFlags::NO_ENCRYPT_TO,
+        );
+        self.context.set_armor(armor);
+        if let Err(err) = &encrypt_res {
+            return Err(err.to_string());
+        }
+        Ok(ciphertext)
+    }
+
+    fn decrypt(&mut self, ciphertext: &[u8]) -> Result<Decrypted, String> {
+        let mut plaintext = Vec::new();
+        let decrypt_res = self.context.decrypt(ciphertext, &mut plaintext);
+        if let Err(err) = &decrypt_res {
+            return Err(err.to_string());
+        }
+
+        let mut recipients = Vec::new();
+        for recipient in decrypt_res.unwrap().recipients() {
+            let key_id = recipient.key_id();
+            if let Ok(key_id) = &key_id {
+                recipients.push(key_id.to_string());
+            }
+        }
+        Ok(Decrypted {
+            plaintext,
+            recipients,
+        })
+    }
+
+    fn staging(&self) -> Result<Box<dyn KeyBackend>, String> {
+        Ok(Box::new(GpgmeBackend::new_staging()?))
+    }
+}
+
+impl Drop for GpgmeBackend {
+    fn drop(&mut self) {
+        if self.staging_home.is_none() {
+            return;
+        }
+        let home_dir = self.staging_home.as_ref().unwrap().to_str().unwrap();
+        let kill = command::Invocation::new("gpgconf", &["--homedir", home_dir, "--kill", "all"])
+            .timeout(GPGCONF_TIMEOUT);
+        let _ = command::run(&kill);
+        let _ = fs::remove_dir_all(home_dir);
+    }
+}
+
+fn create_context(home_dir: Option<&Path>) -> Result<Context, String> {
+    let context = Context::from_protocol(gpgme::Protocol::OpenPgp);
+    if context.is_err() {
+        return Err("Unable to get gpg context".to_string());
+    }
+    let mut context = context.unwrap();
+    if let Some(home_dir) = home_dir {
+        let home_res = context.set_engine_home_dir(home_dir.to_str().unwrap());
+        if home_res.is_err() {
+            return Err("Unable to point gpg at staging keyring".to_string());
+        }
+    }
+    context.set_armor(true);
+    context.clear_signers();
+    let mut key_list_mode = KeyListMode::empty();
+    key_list_mode.insert(KeyListMode::LOCAL);
+    key_list_mode.insert(KeyListMode::SIGS);
+    let list_mode_res = context.set_key_list_mode(key_list_mode);
+    if list_mode_res.is_err() {
+        return Err("Unable to read signatures from gpg context".to_string());
+    }
+
+    Ok(context)
+}
+
+fn collect_records<I>(keys: I, kind: &str) -> Vec<KeyRecord>
+where
+    I: Iterator<Item = gpgme::Result<gpgme::Key>>,
+{
+    let mut records = Vec::new();
+    for key in keys {
+        if key.is_err() {
+            eprintln!("Unable to read {}", kind);
+            continue;
+        }
+        let record = key_record(&key.unwrap());
+        if let Err(err) = &record {
+            eprintln!("Unable to read {}: {}", kind, err);
+            continue;
+        }
+        records.push(record.unwrap());
+    }
+    records
+}
+
+fn key_record(key: &gpgme::Key) -> Result<KeyRecord, String> {
+    let fingerprint = key.fingerprint();
+    if fingerprint.is_err() {
+        return Err("key has no readable fingerprint".to_string());
+    }
+
+    let mut user_ids = Vec::new();
+    for user_id in key.user_ids() {
+        let mut certifications = Vec::new();
+        for sig in user_id.signatures() {
+            let status = if sig.status().code() == gpgme::Error::BAD_SIGNATURE.code() {
+                CertStatus::Bad
+            } else if sig.is_invalid() || sig.status() != gpgme::Error::NO_ERROR {
+                CertStatus::Unknown
+            } else {
+                CertStatus::Good
+            };
+            certifications.push(Certification {
+                signer: sig.signer_key_id().ok().map(str::to_string),
+                status,
+                revocation: sig.is_revocation(),
+                expired: sig.is_expired(),
+            });
+        }
+        user_ids.push(UserIdRecord {
+            user_id: user_id.id().unwrap_or_default().to_string(),
+            email: user_id.email().ok().map(str::to_string),
+            certifications,
+        });
+    }
+
+    Ok(KeyRecord {
+        fingerprint: fingerprint.unwrap().to_string(),
+        user_ids,
+        has_secret: key.has_secret() || key.subkeys().any(|subkey| subkey.is_secret()),
+        revoked: key.is_revoked(),
+        expired: key.is_expired(),
+        can_encrypt: key.can_encrypt(),
+    })
+}
@@ -0,0 +1,276 @@
+#[cfg(test)]
+mod fake;
+mod gnupg;
+pub mod packets;
+#[cfg(feature = "sequoia")]
+mod sequoia;
+
+use super::generate::KeySpec;
+#[cfg(test)]
+pub use fake::FakeBackend;
+pub use gnupg::GpgmeBackend;
+#[cfg(feature = "sequoia")]
+pub use sequoia::SequoiaBackend;
+use std::cell::RefCell;
+use std::env;
+#[cfg(feature = "sequoia")]
+use std::path::PathBuf;
+use std::sync::Arc;
+
+pub const BACKEND_VAR_NAME: &str = "PASS_PROVISION_BACKEND";
+pub const KEYRING_VAR_NAME: &str = "PASS_PROVISION_KEYRING";
+const DEFAULT_BACKEND: &str = "gpgme";
+
+pub type Opener = dyn Fn() -> Result<Box<dyn KeyBackend>, String> + Send + Sync;
+
+thread_local! {
+    static OPENER: RefCell<Option<Arc<Opener>>> = const { RefCell::new(None) };
+}
+
+pub trait KeyBackend {
+    fn name(&self) -> &'static str;
+    fn list_keys(&mut self) -> Result<Vec<KeyRecord>, String>;
+    fn list_secret_keys(&mut self) -> Result<Vec<KeyRecord>, String>;
+    fn get_key(&mut self, id: &str) -> Option<KeyRecord>;
+    fn get_secret_key(&mut self, id: &str) -> Option<KeyRecord>;
+    fn read_keys(&mut self, data: &[u8]) -> Result<Vec<KeyRecord>, String>;
+    fn import(&mut self, data: &[u8]) -> Result<ImportSummary, String>;
+    fn export(&mut self, fingerprints: &[&str], filter: ExportFilter) -> Result<Vec<u8>, String>;
+    fn add_signer(&mut self, fingerprint: &str) -> Result<(), String>;
+    fn signers(&self) -> Vec<String>;
+    fn certify(&mut self, fingerprint: &str) -> Result<(), String>;
+    fn generate_key(&mut self, spec: &KeySpec) -> Result<String, String>;
+    fn generate_key_interactively(&mut self) -> Result<(), String>;
+    fn sign_detached(&mut self, data: &[u8]) -> Result<Vec<u8>, String>;
+    fn verify_detached(&mut self, signature: &[u8], data: &[u8]) -> Result<Vec<String>, String>;
+    fn encrypt(&mut self, recipients: &[&str], plaintext: &[u8]) -> Result<Vec<u8>, String>;
+    fn decrypt(&mut self, ciphertext: &[u8]) -> Result<Decrypted, String>;
+
+    /// The key IDs a message is encrypted to, read without decrypting it.
+    fn encrypted_to(&mut self, ciphertext: &[u8]) -> Result<Vec<String>, String> {
+        packets::encrypted_to(ciphertext)
+    }
+    fn staging(&self) -> Result<Box<dyn KeyBackend>, String>;
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExportFilter {
+    Full,
+    Minimal,
+    Clean,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CertStatus {
+    Good,
+    Bad,
+    Unknown,
+}
+
+#[derive(Clone, Debug)]
+pub struct Certification {
+    signer: Option<String>,
+    status: CertStatus,
+    revocation: bool,
+    expired: bool,
+}
+
+impl Certification {
+    pub fn get_signer(&self) -> Option<&str> {
+        self.signer.as_deref()
+    }
+
+    pub fn get_status(&self) -> CertStatus {
+        self.status
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.status == CertStatus::Good && !self.revocation && !self.expired
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct UserIdRecord {
+    user_id: String,
+    email: Option<String>,
+    certifications: Vec<Certification>,
+}
+
+impl UserIdRecord {
+    pub fn get_user_id(&self) -> &str {
+        &self.user_id
+    }
+
+    pub fn get_email(&self) -> Option<&str> {
+        self.email.as_deref()
+    }
+
+    pub fn certifications(&self) -> &[Certification] {
+        &self.certifications
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct KeyRecord {
+    fingerprint: String,
+    user_ids: Vec<UserIdRecord>,
+    has_secret: bool,
+    revoked: bool,
+    expired: bool,
+    can_encrypt: bool,
+}
+
+impl KeyRecord {
+    pub fn get_fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+
+    pub fn user_ids(&self) -> &[UserIdRecord] {
+        &self.user_ids
+    }
+
+    pub fn has_secret(&self) -> bool {
+        self.has_secret
+    }
+
+    pub fn is_revoked(&self) -> bool {
+        self.revoked
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expired
+    }
+
+    /// Whether the key has a subkey usable for encryption.
+    pub fn can_encrypt(&self) -> bool {
+        self.can_encrypt
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ImportedKey {
+    fingerprint: String,
+    new: bool,
+}
+
+impl ImportedKey {
+    pub fn get_fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+
+    pub fn is_new(&self) -> bool {
+        self.new
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ImportSummary {
+    imports: Vec<ImportedKey>,
+    secret_considered: u32,
+    new_signatures: u32,
+    new_subkeys: u32,
+    new_user_ids: u32,
+    new_revocations: u32,
+}
+
+impl ImportSummary {
+    pub fn imports(&self) -> &[ImportedKey] {
+        &self.imports
+    }
+
+    pub fn secret_considered(&self) -> u32 {
+        self.secret_considered
+    }
+
+    pub fn new_signatures(&self) -> u32 {
+        self.new_signatures
+    }
+
+    pub fn new_subkeys(&self) -> u32 {
+        self.new_subkeys
+    }
+
+    pub fn new_user_ids(&self) -> u32 {
+        self.new_user_ids
+    }
+
+    pub fn new_revocations(&self) -> u32 {
+        self.new_revocations
+    }
+}
+
+pub struct Decrypted {
+    plaintext: Vec<u8>,
+    recipients: Vec<String>,
+}
+
+impl Decrypted {
+    pub fn get_plaintext(&self) -> &[u8] {
+        &self.plaintext
+    }
+
+    pub fn get_recipients(&self) -> &[String] {
+        &self.recipients
+    }
+}
+
+pub fn backends() -> Vec<&'static str> {
+    let mut names = vec![DEFAULT_BACKEND];
+    if cfg!(feature = "sequoia") {
+        names.push("sequoia");
+    }
+    names
+}
+
+pub fn open(name: &str) -> Result<Box<dyn KeyBackend>, String> {
+    match name {
+        "gpgme" => Ok(Box::new(GpgmeBackend::new()?)),
+        #[cfg(feature = "sequoia")]
+        "sequoia" => Ok(Box::new(SequoiaBackend::open(&keyring_dir()?)?)),
+        _ => Err(format!(
+            "Unknown key backend: {}, expected one of: {}",
+            name,
+            backends().join(", ")
+        )),
+    }
+}
+
+pub fn open_default() -> Result<Box<dyn KeyBackend>, String> {
+    let opener = current_opener();
+    if let Some(opener) = opener {
+        return opener();
+    }
+    open(&selected())
+}
+
+pub fn current_opener() -> Option<Arc<Opener>> {
+    OPENER.with(|opener| opener.borrow().clone())
+}
+
+pub fn set_opener(opener: Option<Arc<Opener>>) {
+    OPENER.with(|current| *current.borrow_mut() = opener);
+}
+
+fn selected() -> String {
+    let name = env::var(BACKEND_VAR_NAME);
+    if name.is_err() || name.as_ref().unwrap().is_empty() {
+        return DEFAULT_BACKEND.to_string();
+    }
+    name.unwrap()
+}
+
+#[cfg(feature = "sequoia")]
+fn keyring_dir() -> Result<PathBuf, String> {
+    let keyring = env::var_os(KEYRING_VAR_NAME);
+    if let Some(keyring) = keyring {
+        return Ok(PathBuf::from(keyring));
+    }
+    let data_dir = dirs::data_dir();
+    if data_dir.is_none() {
+        return Err(format!(
+            "Unable to locate a data directory, please set {}",
+            KEYRING_VAR_NAME
+        ));
+    }
+    Ok(data_dir.unwrap().join("pass-provision").join("keyring"))
+}
@@ -0,0 +1,154 @@
+//! Just enough OpenPGP packet parsing to tell who a message is encrypted to
+//! without decrypting it.
+
+const PKESK_TAG: u8 = 1;
+const SKESK_TAG: u8 = 3;
+const MARKER_TAG: u8 = 10;
+const ARMOR_HEADER: &[u8] = b"-----BEGIN PGP MESSAGE-----";
+
+/// The key IDs (fingerprints, for v6 packets) named by the public-key
+/// encrypted session key packets that open `message`. An anonymous recipient
+/// comes back as all zeros.
+pub fn encrypted_to(message: &[u8]) -> Result<Vec<String>, String> {
+    let message = if message.starts_with(ARMOR_HEADER) {
+        dearmor(message)?
+    } else {
+        message.to_vec()
+    };
+
+    let mut recipients = Vec::new();
+    let mut rest = &message[..];
+    while !rest.is_empty() {
+        let tag = packet_tag(rest[0]);
+        if tag.is_none() {
+            return Err("Not an OpenPGP message".to_string());
+        }
+        let tag = tag.unwrap();
+        if tag != PKESK_TAG && tag != SKESK_TAG && tag != MARKER_TAG {
+            break;
+        }
+        let (body, next) = split_packet(rest)?;
+        if tag == PKESK_TAG {
+            recipients.push(pkesk_recipient(body)?);
+        }
+        rest = next;
+    }
+    if recipients.is_empty() {
+        return Err("Message isn't encrypted to any public key".to_string());
+    }
+    Ok(recipients)
+}
+
+fn packet_tag(header: u8) -> Option<u8> {
+    if header & 0x80 == 0 {
+        return None;
+    }
+    if header & 0x40 != 0 {
+        Some(header & 0x3f)
+    } else {
+        Some((header >> 2) & 0x0f)
+    }
+}
+
+/// Splits the first packet's body from the packets after it.
+fn split_packet(data: &[u8]) -> Result<(&[u8], &[u8]), String> {
+    let truncated = || "Truncated OpenPGP packet".to_string();
+    let (header_len, body_len) = if data[0] & 0x40 != 0 {
+        let first = *data.get(1).ok_or_else(truncated)? as usize;
+        match first {
+            0..=191 => (2, first),
+            192..=223 => {
+                let second = *data.get(2).ok_or_else(truncated)? as usize;
+                (3, ((first - 192) << 8) + second + 192)
+            }
+            255 => (6, read_be(data.get(2..6).ok_or_else(truncated)?)),
+            _ => return Err("Unexpected partial length packet".to_string()),
+        }
+    } else {
+        match data[0] & 0x03 {
+            0 => (2, read_be(data.get(1..2).ok_or_else(truncated)?)),
+            1 => (3, read_be(data.get(1..3).ok_or_else(truncated)?)),
+            2 => (5, read_be(data.get(1..5).ok_or_else(truncated)?)),
+            _ => return Err("Unexpected indeterminate length packet".to_string()),
+        }
+    };
+    let end = header_len + body_len;
+    if data.len() < end {
+        return Err(truncated());
+    }
+    Ok((&data[header_len..end], &data[end..]))
+}
+
+fn read_be(bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .fold(0, |value, byte| (value << 8) | *byte as usize)
+}
+
+fn pkesk_recipient(body: &[u8]) -> Result<String, String> {
+    let truncated = || "Truncated session key packet".to_string();
+    match body.first() {
+        Some(3) => Ok(to_hex(body.get(1..9).ok_or_else(truncated)?)),
+        Some(6) => {
+            let len = *body.get(1).ok_or_else(truncated)? as usize;
+            if len == 0 {
+                return Ok("0".repeat(16));
+            }
+            // The length covers the key version byte before the fingerprint
+            Ok(to_hex(body.get(3..2 + len).ok_or_else(truncated)?))
+        }
+        Some(version) => Err(format!(
+            "Unsupported session key packet version {}",
+            version
+        )),
+        None => Err(truncated()),
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02X}", byte)).collect()
+}
+
+fn dearmor(message: &[u8]) -> Result<Vec<u8>, String> {
+    let text = String::from_utf8_lossy(message);
+    let mut lines = text.lines().skip(1);
+    // Armor headers run up to the first blank line
+    for line in lines.by_ref() {
+        if line.trim().is_empty() {
+            break;
+        }
+    }
+    let mut encoded = String::new();
+    for line in lines {
+        let line = line.trim();
+        if line.starts_with('=') || line.starts_with("-----") {
+            break;
+        }
+        encoded += line;
+    }
+    decode_base64(&encoded).ok_or_else(|| "Malformed ASCII armor".to_string())
+}
+
+fn decode_base64(encoded: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in encoded.trim_end_matches('=').chars() {
+        let value = match c {
+            'A'..='Z' => c as u32 - 'A' as u32,
+            'a'..='z' => c as u32 - 'a' as u32 + 26,
+            '0'..='9' => c as u32 - '0' as u32 + 52,
+            '+' => 62,
+            '/' => 63,
+            _ => return None,
+        };
+        buffer = (buffer << 6) | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Some(bytes)
+}
@@ -0,0 +1,874 @@
+use super::CertStatus;
+use super::Certification;
+use super::Decrypted;
+use super::ExportFilter;
+use super::ImportSummary;
+use super::ImportedKey;
+use super::KeyBackend;
+use super::KeyRecord;
+use super::UserIdRecord;
+use crate::key::generate::KeySpec;
+use crate::prompt;
+use openpgp::cert::prelude::*;
+use openpgp::crypto::KeyPair;
+use openpgp::crypto::SessionKey;
+use openpgp::packet::key;
+use openpgp::packet::Key;
+use openpgp::packet::Signature;
+use openpgp::packet::UserID;
+use openpgp::packet::PKESK;
+use openpgp::packet::SKESK;
+use openpgp::parse::stream::DecryptionHelper;
+use openpgp::parse::stream::DecryptorBuilder;
+use openpgp::parse::stream::DetachedVerifierBuilder;
+use openpgp::parse::stream::MessageLayer;
+use openpgp::parse::stream::MessageStructure;
+use openpgp::parse::stream::VerificationHelper;
+use openpgp::parse::Parse;
+use openpgp::policy::StandardPolicy;
+use openpgp::serialize::stream::Encryptor;
+use openpgp::serialize::stream::LiteralWriter;
+use openpgp::serialize::stream::Message;
+use openpgp::serialize::stream::Recipient;
+use openpgp::serialize::stream::Signer;
+use openpgp::serialize::Serialize;
+use openpgp::types::KeyFlags;
+use openpgp::types::RevocationStatus;
+use openpgp::types::SignatureType;
+use openpgp::types::SymmetricAlgorithm;
+use openpgp::Cert;
+use openpgp::KeyHandle;
+use openpgp::KeyID;
+use openpgp::Packet;
+use sequoia_openpgp as openpgp;
+use std::fs;
+use std::io::Read;
+use std::io::Write;
+use std::os::unix::fs::DirBuilderExt;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::SystemTime;
+
+const KEYRING_FILE: &str = "keyring.pgp";
+
+pub struct SequoiaBackend {
+    path: Option<PathBuf>,
+    certs: Vec<Cert>,
+    signers: Vec<String>,
+    policy: StandardPolicy<'static>,
+}
+
+impl SequoiaBackend {
+    pub fn open(dir: &Path) -> Result<SequoiaBackend, String> {
+        let create_res = fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir);
+        if let Err(err) = &create_res {
+            return Err(format!(
+                "Unable to create keyring directory {}: {}",
+                dir.display(),
+                err
+            ));
+        }
+
+        let path = dir.join(KEYRING_FILE);
+        let mut certs = Vec::new();
+        if path.exists() {
+            let data = fs::read(&path);
+            if let Err(err) = &data {
+                return Err(format!(
+                    "Unable to read keyring {}: {}",
+                    path.display(),
+                    err
+                ));
+            }
+            let parsed = parse_certs(&data.unwrap());
+            if let Err(err) = &parsed {
+                return Err(format!(
+                    "Unable to read keyring {}: {}",
+                    path.display(),
+                    err
+                ));
+            }
+            certs = parsed.unwrap();
+        }
+
+        Ok(SequoiaBackend {
+            path: Some(path),
+            certs,
+            signers: Vec::new(),
+            policy: StandardPolicy::new(),
+        })
+    }
+
+    fn in_memory() -> SequoiaBackend {
+        SequoiaBackend {
+            path: None,
+            certs: Vec::new(),
+            signers: Vec::new(),
+            policy: StandardPolicy::new(),
+        }
+    }
+
+    fn save(&self) -> Result<(), String> {
+        if self.path.is_none() {
+            return Ok(());
+        }
+        let path = self.path.as_ref().unwrap();
+
+        let mut data = Vec::new();
+        for cert in &self.certs {
+            let res = cert.as_tsk().serialize(&mut data);
+            if let Err(err) = &res {
+                return Err(format!("Unable to serialize keyring: {}", err));
+            }
+        }
+
+        let tmp_path = path.with_extension("pgp.tmp");
+        let file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&tmp_path);
+        if let Err(err) = &file {
+            return Err(format!(
+                "Unable to write keyring {}: {}",
+                tmp_path.display(),
+                err
+            ));
+        }
+        let write_res = file.unwrap().write_all(&data);
+        if let Err(err) = &write_res {
+            return Err(format!(
+                "Unable to write keyring {}: {}",
+                tmp_path.display(),
+                err
+            ));
+        }
+        let rename_res = fs::rename(&tmp_path, path);
+        if let Err(err) = &rename_res {
+            return Err(format!(
+                "Unable to write keyring {}: {}",
+                path.display(),
+                err
+            ));
+        }
+        Ok(())
+    }
+
+    fn find(&self, id: &str) -> Option<usize> {
+        let id = normalize_id(id);
+        self.certs.iter().position(|cert| {
+            cert.keys()
+                .any(|ka| ka.key().fingerprint().to_hex() == id || ka.key().keyid().to_hex() == id)
+        })
+    }
+
+    fn find_recipient(&self, recipient: &str) -> Option<usize> {
+        let found = self.find(recipient);
+        if found.is_some() {
+            return found;
+        }
+        let email = recipient.trim_start_matches('<').trim_end_matches('>');
+        self.certs.iter().position(|cert| {
+            cert.userids()
+                .any(|ua| ua.userid().email().ok().flatten() == Some(email))
+        })
+    }
+
+    fn find_issuer(
+        &self,
+        cert: &Cert,
+        issuers: &[KeyHandle],
+    ) -> Option<Key<key::PublicParts, key::UnspecifiedRole>> {
+        for candidate in std::iter::once(cert).chain(self.certs.iter()) {
+            for issuer in issuers {
+                let found = candidate.keys().key_handle(issuer.clone()).next();
+                if let Some(found) = found {
+                    return Some(found.key().clone().role_into_unspecified());
+                }
+            }
+        }
+        None
+    }
+
+    fn certification_status(&self, cert: &Cert, userid: &UserID, sig: &Signature) -> CertStatus {
+        let signer = self.find_issuer(cert, &sig.get_issuers());
+        if signer.is_none() {
+            return CertStatus::Unknown;
+        }
+        let signer = signer.unwrap();
+        let primary = cert.primary_key().key();
+        let verified = if sig.typ() == SignatureType::CertificationRevocation {
+            sig.verify_userid_revocation(&signer, primary, userid)
+        } else {
+            sig.verify_userid_binding(&signer, primary, userid)
+        };
+        if verified.is_err() {
+            return CertStatus::Bad;
+        }
+        CertStatus::Good
+    }
+
+    fn record(&self, cert: &Cert) -> KeyRecord {
+        let mut user_ids = Vec::new();
+        for ua in cert.userids() {
+            let mut certifications = Vec::new();
+            let sigs = ua
+                .self_signatures()
+                .chain(ua.certifications())
+                .chain(ua.self_revocations())
+                .chain(ua.other_revocations());
+            for sig in sigs {
+                let signer = sig
+                    .get_issuers()
+                    .first()
+                    .map(|issuer| KeyID::from(issuer).to_hex());
+                let expired = sig
+                    .signature_expiration_time()
+                    .map(|expires| expires <= SystemTime::now())
+                    .unwrap_or(false);
+                certifications.push(Certification {
+                    signer,
+                    status: self.certification_status(cert, ua.userid(), sig),
+                    revocation: sig.typ() == SignatureType::CertificationRevocation,
+                    expired,
+                });
+            }
+            user_ids.push(UserIdRecord {
+                user_id: String::from_utf8_lossy(ua.userid().value()).to_string(),
+                email: ua.userid().email().ok().flatten().map(str::to_string),
+                certifications,
+            });
+        }
+
+        let revoked = matches!(
+            cert.revocation_status(&self.policy, None),
+            RevocationStatus::Revoked(_)
+        );
+        let valid = cert.with_policy(&self.policy, None);
+        let expired = valid.is_ok() && valid.as_ref().unwrap().alive().is_err();
+        let can_encrypt = valid.is_ok()
+            && valid
+                .unwrap()
+                .keys()
+                .supported()
+                .key_flags(
+                    KeyFlags::empty()
+                        .set_transport_encryption()
+                        .set_storage_encryption(),
+                )
+                .next()
+                .is_some();
+
+        KeyRecord {
+            fingerprint: cert.fingerprint().to_hex(),
+            user_ids,
+            has_secret: cert.is_tsk(),
+            revoked,
+            expired,
+            can_encrypt,
+        }
+    }
+
+    fn filter_certifications(&self, cert: &Cert, filter: ExportFilter) -> openpgp::Result<Cert> {
+        if filter == ExportFilter::Full {
+            return Ok(cert.clone());
+        }
+
+        let own_keys: Vec<KeyHandle> = cert.keys().map(|ka| ka.key().key_handle()).collect();
+        let mut packets = Vec::new();
+        let mut userid: Option<UserID> = None;
+        for packet in cert.clone().into_packets() {
+            match &packet {
+                Packet::UserID(current) => userid = Some(current.clone()),
+                Packet::UserAttribute(_) | Packet::PublicSubkey(_) => userid = None,
+                Packet::Signature(sig) if userid.is_some() && is_certification(sig) => {
+                    let own = sig
+                        .get_issuers()
+                        .iter()
+                        .any(|issuer| own_keys.iter().any(|key| key.aliases(issuer)));
+                    let keep = own
+                        || (filter == ExportFilter::Clean
+                            && self.certification_status(cert, userid.as_ref().unwrap(), sig)
+                                == CertStatus::Good);
+                    if !keep {
+                        continue;
+                    }
+                }
+                _ => {}
+            }
+            packets.push(packet);
+        }
+        Cert::from_packets(packets.into_iter())
+    }
+
+    fn signing_keypair(&self, fingerprint: &str) -> Result<KeyPair, String> {
+        let index = self.find(fingerprint);
+        if index.is_none() {
+            return Err(format!("No secret key found for: {}", fingerprint));
+        }
+        let cert = &self.certs[index.unwrap()];
+        let valid = cert.with_policy(&self.policy, None);
+        if let Err(err) = &valid {
+            return Err(format!("Unable to use key {}: {}", fingerprint, err));
+        }
+        let signing_key = valid
+            .unwrap()
+            .keys()
+            .unencrypted_secret()
+            .for_signing()
+            .alive()
+            .revoked(false)
+            .next();
+        if signing_key.is_none() {
+            return Err(format!("No usable signing key found for: {}", fingerprint));
+        }
+        let keypair = signing_key.unwrap().key().clone().into_keypair();
+        if keypair.is_err() {
+            return Err(format!(
+                "Unable to use key {}: {}",
+                fingerprint,
+                keypair.err().unwrap()
+            ));
+        }
+        Ok(keypair.unwrap())
+    }
+
+    fn certifying_keypair(&self) -> Result<KeyPair, String> {
+        for signer in &self.signers {
+            let index = self.find(signer);
+            if index.is_none() {
+                continue;
+            }
+            let primary = self.certs[index.unwrap()].primary_key().key().clone();
+            let secret = primary.parts_into_secret();
+            if secret.is_err() {
+                continue;
+            }
+            let keypair = secret.unwrap().into_keypair();
+            if let Ok(keypair) = keypair {
+                return Ok(keypair);
+            }
+        }
+        Err("No signing key available to certify with".to_string())
+    }
+}
+
+impl KeyBackend for SequoiaBackend {
+    fn name(&self) -> &'static str {
+        "sequoia"
+    }
+
+    fn list_keys(&mut self) -> Result<Vec<KeyRecord>, String> {
+        Ok(self.certs.iter().map(|cert| self.record(cert)).collect())
+    }
+
+    fn list_secret_keys(&mut self) -> Result<Vec<KeyRecord>, String> {
+        Ok(self
+            .certs
+            .iter()
+            .filter(|cert| cert.is_tsk())
+            .map(|cert| self.record(cert))
+            .collect())
+    }
+
+    fn get_key(&mut self, id: &str) -> Option<KeyRecord> {
+        let index = self.find(id)?;
+        Some(self.record(&self.certs[index]))
+    }
+
+    fn get_secret_key(&mut self, id: &str) -> Option<KeyRecord> {
+        let index = self.find(id);
+        if index.is_none() || !self.certs[index.unwrap()].is_tsk() {
+            return None;
+        }
+        Some(self.record(&self.certs[index.unwrap()]))
+    }
+
+    fn read_keys(&mut self, data: &[u8]) -> Result<Vec<KeyRecord>, String> {
+        let certs = parse_certs(data);
+        if let Err(err) = &certs {
+            return Err(err.to_string());
+        }
+        Ok(certs
+            .unwrap()
+            .iter()
+            .map(|cert| self.record(cert))
+            .collect())
+    }
+
+    fn import(&mut self, data: &[u8]) -> Result<ImportSummary, String> {
+        let certs = parse_certs(data);
+        if let Err(err) = &certs {
+            return Err(err.to_string());
+        }
+
+        let mut summary = ImportSummary::default();
+        for cert in certs.unwrap() {
+            if cert.is_tsk() {
+                summary.secret_considered += 1;
+            }
+            let fingerprint = cert.fingerprint().to_hex();
+            let index = self.find(&fingerprint);
+            if index.is_none() {
+                self.certs.push(cert);
+                summary.imports.push(ImportedKey {
+                    fingerprint,
+                    new: true,
+                });
+                continue;
+            }
+
+            let index = index.unwrap();
+            let before = PacketCounts::of(&self.certs[index]);
+            let merged = self.certs[index].clone().merge_public_and_secret(cert);
+            if let Err(err) = &merged {
+                return Err(format!("Unable to merge key {}: {}", fingerprint, err));
+            }
+            let merged = merged.unwrap();
+            let after = PacketCounts::of(&merged);
+            summary.new_signatures += after.signatures.saturating_sub(before.signatures);
+            summary.new_subkeys += after.subkeys.saturating_sub(before.subkeys);
+            summary.new_user_ids += after.user_ids.saturating_sub(before.user_ids);
+            summary.new_revocations += after.revocations.saturating_sub(before.revocations);
+            self.certs[index] = merged;
+            summary.imports.push(ImportedKey {
+                fingerprint,
+                new: false,
+            });
+        }
+
+        self.save()?;
+        Ok(summary)
+    }
+
+    fn export(&mut self, fingerprints: &[&str], filter: ExportFilter) -> Result<Vec<u8>, String> {
+        let mut certs = Vec::new();
+        for fingerprint in fingerprints {
+            let index = self.find(fingerprint);
+            if index.is_none() {
+                return Err(format!(
+                    "Unable to locate key for exporting for fingerprint: {}",
+                    fingerprint
+                ));
+            }
+            let cert = self.filter_certifications(&self.certs[index.unwrap()], filter);
+            if let Err(err) = &cert {
+                return Err(format!(
+                    "Unable to export key for fingerprint: {}: {}",
+                    fingerprint, err
+                ));
+            }
+            certs.push(cert.unwrap());
+        }
+
+        let exported = armor_certs(&certs);
+        if let Err(err) = &exported {
+            return Err(format!(
+                "Unable to export key for fingerprint: {}: {}",
+                fingerprints.join(" "),
+                err
+            ));
+        }
+        Ok(exported.unwrap())
+    }
+
+    fn add_signer(&mut self, fingerprint: &str) -> Result<(), String> {
+        let index = self.find(fingerprint);
+        if index.is_none() || !self.certs[index.unwrap()].is_tsk() {
+            return Err(format!("No secret key found for: {}", fingerprint));
+        }
+        let fingerprint = self.certs[index.unwrap()].fingerprint().to_hex();
+        if !self.signers.contains(&fingerprint) {
+            self.signers.push(fingerprint);
+        }
+        Ok(())
+    }
+
+    fn signers(&self) -> Vec<String> {
+        self.signers.clone()
+    }
+
+    fn certify(&mut self, fingerprint: &str) -> Result<(), String> {
+        let index = self.find(fingerprint);
+        if index.is_none() {
+            return Err(format!("Unable to find key: {}", fingerprint));
+        }
+        let index = index.unwrap();
+        let mut keypair = self.certifying_keypair()?;
+
+        let cert = self.certs[index].clone();
+        let mut certifications = Vec::new();
+        for ua in cert.userids() {
+            let certification = ua.userid().certify(
+                &mut keypair,
+                &cert,
+                SignatureType::GenericCertification,
+                None,
+                None,
+            );
+            if let Err(err) = &certification {
+                return Err(err.to_string());
+            }
+            certifications.push(certification.unwrap());
+        }
+
+        let updated = cert.insert_packets(certifications);
+        if let Err(err) = &updated {
+            return Err(err.to_string());
+        }
+        self.certs[index] = updated.unwrap().0;
+        self.save()
+    }
+
+    fn generate_key(&mut self, spec: &KeySpec) -> Result<String, String> {
+        if spec.has_passphrase() {
+            return Err(
+                "The sequoia backend can't protect keys with a passphrase, use --no-passphrase"
+                    .to_string(),
+            );
+        }
+        let suite = cipher_suite(spec.get_algo())?;
+        let subkey_suite = cipher_suite(spec.get_subkey_algo())?;
+        let validity = spec.get_expire().map(Duration::from_secs);
+
+        let generated = CertBuilder::new()
+            .add_userid(spec.get_user_id())
+            .set_cipher_suite(suite)
+            .set_primary_key_flags(KeyFlags::empty().set_certification().set_signing())
+            .set_validity_period(validity)
+            .add_subkey(
+                KeyFlags::empty()
+                    .set_transport_encryption()
+                    .set_storage_encryption(),
+                validity,
+                subkey_suite,
+            )
+            .generate();
+        if let Err(err) = &generated {
+            return Err(format!("Unable to generate key: {}", err));
+        }
+        let (cert, _) = generated.unwrap();
+        let fingerprint = cert.fingerprint().to_hex();
+        self.certs.push(cert);
+        self.save()?;
+        Ok(fingerprint)
+    }
+
+    fn generate_key_interactively(&mut self) -> Result<(), String> {
+        let prompter = prompt::current_prompter();
+        let name = prompter.ask("Real name:")?;
+        let email = prompter.ask("Email address:")?;
+        let spec = KeySpec::new(Some(name.trim()), email.trim(), None, None, false)?;
+        let fingerprint = self.generate_key(&spec)?;
+        println!("Generated key {}", fingerprint);
+        Ok(())
+    }
+
+    fn sign_detached(&mut self, data: &[u8]) -> Result<Vec<u8>, String> {
+        if self.signers.is_empty() {
+            return Err("No signing key configured".to_string());
+        }
+        let mut keypairs = Vec::new();
+        for signer in &self.signers {
+            keypairs.push(self.signing_keypair(signer)?);
+        }
+
+        let signature = sign_detached(keypairs, data);
+        if let Err(err) = &signature {
+            return Err(err.to_string());
+        }
+        Ok(signature.unwrap())
+    }
+
+    fn verify_detached(&mut self, signature: &[u8], data: &[u8]) -> Result<Vec<String>, String> {
+        let helper = Helper::new(self.certs.clone(), Vec::new());
+        let verifier = DetachedVerifierBuilder::from_bytes(signature)
+            .and_then(|builder| builder.with_policy(&self.policy, None, helper));
+        if verifier.is_err() {
+            return Err(verifier.err().unwrap().to_string());
+        }
+        let mut verifier = verifier.unwrap();
+        let verify_res = verifier.verify_bytes(data);
+        if let Err(err) = &verify_res {
+            return Err(err.to_string());
+        }
+        Ok(verifier.into_helper().signers)
+    }
+
+    fn encrypt(&mut self, recipients: &[&str], plaintext: &[u8]) -> Result<Vec<u8>, String> {
+        let mut valid_certs = Vec::new();
+        for recipient in recipients {
+            let index = self.find_recipient(recipient);
+            if index.is_none() {
+                return Err(format!("Unable to find key for recipient: {}", recipient));
+            }
+            let valid = self.certs[index.unwrap()].with_policy(&self.policy, None);
+            if let Err(err) = &valid {
+                return Err(format!(
+                    "Unable to use key for recipient: {}: {}",
+                    recipient, err
+                ));
+            }
+            valid_certs.push((recipient, valid.unwrap()));
+        }
+
+        let mut encryption_keys: Vec<Recipient> = Vec::new();
+        for (recipient, valid) in &valid_certs {
+            let found: Vec<Recipient> = valid
+                .keys()
+                .supported()
+                .alive()
+                .revoked(false)
+                .key_flags(
+                    KeyFlags::empty()
+                        .set_transport_encryption()
+                        .set_storage_encryption(),
+                )
+                .map(Recipient::from)
+                .collect();
+            if found.is_empty() {
+                return Err(format!(
+                    "No usable encryption key found for recipient: {}",
+                    recipient
+                ));
+            }
+            encryption_keys.extend(found);
+        }
+
+        let ciphertext = encrypt(encryption_keys, plaintext);
+        if let Err(err) = &ciphertext {
+            return Err(err.to_string());
+        }
+        Ok(ciphertext.unwrap())
+    }
+
+    fn decrypt(&mut self, ciphertext: &[u8]) -> Result<Decrypted, String> {
+        let mut keypairs = Vec::new();
+        for cert in self.certs.iter().filter(|cert| cert.is_tsk()) {
+            for ka in cert.keys().unencrypted_secret() {
+                let keypair = ka.key().clone().into_keypair();
+                if let Ok(keypair) = keypair {
+                    keypairs.push(keypair);
+                }
+            }
+        }
+
+        let helper = Helper::new(self.certs.clone(), keypairs);
+        let decryptor = DecryptorBuilder::from_bytes(ciphertext)
+            .and_then(|builder| builder.with_policy(&self.policy, None, helper));
+        if decryptor.is_err() {
+            return Err(decryptor.err().unwrap().to_string());
+        }
+        let mut decryptor = decryptor.unwrap();
+        let mut plaintext = Vec::new();
+        let read_res = decryptor.read_to_end(&mut plaintext);
+        if let Err(err) = &read_res {
+            return Err(err.to_string());
+        }
+
+        Ok(Decrypted {
+            plaintext,
+            recipients: decryptor.into_helper().recipients,
+        })
+    }
+
+    fn staging(&self) -> Result<Box<dyn KeyBackend>, String> {
+        Ok(Box::new(SequoiaBackend::in_memory()))
+    }
+}
+
+struct Helper {
+    certs: Vec<Cert>,
+    keypairs: Vec<KeyPair>,
+    signers: Vec<String>,
+    recipients: Vec<String>,
+}
+
+impl Helper {
+    fn new(certs: Vec<Cert>, keypairs: Vec<KeyPair>) -> Helper {
+        Helper {
+            certs,
+            keypairs,
+            signers: Vec::new(),
+            recipients: Vec::new(),
+        }
+    }
+}
+
+impl VerificationHelper for Helper {
+    fn get_certs(&mut self, _ids: &[KeyHandle]) -> openpgp::Result<Vec<Cert>> {
+        Ok(self.certs.clone())
+    }
+
+    fn check(&mut self, structure: MessageStructure) -> openpgp::Result<()> {
+        for layer in structure.into_iter() {
+            if let MessageLayer::SignatureGroup { results } = layer {
+                for good in results.into_iter().flatten() {
+                    self.signers.push(good.ka.cert().fingerprint().to_hex());
+                    self.signers.push(good.ka.key().fingerprint().to_hex());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl DecryptionHelper for Helper {
+    fn decrypt(
+        &mut self,
+        pkesks: &[PKESK],
+        _skesks: &[SKESK],
+        sym_algo: Option<SymmetricAlgorithm>,
+        decrypt: &mut dyn FnMut(Option<SymmetricAlgorithm>, &SessionKey) -> bool,
+    ) -> openpgp::Result<Option<Cert>> {
+        for pkesk in pkesks {
+            let recipient = pkesk.recipient();
+            if let Some(recipient) = recipient {
+                self.recipients.push(KeyID::from(recipient).to_hex());
+            }
+        }
+
+        for pkesk in pkesks {
+            let recipient = pkesk.recipient();
+            for keypair in self.keypairs.iter_mut() {
+                if recipient.is_some()
+                    && !recipient
+                        .as_ref()
+                        .unwrap()
+                        .aliases(keypair.public().key_handle())
+                {
+                    continue;
+                }
+                let session_key = pkesk.decrypt(keypair, sym_algo);
+                if let Some(session_key) = session_key {
+                    let (algo, session_key) = session_key;
+                    if decrypt(algo, &session_key) {
+                        return Ok(None);
+                    }
+                }
+            }
+        }
+        Err(
+            openpgp::Error::InvalidOperation("No secret key available to decrypt with".to_string())
+                .into(),
+        )
+    }
+}
+
+struct PacketCounts {
+    signatures: u32,
+    subkeys: u32,
+    user_ids: u32,
+    revocations: u32,
+}
+
+impl PacketCounts {
+    fn of(cert: &Cert) -> PacketCounts {
+        let mut counts = PacketCounts {
+            signatures: 0,
+            subkeys: 0,
+            user_ids: 0,
+            revocations: 0,
+        };
+        for packet in cert.clone().into_packets() {
+            match packet {
+                Packet::PublicSubkey(_) => counts.subkeys += 1,
+                Packet::UserID(_) => counts.user_ids += 1,
+                Packet::Signature(sig) if is_revocation(&sig) => counts.revocations += 1,
+                Packet::Signature(_) => counts.signatures += 1,
+                _ => {}
+            }
+        }
+        counts
+    }
+}
+
+fn parse_certs(data: &[u8]) -> openpgp::Result<Vec<Cert>> {
+    let mut certs = Vec::new();
+    for cert in CertParser::from_bytes(data)? {
+        certs.push(cert?);
+    }
+    Ok(certs)
+}
+
+fn armor_certs(certs: &[Cert]) -> openpgp::Result<Vec<u8>> {
+    let mut writer = openpgp::armor::Writer::new(Vec::new(), openpgp::armor::Kind::PublicKey)?;
+    for cert in certs {
+        cert.serialize(&mut writer)?;
+    }
+    Ok(writer.finalize()?)
+}
+
+fn sign_detached(keypairs: Vec<KeyPair>, data: &[u8]) -> openpgp::Result<Vec<u8>> {
+    let mut signature = Vec::new();
+    let mut keypairs = keypairs.into_iter();
+    let message = Message::new(&mut signature);
+    let mut signer = Signer::new(message, keypairs.next().unwrap())?.detached();
+    for keypair in keypairs {
+        signer = signer.add_signer(keypair)?;
+    }
+    let mut message = signer.build()?;
+    message.write_all(data)?;
+    message.finalize()?;
+    Ok(signature)
+}
+
+fn encrypt(recipients: Vec<Recipient>, plaintext: &[u8]) -> openpgp::Result<Vec<u8>> {
+    let mut ciphertext = Vec::new();
+    let message = Message::new(&mut ciphertext);
+    let message = Encryptor::for_recipients(message, recipients).build()?;
+    let mut message = LiteralWriter::new(message).build()?;
+    message.write_all(plaintext)?;
+    message.finalize()?;
+    Ok(ciphertext)
+}
+
+fn cipher_suite(algo: &str) -> Result<CipherSuite, String> {
+    match algo {
+        "ed25519" | "cv25519" => Ok(CipherSuite::Cv25519),
+        "ed448" | "cv448" => Ok(CipherSuite::Cv448),
+        "rsa2048" => Ok(CipherSuite::RSA2k),
+        "rsa3072" | "rsa" => Ok(CipherSuite::RSA3k),
+        "rsa4096" => Ok(CipherSuite::RSA4k),
+        "nistp256" => Ok(CipherSuite::P256),
+        "nistp384" => Ok(CipherSuite::P384),
+        "nistp521" => Ok(CipherSuite::P521),
+        _ => Err(format!(
+            "Unsupported algorithm for the sequoia backend: {}",
+            algo
+        )),
+    }
+}
+
+fn is_certification(sig: &Signature) -> bool {
+    matches!(
+        sig.typ(),
+        SignatureType::GenericCertification
+            | SignatureType::PersonaCertification
+            | SignatureType::CasualCertification
+            | SignatureType::PositiveCertification
+            | SignatureType::CertificationRevocation
+    )
+}
+
+fn is_revocation(sig: &Signature) -> bool {
+    matches!(
+        sig.typ(),
+        SignatureType::KeyRevocation
+            | SignatureType::SubkeyRevocation
+            | SignatureType::CertificationRevocation
+    )
+}
+
+fn normalize_id(id: &str) -> String {
+    id.trim()
+        .trim_start_matches("0x")
+        .replace(' ', "")
+        .to_uppercase()
+}
@@ -0,0 +1,257 @@
+use super::backend::ExportFilter;
+use super::backend::KeyBackend;
+use super::folder;
+use super::gpg_id;
+use super::recipients::matches_fingerprint;
+use super::recipients::matches_key;
+use super::recipients::RecipientResolver;
+use super::Key;
+use crate::config::Config;
+use crate::store;
+use std::collections::HashSet;
+use std::path::Path;
+use toml_edit::DocumentMut;
+use toml_edit::ImDocument;
+
+pub const COMPROMISED_FILE_PATH: &str = ".keys/compromised.toml";
+const COMPROMISED_FILE_NAME: &str = "compromised.toml";
+const COMPROMISED_LIST: &str = "compromised";
+const COMPROMISED_HEADER: &str =
+    "# Keys reported with `pass-provision compromise`, which sync refuses to import.\n";
+
+/// The fingerprints on the committed revocation list, which has to carry a
+/// valid signature just like .gpg-id.
+pub fn load_compromised(config: &Config) -> Result<Vec<String>, String> {
+    let path = folder::get_pass_dir().join(COMPROMISED_FILE_PATH);
+    if !store::exists(&path) {
+        return Ok(Vec::new());
+    }
+    let verify_res = gpg_id::verify_file(&path, config);
+    if let Err(err) = &verify_res {
+        return Err(format!(
+            "{}\nRefusing to use an unverified revocation list",
+            err
+        ));
+    }
+    let contents = store::read_to_string(&path);
+    if let Err(err) = &contents {
+        return Err(format!("Unable to read {}: {}", path.display(), err));
+    }
+    let document = contents.unwrap().parse::<ImDocument<String>>();
+    if let Err(err) = &document {
+        return Err(format!("{}: {}", path.display(), err.message().trim()));
+    }
+
+    let document = document.unwrap();
+    let list = document.get(COMPROMISED_LIST);
+    if list.is_none() {
+        return Ok(Vec::new());
+    }
+    let fingerprints = list.unwrap().as_array().map(|array| {
+        array
+            .iter()
+            .map(|fpr| fpr.as_str().map(str::to_uppercase))
+            .collect::<Option<Vec<String>>>()
+    });
+    if fingerprints.is_none() || fingerprints.as_ref().unwrap().is_none() {
+        return Err(format!(
+            "{}: {} must be an array of fingerprints",
+            path.display(),
+            COMPROMISED_LIST
+        ));
+    }
+    Ok(fingerprints.unwrap().unwrap())
+}
+
+/// Adds `fingerprint` to the revocation list, keeping whatever else the file
+/// holds, and signs it. Returns false if it was already listed.
+pub fn mark_compromised(fingerprint: &str, config: &Config) -> Result<bool, String> {
+    let path = folder::get_keys_dir().join(COMPROMISED_FILE_NAME);
+    let contents = if store::exists(&path) {
+        store::read_to_string(&path)
+    } else {
+        Ok(format!("{}{} = []\n", COMPROMISED_HEADER, COMPROMISED_LIST))
+    };
+    if let Err(err) = &contents {
+        return Err(format!("Unable to read {}: {}", path.display(), err));
+    }
+    let document = contents.unwrap().parse::<DocumentMut>();
+    if let Err(err) = &document {
+        return Err(format!("{}: {}", path.display(), err.message().trim()));
+    }
+
+    let mut document = document.unwrap();
+    if document.get(COMPROMISED_LIST).is_none() {
+        document[COMPROMISED_LIST] = toml_edit::value(toml_edit::Array::new());
+    }
+    let list = document[COMPROMISED_LIST].as_array_mut();
+    if list.is_none() {
+        return Err(format!(
+            "{}: {} must be an array of fingerprints",
+            path.display(),
+            COMPROMISED_LIST
+        ));
+    }
+    let list = list.unwrap();
+    if list.iter().any(|fpr| {
+        fpr.as_str()
+            .is_some_and(|fpr| fpr.eq_ignore_ascii_case(fingerprint))
+    }) {
+        return Ok(false);
+    }
+    list.push(fingerprint);
+
+    let write_res = store::write(&path, document.to_string());
+    if let Err(err) = &write_res {
+        return Err(format!("Unable to write {}: {}", path.display(), err));
+    }
+    gpg_id::sign_file(&path, config)?;
+    Ok(true)
+}
+
+/// Every password the key could decrypt, going by the effective .gpg-id of
+/// each folder.
+pub fn readable_paths(fingerprint: &str, keys: &[Key]) -> Vec<String> {
+    let resolver = RecipientResolver::new(keys);
+    let mut paths = Vec::new();
+    for folder in folder::get_store_folders() {
+        let resolved = resolver.resolve(Path::new(&folder)).unwrap();
+        let readable = resolved
+            .get_gpg_ids()
+            .iter()
+            .enumerate()
+            .any(|(index, gpg)| {
+                matches_fingerprint(gpg, fingerprint)
+                    || resolved
+                        .get_keys(index)
+                        .iter()
+                        .any(|fpr| fpr == fingerprint)
+            });
+        if readable {
+            paths.extend(folder::get_password_files(&folder));
+        }
+    }
+    paths
+}
+
+/// A .gpg-id rewritten without the compromised key.
+pub struct GpgIdChange {
+    folder: String,
+    gpg_ids: HashSet<String>,
+    groups: gpg_id::Groups,
+}
+
+impl GpgIdChange {
+    pub fn get_folder(&self) -> &str {
+        &self.folder
+    }
+
+    pub fn write(&self, config: &Config) {
+        gpg_id::write_gpg_ids_with_groups(
+            &folder::get_folder_dir(&self.folder),
+            &self.gpg_ids,
+            &self.groups,
+            config,
+        );
+    }
+}
+
+/// The .gpg-id files that name the key, with the key taken out. An entry
+/// that also matches other keys, like an email several devices share, is
+/// replaced by the fingerprints of those other keys so they keep access.
+pub fn remove_from_gpg_ids(fingerprint: &str, keys: &[Key]) -> Result<Vec<GpgIdChange>, String> {
+    let compromised = keys.iter().find(|key| key.get_fingerprint() == fingerprint);
+    let names_key = |gpg: &str| {
+        matches_fingerprint(gpg, fingerprint)
+            || compromised.is_some_and(|key| matches_key(gpg, key))
+    };
+
+    let mut changes = Vec::new();
+    for folder_name in folder::get_store_folders() {
+        let mut change = GpgIdChange {
+            folder: folder_name,
+            gpg_ids: HashSet::new(),
+            groups: gpg_id::Groups::new(),
+        };
+        let dir = folder::get_folder_dir(&change.folder);
+        if !gpg_id::has_gpg_id_file(&dir) {
+            continue;
+        }
+
+        let gpg_ids = gpg_id::get_base_gpgs_for_dir(&dir);
+        if !gpg_ids.iter().any(|gpg| names_key(gpg)) {
+            continue;
+        }
+        for gpg in gpg_ids {
+            if !names_key(&gpg) {
+                change.gpg_ids.insert(gpg);
+                continue;
+            }
+            for key in keys {
+                if key.get_fingerprint() != fingerprint && matches_key(&gpg, key) {
+                    change.gpg_ids.insert(key.get_fingerprint().to_string());
+                }
+            }
+        }
+        change.groups = gpg_id::get_groups_for_dir(&dir);
+        for members in change.groups.values_mut() {
+            members.retain(|member| !matches_fingerprint(member, fingerprint));
+        }
+
+        if change.gpg_ids.is_empty() {
+            return Err(format!(
+                "{} would have no recipients left once {} is removed, add another key to it first",
+                Path::new(&change.folder).join(".gpg-id").display(),
+                fingerprint
+            ));
+        }
+        changes.push(change);
+    }
+    Ok(changes)
+}
+
+/// Checks `data` in a staging keyring holding just the key before it goes
+/// anywhere near the real one: it has to be about `fingerprint` alone and
+/// revoke it, without bringing new signatures, user ids or subkeys.
+pub fn check_revocation(
+    backend: &mut dyn KeyBackend,
+    fingerprint: &str,
+    data: &[u8],
+) -> Result<(), String> {
+    if backend.get_key(fingerprint).is_none() {
+        return Err(format!("{} isn't in the keyring", fingerprint));
+    }
+    let key_data = backend.export(&[fingerprint], ExportFilter::Minimal);
+    if let Err(err) = &key_data {
+        return Err(format!("Unable to export {}: {}", fingerprint, err));
+    }
+    let mut staging = backend.staging()?;
+    staging.import(&key_data.unwrap())?;
+
+    let summary = staging.import(data)?;
+    if summary.secret_considered() > 0 {
+        return Err("Revocation certificate contains secret key material".to_string());
+    }
+    if summary.imports().is_empty() {
+        return Err("Revocation certificate doesn't contain a key".to_string());
+    }
+    for imported in summary.imports() {
+        if imported.get_fingerprint() != fingerprint {
+            return Err(format!(
+                "Revocation certificate is for {}, not {}",
+                imported.get_fingerprint(),
+                fingerprint
+            ));
+        }
+    }
+    if summary.new_signatures() > 0 || summary.new_user_ids() > 0 || summary.new_subkeys() > 0 {
+        return Err("Revocation certificate brings more than a revocation".to_string());
+    }
+    if summary.new_revocations() == 0 {
+        return Err(format!(
+            "Revocation certificate doesn't revoke {}",
+            fingerprint
+        ));
+    }
+    Ok(())
+}
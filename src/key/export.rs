@@ -0,0 +1,219 @@
+use super::backend::ExportFilter;
+use super::backend::ImportSummary;
+use super::backend::KeyBackend;
+use super::folder;
+use crate::store;
+use std::fmt;
+use std::path::Path;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExportMode {
+    Full,
+    Minimal,
+    KnownSigners,
+}
+
+impl ExportMode {
+    pub fn parse(mode: &str) -> Option<ExportMode> {
+        match mode {
+            "full" => Some(ExportMode::Full),
+            "minimal" => Some(ExportMode::Minimal),
+            "known-signers" => Some(ExportMode::KnownSigners),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ExportMode::Full => "full",
+            ExportMode::Minimal => "minimal",
+            ExportMode::KnownSigners => "known-signers",
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ExportChanges {
+    signatures: u32,
+    subkeys: u32,
+    user_ids: u32,
+    revocations: u32,
+}
+
+impl ExportChanges {
+    fn from_import(import_result: &ImportSummary) -> ExportChanges {
+        ExportChanges {
+            signatures: import_result.new_signatures(),
+            subkeys: import_result.new_subkeys(),
+            user_ids: import_result.new_user_ids(),
+            revocations: import_result.new_revocations(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.signatures == 0 && self.subkeys == 0 && self.user_ids == 0 && self.revocations == 0
+    }
+}
+
+impl fmt::Display for ExportChanges {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts = Vec::new();
+        for (count, name) in &[
+            (self.signatures, "signatures"),
+            (self.subkeys, "subkeys"),
+            (self.user_ids, "user ids"),
+            (self.revocations, "revocations"),
+        ] {
+            if *count > 0 {
+                parts.push(format!("{} {}", count, name));
+            }
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+pub enum ExportStatus {
+    Created,
+    Unchanged,
+    Gained(ExportChanges),
+    Pruned(ExportChanges),
+    /// The export has less than the published file, which was left alone.
+    Kept(ExportChanges),
+}
+
+impl fmt::Display for ExportStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExportStatus::Created => write!(f, "created"),
+            ExportStatus::Unchanged => write!(f, "unchanged"),
+            ExportStatus::Gained(changes) => write!(f, "gained {}", changes),
+            ExportStatus::Pruned(changes) => write!(f, "dropped {}", changes),
+            ExportStatus::Kept(changes) => write!(
+                f,
+                "kept, the export would drop {} (run sync --prune to rewrite it)",
+                changes
+            ),
+        }
+    }
+}
+
+/// Writes the key to `path` unless that would drop material the existing file
+/// already published, which only happens when `prune` is set.
+pub fn write_key_file(
+    backend: &mut dyn KeyBackend,
+    fingerprint: &str,
+    mode: ExportMode,
+    path: &Path,
+    prune: bool,
+) -> Result<ExportStatus, String> {
+    let exported_bytes = export_key(backend, fingerprint, mode)?;
+
+    let status = if store::exists(path) {
+        let existing_bytes = store::read(path);
+        if existing_bytes.is_err() {
+            return Err(format!(
+                "Unable to read existing key file for fingerprint: {}",
+                fingerprint
+            ));
+        }
+        let existing_bytes = existing_bytes.unwrap();
+        if existing_bytes == exported_bytes {
+            return Ok(ExportStatus::Unchanged);
+        }
+
+        let gained = merge_changes(backend, &existing_bytes, &exported_bytes)?;
+        if !gained.is_empty() {
+            ExportStatus::Gained(gained)
+        } else {
+            let pruned = merge_changes(backend, &exported_bytes, &existing_bytes)?;
+            if pruned.is_empty() {
+                return Ok(ExportStatus::Unchanged);
+            }
+            if !prune {
+                return Ok(ExportStatus::Kept(pruned));
+            }
+            ExportStatus::Pruned(pruned)
+        }
+    } else {
+        ExportStatus::Created
+    };
+
+    let write_res = store::write(path, exported_bytes);
+    if write_res.is_err() {
+        return Err(format!(
+            "Unable to write exported key for fingerprint: {}",
+            fingerprint
+        ));
+    }
+
+    Ok(status)
+}
+
+fn merge_changes(
+    backend: &dyn KeyBackend,
+    base: &[u8],
+    update: &[u8],
+) -> Result<ExportChanges, String> {
+    let mut staging = backend.staging()?;
+    if staging.import(base).is_err() {
+        return Err("Unable to stage existing key file".to_string());
+    }
+    let import_result = staging.import(update);
+    if import_result.is_err() {
+        return Err("Unable to stage exported key".to_string());
+    }
+
+    Ok(ExportChanges::from_import(&import_result.unwrap()))
+}
+
+pub fn export_key(
+    backend: &mut dyn KeyBackend,
+    fingerprint: &str,
+    mode: ExportMode,
+) -> Result<Vec<u8>, String> {
+    if backend.get_key(fingerprint).is_none() {
+        return Err(format!(
+            "Unable to locate key for exporting for fingerprint: {}",
+            fingerprint
+        ));
+    }
+
+    match mode {
+        ExportMode::Full => backend.export(&[fingerprint], ExportFilter::Full),
+        ExportMode::Minimal => backend.export(&[fingerprint], ExportFilter::Minimal),
+        ExportMode::KnownSigners => export_known_signers(backend, fingerprint),
+    }
+}
+
+fn export_known_signers(
+    backend: &mut dyn KeyBackend,
+    fingerprint: &str,
+) -> Result<Vec<u8>, String> {
+    let mut known_fingerprints = folder::get_key_ids();
+    known_fingerprints.extend(backend.signers());
+    let mut known_keys = Vec::new();
+    for known_fingerprint in known_fingerprints {
+        let known_key = backend.get_key(&known_fingerprint);
+        if let Some(known_key) = known_key {
+            known_keys.push(known_key.get_fingerprint().to_string());
+        }
+    }
+
+    let mut staging = backend.staging()?;
+    if !known_keys.is_empty() {
+        let known_keys: Vec<&str> = known_keys.iter().map(String::as_str).collect();
+        let known_bytes = backend.export(&known_keys, ExportFilter::Minimal);
+        if known_bytes.is_err() || staging.import(&known_bytes.unwrap()).is_err() {
+            return Err("Unable to copy known keys into staging keyring".to_string());
+        }
+    }
+    let full_bytes = backend.export(&[fingerprint], ExportFilter::Full)?;
+    if staging.import(&full_bytes).is_err() {
+        return Err(format!(
+            "Unable to stage key for fingerprint: {}",
+            fingerprint
+        ));
+    }
+
+    staging.export(&[fingerprint], ExportFilter::Clean)
+}
@@ -1,40 +1,106 @@
-use dirs;
+use crate::pass_env;
+use crate::store;
 use std::env;
 use std::fs;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::Path;
 use std::path::PathBuf;
 
-const PASS_DIR_VAR_NAME: &str = "PASSWORD_STORE_DIR";
+pub const PASS_DIR_VAR_NAME: &str = "PASSWORD_STORE_DIR";
+pub const ROOT_FOLDER: &str = ".";
 const KEYS_DIR_NAME: &str = ".keys";
 const PASSWORD_STORE_DEFAULT_NAME: &str = ".password-store";
+const QUARANTINE_DIR_NAME: &str = "quarantine";
+const RETIRED_DIR_NAME: &str = "retired";
+const KEY_FILE_EXTENSION: &str = "asc";
+const PASSWORD_FILE_EXTENSION: &str = "gpg";
+const METADATA_FILE_NAME: &str = "provision.toml";
+const METADATA_FORMAT: u32 = 1;
 
 pub fn get_key_ids() -> Vec<String> {
     let key_dir = get_keys_dir();
     let mut keys = Vec::new();
 
-    let ls_dir_res = fs::read_dir(&key_dir);
+    let ls_dir_res = store::read_dir(&key_dir);
     if ls_dir_res.is_err() {
         return keys;
     }
 
-    for file in ls_dir_res.unwrap() {
-        if file.is_ok() {
-            let fpath = file.unwrap().path();
-            let stem = fpath.file_stem();
-            if stem.is_some() {
-                keys.push(fpath.file_stem().unwrap().to_str().unwrap().to_string());
-            }
+    for fpath in ls_dir_res.unwrap() {
+        if !store::is_file(&fpath) || fpath.extension() != Some(KEY_FILE_EXTENSION.as_ref()) {
+            continue;
+        }
+        let stem = fpath.file_stem();
+        if stem.is_some() {
+            keys.push(fpath.file_stem().unwrap().to_str().unwrap().to_string());
         }
     }
 
     keys
 }
 
+/// The store root as `.` followed by every non-hidden folder below it,
+/// relative to the root.
+pub fn get_store_folders() -> Vec<String> {
+    let mut folders = vec![ROOT_FOLDER.to_string()];
+    collect_folders(&get_pass_dir(), "", &mut folders);
+    folders
+}
+
+/// The directory of a store-relative folder, `.` being the store root.
+pub fn get_folder_dir(folder: &str) -> PathBuf {
+    let pass_dir = get_pass_dir();
+    if folder == ROOT_FOLDER {
+        pass_dir
+    } else {
+        pass_dir.join(folder)
+    }
+}
+
+/// Store-relative paths of the password files directly inside `folder`.
+pub fn get_password_files(folder: &str) -> Vec<String> {
+    let pass_dir = get_pass_dir();
+    let dir = get_folder_dir(folder);
+    let entries = store::read_dir(&dir);
+    if entries.is_err() {
+        return Vec::new();
+    }
+    let mut files: Vec<String> = entries
+        .unwrap()
+        .into_iter()
+        .filter(|path| {
+            store::is_file(path) && path.extension() == Some(PASSWORD_FILE_EXTENSION.as_ref())
+        })
+        .map(|path| path.strip_prefix(&pass_dir).unwrap().display().to_string())
+        .collect();
+    files.sort();
+    files
+}
+
+fn collect_folders(dir: &Path, prefix: &str, folders: &mut Vec<String>) {
+    let entries = store::read_dir(dir);
+    if entries.is_err() {
+        return;
+    }
+    let mut entries = entries.unwrap();
+    entries.sort();
+    for entry in entries {
+        let name = entry.file_name().unwrap().to_string_lossy().to_string();
+        if name.starts_with('.') || !store::is_dir(&entry) {
+            continue;
+        }
+        let folder = format!("{}{}", prefix, name);
+        folders.push(folder.clone());
+        collect_folders(&entry, &format!("{}/", folder), folders);
+    }
+}
+
 pub fn get_keys_dir() -> PathBuf {
     let pass_dir = get_pass_dir();
     let key_dir = pass_dir.join(KEYS_DIR_NAME);
-    if !key_dir.exists() {
+    if !store::exists(&key_dir) {
         println!("creating dir: {}", key_dir.display());
-        let err = fs::create_dir_all(&key_dir);
+        let err = store::create_dir_all(&key_dir);
         if err.is_err() {
             eprintln!("Unable to create key dir");
             std::process::exit(1);
@@ -44,24 +110,118 @@ pub fn get_keys_dir() -> PathBuf {
     key_dir
 }
 
-pub fn get_pass_dir() -> PathBuf {
-    let pass_dir_env_var = env::var(PASS_DIR_VAR_NAME);
+pub fn quarantine_key(fingerprint: &str, reason: &str) -> Result<PathBuf, String> {
+    let quarantine_path = move_key_file(fingerprint, QUARANTINE_DIR_NAME);
+    if let Err(err) = &quarantine_path {
+        return Err(format!("Unable to move key file into quarantine: {}", err));
+    }
 
-    let pass_dir = if pass_dir_env_var.is_ok() {
-        PathBuf::from(pass_dir_env_var.unwrap())
-    } else {
-        let home_dir = dirs::home_dir();
-        if home_dir.is_none() {
-            eprintln!("Cannot find password store directory");
-            std::process::exit(1);
-        }
-        home_dir.unwrap().join(PASSWORD_STORE_DEFAULT_NAME)
-    };
+    let reason_path = get_keys_dir()
+        .join(QUARANTINE_DIR_NAME)
+        .join(format!("{}.reason", fingerprint));
+    let write_res = store::write(&reason_path, format!("{}\n", reason));
+    if let Err(err) = &write_res {
+        return Err(format!("Unable to write quarantine reason: {}", err));
+    }
+
+    quarantine_path
+}
+
+/// Moves a key no `.gpg-id` uses any more to `.keys/retired/`, out of the way
+/// of `sync` but still in the store's history.
+pub fn retire_key(fingerprint: &str) -> Result<PathBuf, String> {
+    let retired_path = move_key_file(fingerprint, RETIRED_DIR_NAME);
+    if let Err(err) = &retired_path {
+        return Err(format!("Unable to retire key file: {}", err));
+    }
+    retired_path
+}
+
+fn move_key_file(fingerprint: &str, dir_name: &str) -> Result<PathBuf, String> {
+    let key_dir = get_keys_dir();
+    let target_dir = key_dir.join(dir_name);
+    let create_res = store::create_dir_all(&target_dir);
+    if let Err(err) = &create_res {
+        return Err(format!(
+            "Unable to create {}: {}",
+            target_dir.display(),
+            err
+        ));
+    }
+
+    let fname = format!("{}.{}", fingerprint, KEY_FILE_EXTENSION);
+    let target_path = target_dir.join(&fname);
+    let rename_res = store::rename(&key_dir.join(&fname), &target_path);
+    if let Err(err) = &rename_res {
+        return Err(err.to_string());
+    }
+    Ok(target_path)
+}
+
+pub fn create_pass_dir(pass_dir: &Path) -> Result<(), String> {
+    let create_res = fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(pass_dir);
+    if let Err(err) = &create_res {
+        return Err(format!(
+            "Unable to create password store at {}: {}",
+            pass_dir.display(),
+            err
+        ));
+    }
+    Ok(())
+}
+
+pub fn write_metadata(gpg_id_format: &str) -> Result<PathBuf, String> {
+    let metadata_path = get_keys_dir().join(METADATA_FILE_NAME);
+    let metadata = format!(
+        "# Written by pass-provision, describes how this store is provisioned\n\
+         format = {}\n\
+         created-by = \"pass-provision {}\"\n\
+         gpg-id = \"{}\"\n",
+        METADATA_FORMAT,
+        env!("CARGO_PKG_VERSION"),
+        gpg_id_format
+    );
+    let write_res = store::write(&metadata_path, metadata);
+    if let Err(err) = &write_res {
+        return Err(format!("Unable to write store metadata: {}", err));
+    }
+    Ok(metadata_path)
+}
 
-    if !pass_dir.exists() {
+pub fn get_pass_dir() -> PathBuf {
+    let pass_dir = resolve_pass_dir();
+    if pass_dir.is_none() {
+        eprintln!("Cannot find password store directory");
+        std::process::exit(1);
+    }
+    let pass_dir = pass_dir.unwrap();
+
+    if !store::exists(&pass_dir) {
         eprintln!("Password store directory doesn't exist!");
         std::process::exit(1);
     }
 
     pass_dir
 }
+
+pub fn resolve_pass_dir() -> Option<PathBuf> {
+    let store_root = store::root();
+    if store_root.is_some() {
+        return store_root;
+    }
+
+    let pass_dir_env_var = env::var(PASS_DIR_VAR_NAME);
+    if let Ok(pass_dir_env_var) = &pass_dir_env_var {
+        return Some(PathBuf::from(pass_dir_env_var));
+    }
+
+    let store_prefix = pass_env::store_prefix();
+    if store_prefix.is_some() {
+        return store_prefix;
+    }
+
+    dirs::home_dir().map(|home_dir| home_dir.join(PASSWORD_STORE_DEFAULT_NAME))
+}
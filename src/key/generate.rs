@@ -0,0 +1,118 @@
+const DEFAULT_ALGO: &str = "ed25519/cv25519";
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+pub struct KeySpec {
+    user_id: String,
+    algo: String,
+    subkey_algo: String,
+    expire: Option<u64>,
+    passphrase: bool,
+}
+
+impl KeySpec {
+    pub fn new(
+        name: Option<&str>,
+        email: &str,
+        algo: Option<&str>,
+        expire: Option<&str>,
+        passphrase: bool,
+    ) -> Result<KeySpec, String> {
+        if email.is_empty() || email.contains(char::is_whitespace) || !email.contains('@') {
+            return Err(format!("Invalid email address: {}", email));
+        }
+        let name = name.map(str::trim).filter(|name| !name.is_empty());
+        let user_id = if let Some(name) = name {
+            format!("{} <{}>", name, email)
+        } else {
+            format!("<{}>", email)
+        };
+        let (algo, subkey_algo) = parse_algo(algo.unwrap_or(DEFAULT_ALGO))?;
+        let expire = parse_expire(expire.unwrap_or("never"))?;
+
+        Ok(KeySpec {
+            user_id,
+            algo,
+            subkey_algo,
+            expire,
+            passphrase,
+        })
+    }
+
+    pub fn get_user_id(&self) -> &str {
+        &self.user_id
+    }
+
+    pub fn get_algo(&self) -> &str {
+        &self.algo
+    }
+
+    pub fn get_subkey_algo(&self) -> &str {
+        &self.subkey_algo
+    }
+
+    pub fn get_expire(&self) -> Option<u64> {
+        self.expire
+    }
+
+    pub fn has_passphrase(&self) -> bool {
+        self.passphrase
+    }
+}
+
+pub fn parse_algo(algo: &str) -> Result<(String, String), String> {
+    let mut parts = algo.split('/');
+    let primary = parts.next().unwrap().trim().to_lowercase();
+    let subkey = parts.next().map(|part| part.trim().to_lowercase());
+    if primary.is_empty() || parts.next().is_some() {
+        return Err(format!(
+            "Invalid algorithm: {}, expected <primary>[/<encryption subkey>]",
+            algo
+        ));
+    }
+    if let Some(subkey) = subkey {
+        if subkey.is_empty() {
+            return Err(format!("Invalid algorithm: {}", algo));
+        }
+        return Ok((primary, subkey));
+    }
+
+    let subkey = match primary.as_str() {
+        "ed25519" => "cv25519".to_string(),
+        "ed448" => "cv448".to_string(),
+        _ => primary.clone(),
+    };
+    Ok((primary, subkey))
+}
+
+pub fn parse_expire(expire: &str) -> Result<Option<u64>, String> {
+    let expire = expire.trim().to_lowercase();
+    if expire == "never" || expire == "0" {
+        return Ok(None);
+    }
+
+    let unit_index = expire.find(|c: char| !c.is_ascii_digit());
+    let (count, unit) = match unit_index {
+        Some(index) => (&expire[..index], &expire[index..]),
+        None => (expire.as_str(), "d"),
+    };
+    let count = count.parse::<u64>();
+    if count.is_err() || *count.as_ref().unwrap() == 0 {
+        return Err(format!(
+            "Invalid expiry: {}, expected never or a period like 30d, 6w, 12m or 2y",
+            expire
+        ));
+    }
+    let days = match unit {
+        "d" => 1,
+        "w" => 7,
+        "m" => 30,
+        "y" => 365,
+        _ => {
+            return Err(format!(
+                "Invalid expiry: {}, expected never or a period like 30d, 6w, 12m or 2y",
+                expire
+            ))
+        }
+    };
+    Ok(Some(count.unwrap() * days * SECONDS_PER_DAY))
+}
@@ -1,11 +1,20 @@
+use super::backend;
+use super::backend::KeyBackend;
 use super::folder;
+use crate::config::Config;
+use crate::store;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::collections::HashSet;
-use std::fs::File;
-use std::io::Read;
-use std::io::Write;
 use std::path::Path;
+use std::path::PathBuf;
 
 const GPG_ID_FILE_NAME: &str = ".gpg-id";
+const GPG_ID_SIG_FILE_NAME: &str = ".gpg-id.sig";
+const SIG_EXTENSION: &str = ".sig";
+const GROUP_MARKER: &str = "#@";
+
+pub type Groups = BTreeMap<String, BTreeSet<String>>;
 
 pub fn get_all_gpgs() -> HashSet<String> {
     let pass_dir = folder::get_pass_dir();
@@ -13,12 +22,63 @@ pub fn get_all_gpgs() -> HashSet<String> {
     get_all_gpgs_for_dir(&pass_dir)
 }
 
+pub fn has_gpg_id_file(path: &Path) -> bool {
+    store::is_file(&path.join(GPG_ID_FILE_NAME))
+}
+
 pub fn get_base_gpgs_for_dir(path: &Path) -> HashSet<String> {
     parse_gpg_id_file(&path.join(GPG_ID_FILE_NAME))
 }
 
-pub fn write_gpg_ids(path: &Path, gpg_ids: &HashSet<String>) {
-    if !path.exists() {
+/// The raw lines of a folder's own .gpg-id, comments and whitespace included.
+pub fn get_gpg_id_lines(path: &Path) -> Vec<String> {
+    let contents = store::read_to_string(&path.join(GPG_ID_FILE_NAME));
+    if contents.is_err() {
+        return Vec::new();
+    }
+    contents.unwrap().lines().map(str::to_string).collect()
+}
+
+/// Groups recorded in a folder's own .gpg-id, keyed by `@name`, with the
+/// fingerprints each one expanded to when it was written.
+pub fn get_groups_for_dir(path: &Path) -> Groups {
+    let mut groups = Groups::new();
+    let contents = store::read_to_string(&path.join(GPG_ID_FILE_NAME));
+    if contents.is_err() {
+        return groups;
+    }
+
+    for line in contents.unwrap().lines() {
+        let marker = line.strip_prefix(GROUP_MARKER);
+        if marker.is_none() {
+            continue;
+        }
+        let (name, members) = marker
+            .unwrap()
+            .split_once(':')
+            .unwrap_or((marker.unwrap(), ""));
+        groups.insert(
+            format!("@{}", name.trim()),
+            members.split_whitespace().map(str::to_string).collect(),
+        );
+    }
+
+    groups
+}
+
+pub fn write_gpg_ids(path: &Path, gpg_ids: &HashSet<String>, config: &Config) {
+    write_gpg_ids_with_groups(path, gpg_ids, &get_groups_for_dir(path), config);
+}
+
+/// Writes the recipients along with a `#@name: FPR...` comment per group,
+/// which pass ignores but lets the group be expanded again later.
+pub fn write_gpg_ids_with_groups(
+    path: &Path,
+    gpg_ids: &HashSet<String>,
+    groups: &Groups,
+    config: &Config,
+) {
+    if !store::exists(path) {
         eprintln!(
             "Unable to write gpg_id file! path: {} doesn't exist",
             path.display()
@@ -26,55 +86,180 @@ pub fn write_gpg_ids(path: &Path, gpg_ids: &HashSet<String>) {
         std::process::exit(1);
     }
     let gpg_id_path = path.join(GPG_ID_FILE_NAME);
-    let gpg_file_res = File::create(gpg_id_path);
-    if gpg_file_res.is_err() {
-        eprintln!("Unable to create gpg_id file!");
+    let mut gpg_ids: BTreeSet<&String> = gpg_ids.iter().collect();
+    let mut contents = String::new();
+    for (name, members) in groups {
+        contents += &format!("#{}:", name);
+        for member in members {
+            contents += &format!(" {}", member);
+            gpg_ids.insert(member);
+        }
+        contents += "\n";
+    }
+    for gpg_id in gpg_ids {
+        contents += gpg_id;
+        contents += "\n";
+    }
+    let write_res = store::write(&gpg_id_path, contents);
+    if write_res.is_err() {
+        eprintln!("Unable to write to gpg_id file");
         std::process::exit(1);
     }
-    let mut gpg_file = gpg_file_res.unwrap();
 
-    for gpg_id in gpg_ids {
-        let write_res = writeln!(&mut gpg_file, "{}", gpg_id);
-        if write_res.is_err() {
-            eprintln!("Unable to write to gpg_id file");
-            std::process::exit(1);
+    let sign_res = sign_gpg_id_file(path, config);
+    if let Err(err) = &sign_res {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+pub fn remove_gpg_id_file(path: &Path) -> Result<(), String> {
+    for name in [GPG_ID_FILE_NAME, GPG_ID_SIG_FILE_NAME] {
+        let file_path = path.join(name);
+        if !store::exists(&file_path) {
+            continue;
+        }
+        let remove_res = store::remove_file(&file_path);
+        if let Err(err) = &remove_res {
+            return Err(format!("Unable to remove {}: {}", file_path.display(), err));
+        }
+    }
+    Ok(())
+}
+
+pub fn sign_gpg_id_file(path: &Path, config: &Config) -> Result<(), String> {
+    sign_file(&path.join(GPG_ID_FILE_NAME), config)
+}
+
+pub fn verify_gpg_id_file(path: &Path, config: &Config) -> Result<(), String> {
+    verify_file(&path.join(GPG_ID_FILE_NAME), config)
+}
+
+/// Signs `file_path` with the first usable PASSWORD_STORE_SIGNING_KEY into a
+/// detached signature next to it, like pass does for .gpg-id.
+pub fn sign_file(file_path: &Path, config: &Config) -> Result<(), String> {
+    let signing_keys = config.get_signing_keys();
+    if signing_keys.is_empty() {
+        return Ok(());
+    }
+    let mut backend = signing_backend()?;
+
+    let mut signer_found = false;
+    for signing_key in signing_keys.iter().rev() {
+        if backend.get_secret_key(signing_key).is_some() && backend.add_signer(signing_key).is_ok()
+        {
+            signer_found = true;
+            break;
         }
     }
+    if !signer_found {
+        return Err(format!(
+            "No secret key available for PASSWORD_STORE_SIGNING_KEY: {}",
+            signing_keys.join(" ")
+        ));
+    }
+
+    let contents = store::read(file_path);
+    if contents.is_err() {
+        return Err(format!("Unable to read {}", file_path.display()));
+    }
+    let sign_res = backend.sign_detached(&contents.unwrap());
+    if let Err(err) = &sign_res {
+        return Err(format!("Unable to sign {}: {}", file_path.display(), err));
+    }
+
+    let sig_path = signature_path(file_path);
+    let write_res = store::write(&sig_path, sign_res.unwrap());
+    if write_res.is_err() {
+        return Err(format!("Unable to write {}", sig_path.display()));
+    }
+
+    Ok(())
 }
 
-pub fn get_gpgs_for_dir(path: &Path) -> HashSet<String> {
-    let pass_path = folder::get_pass_dir();
-    for parent_path in path.ancestors() {
-        let gpg_path = pass_path.join(parent_path).join(GPG_ID_FILE_NAME);
-        let gpgs = parse_gpg_id_file(&gpg_path);
-        if !gpgs.is_empty() {
-            return gpgs;
+/// Checks that `file_path` carries a valid signature by one of the
+/// PASSWORD_STORE_SIGNING_KEY keys. Without signing keys, or without the
+/// file, there's nothing to check.
+pub fn verify_file(file_path: &Path, config: &Config) -> Result<(), String> {
+    let signing_keys = config.get_signing_keys();
+    if signing_keys.is_empty() || !store::exists(file_path) {
+        return Ok(());
+    }
+
+    let sig_path = signature_path(file_path);
+    let signature = store::read(&sig_path);
+    if signature.is_err() {
+        return Err(format!(
+            "Signature for {} does not exist",
+            file_path.display()
+        ));
+    }
+    let contents = store::read(file_path);
+    if contents.is_err() {
+        return Err(format!("Unable to read {}", file_path.display()));
+    }
+
+    let mut backend = signing_backend()?;
+    let verify_res = backend.verify_detached(&signature.unwrap(), &contents.unwrap());
+    if let Err(err) = &verify_res {
+        return Err(format!(
+            "Unable to verify signature for {}: {}",
+            file_path.display(),
+            err
+        ));
+    }
+
+    for signer in verify_res.unwrap() {
+        if signing_keys
+            .iter()
+            .any(|signing_key| signing_key.eq_ignore_ascii_case(&signer))
+        {
+            return Ok(());
         }
     }
-    HashSet::new()
+
+    Err(format!(
+        "Signature for {} is invalid or not made by PASSWORD_STORE_SIGNING_KEY",
+        file_path.display()
+    ))
+}
+
+/// Where the detached signature of `file_path` lives: the same name with
+/// `.sig` appended.
+pub fn signature_path(file_path: &Path) -> PathBuf {
+    let mut name = file_path.file_name().unwrap_or_default().to_os_string();
+    name.push(SIG_EXTENSION);
+    file_path.with_file_name(name)
+}
+
+fn signing_backend() -> Result<Box<dyn KeyBackend>, String> {
+    let backend = backend::open_default();
+    if backend.is_err() {
+        return Err(format!(
+            "Unable to open key backend for .gpg-id signatures: {}",
+            backend.err().unwrap()
+        ));
+    }
+    Ok(backend.unwrap())
 }
 
 fn get_all_gpgs_for_dir(path: &Path) -> HashSet<String> {
     let mut gpgs = HashSet::new();
     let gpg_file = path.join(GPG_ID_FILE_NAME);
-    if gpg_file.exists() {
+    if store::exists(&gpg_file) {
         let dir_gpgs = parse_gpg_id_file(&gpg_file);
         union_hash_set(&mut gpgs, dir_gpgs);
     }
 
-    let read_dir_res = path.read_dir();
+    let read_dir_res = store::read_dir(path);
     if read_dir_res.is_err() {
         return gpgs;
     }
 
-    for file in read_dir_res.unwrap() {
-        if file.is_ok() {
-            let entry = file.unwrap();
-            let entry_path = entry.path();
-            if entry_path.is_dir() {
-                let dir_gpgs = get_all_gpgs_for_dir(&entry_path);
-                union_hash_set(&mut gpgs, dir_gpgs);
-            }
+    for entry_path in read_dir_res.unwrap() {
+        if store::is_dir(&entry_path) {
+            let dir_gpgs = get_all_gpgs_for_dir(&entry_path);
+            union_hash_set(&mut gpgs, dir_gpgs);
         }
     }
 
@@ -83,18 +268,16 @@ fn get_all_gpgs_for_dir(path: &Path) -> HashSet<String> {
 
 fn parse_gpg_id_file(path: &Path) -> HashSet<String> {
     let mut gpgs = HashSet::new();
-    let gpg_file = File::open(path);
-    if gpg_file.is_err() {
+    let contents = store::read_to_string(path);
+    if contents.is_err() {
         return gpgs;
     }
 
-    let mut contents = String::new();
-    let read_res = gpg_file.unwrap().read_to_string(&mut contents);
-    if read_res.is_err() {
-        return gpgs;
-    }
-
-    for line in contents.lines() {
+    for line in contents.unwrap().lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
         gpgs.insert(line.to_string());
     }
 
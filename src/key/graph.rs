@@ -0,0 +1,243 @@
+use super::backend::KeyBackend;
+use super::backend::KeyRecord;
+use super::folder;
+use super::Key;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::VecDeque;
+
+#[derive(Default)]
+pub struct TrustGraph {
+    identities: BTreeMap<String, String>,
+    edges: BTreeSet<(String, String)>,
+}
+
+impl TrustGraph {
+    pub fn new() -> TrustGraph {
+        TrustGraph::default()
+    }
+
+    pub fn load(backend: &mut dyn KeyBackend, default_key: &str) -> TrustGraph {
+        let mut records = Vec::new();
+        let mut fingerprints = folder::get_key_ids();
+        fingerprints.push(default_key.to_string());
+        for fpr in fingerprints {
+            let record = backend.get_key(&fpr);
+            if record.is_none() {
+                eprintln!("Unable to find key: {} in keyring", fpr);
+                continue;
+            }
+            records.push(record.unwrap());
+        }
+
+        TrustGraph::from_records(records)
+    }
+
+    pub fn load_keyring(backend: &mut dyn KeyBackend) -> Result<TrustGraph, String> {
+        Ok(TrustGraph::from_records(backend.list_keys()?))
+    }
+
+    fn from_records(records: Vec<KeyRecord>) -> TrustGraph {
+        let mut graph = TrustGraph::new();
+        for record in &records {
+            let key = Key::parse_key(record);
+            if key.is_err() {
+                continue;
+            }
+            let key = key.unwrap();
+            graph.add_node(key.get_fingerprint(), key.get_identity());
+        }
+
+        for record in &records {
+            let signee = record.get_fingerprint();
+            if !graph.contains(signee) {
+                continue;
+            }
+            for user_id in record.user_ids() {
+                for certification in user_id.certifications() {
+                    if !certification.is_valid() || certification.get_signer().is_none() {
+                        continue;
+                    }
+                    let signer = graph.find_fingerprint(certification.get_signer().unwrap());
+                    if let Some(signer) = signer {
+                        graph.add_edge(&signer, signee);
+                    }
+                }
+            }
+        }
+
+        graph
+    }
+
+    pub fn add_node(&mut self, fingerprint: &str, identity: &str) {
+        self.identities
+            .insert(fingerprint.to_string(), identity.to_string());
+    }
+
+    pub fn add_edge(&mut self, signer: &str, signee: &str) {
+        if signer != signee {
+            self.edges.insert((signer.to_string(), signee.to_string()));
+        }
+    }
+
+    pub fn contains(&self, fingerprint: &str) -> bool {
+        self.identities.contains_key(fingerprint)
+    }
+
+    pub fn find_fingerprint(&self, key_id: &str) -> Option<String> {
+        for fingerprint in self.identities.keys() {
+            if fingerprint.ends_with(key_id) {
+                return Some(fingerprint.clone());
+            }
+        }
+        None
+    }
+
+    pub fn signers_of(&self, fingerprint: &str) -> Vec<&str> {
+        let mut signers = Vec::new();
+        for (signer, signee) in &self.edges {
+            if signee == fingerprint {
+                signers.push(signer.as_str());
+            }
+        }
+        signers
+    }
+
+    /// `signer` and the keys it has certified itself.
+    pub fn certified_by(&self, signer: &str) -> BTreeSet<String> {
+        let mut certified: BTreeSet<String> = self
+            .edges
+            .iter()
+            .filter(|(from, _)| from == signer)
+            .map(|(_, signee)| signee.clone())
+            .collect();
+        if self.contains(signer) {
+            certified.insert(signer.to_string());
+        }
+        certified
+    }
+
+    pub fn reachable_from(&self, root: &str) -> BTreeSet<String> {
+        self.shortest_paths(root, "").into_keys().collect()
+    }
+
+    pub fn trusted_signers(
+        &self,
+        root: &str,
+        target: &str,
+        max_depth: Option<usize>,
+    ) -> Vec<Vec<String>> {
+        let paths = self.shortest_paths(root, target);
+        let mut trusted = Vec::new();
+        for signer in self.signers_of(target) {
+            let path = paths.get(signer);
+            if path.is_none() {
+                continue;
+            }
+            let mut path = path.unwrap().clone();
+            path.push(target.to_string());
+            if max_depth.is_none() || path.len() - 1 <= max_depth.unwrap() {
+                trusted.push(path);
+            }
+        }
+        trusted.sort_by_key(Vec::len);
+        trusted
+    }
+
+    fn shortest_paths(&self, root: &str, excluded: &str) -> BTreeMap<String, Vec<String>> {
+        let mut paths = BTreeMap::new();
+        if !self.contains(root) || root == excluded {
+            return paths;
+        }
+        let mut queue = VecDeque::new();
+        paths.insert(root.to_string(), vec![root.to_string()]);
+        queue.push_back(root.to_string());
+
+        while let Some(current) = queue.pop_front() {
+            let current_path = paths[&current].clone();
+            for (signer, signee) in &self.edges {
+                if signer == &current && signee != excluded && !paths.contains_key(signee) {
+                    let mut path = current_path.clone();
+                    path.push(signee.clone());
+                    paths.insert(signee.clone(), path);
+                    queue.push_back(signee.clone());
+                }
+            }
+        }
+
+        paths
+    }
+
+    pub fn identity_of(&self, fingerprint: &str) -> Option<&str> {
+        self.identities.get(fingerprint).map(String::as_str)
+    }
+
+    pub fn to_dot(&self, root: &str) -> String {
+        let reachable = self.reachable_from(root);
+        let mut dot = String::from("digraph trust {\n");
+        for (fingerprint, identity) in &self.identities {
+            let mut attrs = vec![format!(
+                "label=\"{}\\n{}\"",
+                escape_dot(identity),
+                short_fingerprint(fingerprint)
+            )];
+            if fingerprint == root {
+                attrs.push("shape=doublecircle".to_string());
+            } else if !reachable.contains(fingerprint) {
+                attrs.push("color=red".to_string());
+                attrs.push("style=bold".to_string());
+            } else if self.signers_of(fingerprint).len() == 1 {
+                attrs.push("color=orange".to_string());
+                attrs.push("style=bold".to_string());
+            }
+            dot += &format!("    \"{}\" [{}];\n", fingerprint, attrs.join(", "));
+        }
+        for (signer, signee) in &self.edges {
+            dot += &format!("    \"{}\" -> \"{}\";\n", signer, signee);
+        }
+        dot += "}\n";
+        dot
+    }
+
+    pub fn to_json(&self, root: &str) -> String {
+        let reachable = self.reachable_from(root);
+        let mut nodes = Vec::new();
+        for (fingerprint, identity) in &self.identities {
+            let signers = self.signers_of(fingerprint);
+            nodes.push(json!({
+                "fingerprint": fingerprint,
+                "identity": identity,
+                "default": fingerprint == root,
+                "reachable": reachable.contains(fingerprint),
+                "single_signer": signers.len() == 1,
+                "signed_by": signers,
+            }));
+        }
+        let mut edges = Vec::new();
+        for (signer, signee) in &self.edges {
+            edges.push(json!({
+                "signer": signer,
+                "signee": signee,
+            }));
+        }
+        let graph = json!({
+            "default_key": root,
+            "nodes": nodes,
+            "edges": edges,
+        });
+        serde_json::to_string_pretty(&graph).unwrap()
+    }
+}
+
+fn short_fingerprint(fingerprint: &str) -> &str {
+    if fingerprint.len() > 16 {
+        &fingerprint[fingerprint.len() - 16..]
+    } else {
+        fingerprint
+    }
+}
+
+fn escape_dot(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,185 @@
+use super::folder;
+use super::gpg_id;
+use super::recipients::is_fingerprint;
+use super::Key;
+use crate::config::Config;
+use crate::store;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashSet;
+use std::path::Path;
+use std::path::PathBuf;
+use toml_edit::ImDocument;
+
+pub const PEOPLE_FILE_PATH: &str = ".keys/people.toml";
+const GROUP_PREFIX: char = '@';
+
+pub fn is_group(gpg: &str) -> bool {
+    gpg.starts_with(GROUP_PREFIX)
+}
+
+/// The device registry: each person maps to the identities and fingerprints
+/// of their device keys.
+#[derive(Default)]
+pub struct People {
+    people: BTreeMap<String, Vec<String>>,
+    trusted: BTreeSet<String>,
+}
+
+impl People {
+    pub fn parse(contents: &str) -> Result<People, String> {
+        let document = contents.parse::<ImDocument<String>>();
+        if let Err(err) = &document {
+            return Err(err.message().trim().to_string());
+        }
+
+        let mut people = BTreeMap::new();
+        for (name, value) in document.unwrap().as_table().iter() {
+            let devices = value.as_array().map(|array| {
+                array
+                    .iter()
+                    .map(|device| device.as_str().map(str::to_string))
+                    .collect::<Option<Vec<String>>>()
+            });
+            if devices.is_none() || devices.as_ref().unwrap().is_none() {
+                return Err(format!("{}: must be an array of strings", name));
+            }
+            people.insert(name.to_string(), devices.unwrap().unwrap());
+        }
+        Ok(People {
+            people,
+            trusted: BTreeSet::new(),
+        })
+    }
+
+    /// The keys an identity may expand to. Fingerprints listed in the
+    /// registry are taken as they are.
+    pub fn with_trusted(mut self, trusted: BTreeSet<String>) -> People {
+        self.trusted = trusted;
+        self
+    }
+
+    pub fn load() -> Result<People, String> {
+        let path = folder::get_pass_dir().join(PEOPLE_FILE_PATH);
+        if !store::exists(&path) {
+            return Ok(People::default());
+        }
+        let contents = store::read_to_string(&path);
+        if let Err(err) = &contents {
+            return Err(format!("Unable to read {}: {}", path.display(), err));
+        }
+        let people = People::parse(&contents.unwrap());
+        if people.is_err() {
+            return Err(format!("{}: {}", path.display(), people.err().unwrap()));
+        }
+        Ok(people.unwrap())
+    }
+
+    /// Fingerprints of every trusted key belonging to `@name`. A name that
+    /// isn't in the registry is taken as an identity, so `@alice@example.com`
+    /// picks up every trusted device key carrying that email.
+    pub fn expand(&self, group: &str, keys: &[Key]) -> Result<BTreeSet<String>, String> {
+        let name = group.trim_start_matches(GROUP_PREFIX);
+        let unregistered = vec![name.to_string()];
+        let devices = self.people.get(name).unwrap_or(&unregistered);
+
+        let mut fingerprints = BTreeSet::new();
+        for device in devices {
+            for key in keys {
+                let matches = if is_fingerprint(device) {
+                    key.get_fingerprint().eq_ignore_ascii_case(device)
+                } else {
+                    key.get_emails().iter().any(|email| email == device)
+                        && self.trusted.contains(key.get_fingerprint())
+                };
+                if matches {
+                    fingerprints.insert(key.get_fingerprint().to_string());
+                }
+            }
+        }
+        if fingerprints.is_empty() {
+            return Err(format!("{} doesn't match any device key", group));
+        }
+        Ok(fingerprints)
+    }
+
+    /// Expands every group in a folder's .gpg-id against the current registry
+    /// and keyring, and returns the rewrite if a group gained or lost devices.
+    pub fn refresh(&self, dir: &Path, keys: &[Key]) -> Result<Option<GroupRefresh>, String> {
+        let groups = gpg_id::get_groups_for_dir(dir);
+        if groups.is_empty() {
+            return Ok(None);
+        }
+
+        let old_recipients = gpg_id::get_base_gpgs_for_dir(dir);
+        let mut recipients = old_recipients.clone();
+        let mut refreshed = gpg_id::Groups::new();
+        for (name, members) in &groups {
+            for member in members {
+                recipients.remove(member);
+            }
+            refreshed.insert(name.clone(), self.expand(name, keys)?);
+        }
+        if refreshed == groups {
+            return Ok(None);
+        }
+
+        for members in refreshed.values() {
+            recipients.extend(members.iter().cloned());
+        }
+        let mut added: Vec<String> = recipients.difference(&old_recipients).cloned().collect();
+        added.sort();
+        let mut removed: Vec<String> = old_recipients.difference(&recipients).cloned().collect();
+        removed.sort();
+        Ok(Some(GroupRefresh {
+            dir: dir.to_path_buf(),
+            recipients,
+            groups: refreshed,
+            added,
+            removed,
+        }))
+    }
+}
+
+/// A folder's .gpg-id with its groups expanded again.
+pub struct GroupRefresh {
+    dir: PathBuf,
+    recipients: HashSet<String>,
+    groups: gpg_id::Groups,
+    added: Vec<String>,
+    removed: Vec<String>,
+}
+
+impl GroupRefresh {
+    pub fn added(&self) -> &[String] {
+        &self.added
+    }
+
+    pub fn removed(&self) -> &[String] {
+        &self.removed
+    }
+
+    pub fn write(&self, config: &Config) {
+        gpg_id::write_gpg_ids_with_groups(&self.dir, &self.recipients, &self.groups, config);
+    }
+}
+
+/// Splits recipients into plain ones and expanded `@name` groups.
+pub fn expand_all(
+    gpgs: &HashSet<String>,
+    people: &People,
+    keys: &[Key],
+) -> Result<(HashSet<String>, gpg_id::Groups), String> {
+    let mut recipients = HashSet::new();
+    let mut groups = gpg_id::Groups::new();
+    for gpg in gpgs {
+        if is_group(gpg) {
+            let members = people.expand(gpg, keys)?;
+            recipients.extend(members.iter().cloned());
+            groups.insert(gpg.clone(), members);
+        } else {
+            recipients.insert(gpg.clone());
+        }
+    }
+    Ok((recipients, groups))
+}
@@ -0,0 +1,202 @@
+use super::folder;
+use super::folder::ROOT_FOLDER;
+use super::gpg_id;
+use super::recipients::matches_key;
+use super::Key;
+use serde_json::json;
+use serde_json::Value;
+use std::collections::HashMap;
+
+pub enum Problem {
+    Unknown,
+    Ambiguous(Vec<String>),
+    Revoked(String),
+    Expired(String),
+    CannotEncrypt(String),
+    NotExported(String),
+    Duplicate(usize),
+    Whitespace,
+}
+
+impl Problem {
+    fn kind(&self) -> &'static str {
+        match self {
+            Problem::Unknown => "unknown",
+            Problem::Ambiguous(_) => "ambiguous",
+            Problem::Revoked(_) => "revoked",
+            Problem::Expired(_) => "expired",
+            Problem::CannotEncrypt(_) => "cannot-encrypt",
+            Problem::NotExported(_) => "not-exported",
+            Problem::Duplicate(_) => "duplicate",
+            Problem::Whitespace => "whitespace",
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Problem::Unknown => "matches no key in the keyring".to_string(),
+            Problem::Ambiguous(fingerprints) => format!(
+                "matches {} keys: {}",
+                fingerprints.len(),
+                fingerprints.join(", ")
+            ),
+            Problem::Revoked(fpr) => format!("key {} is revoked", fpr),
+            Problem::Expired(fpr) => format!("key {} has expired", fpr),
+            Problem::CannotEncrypt(fpr) => format!("key {} can't encrypt", fpr),
+            Problem::NotExported(fpr) => format!("key {} has no .keys/{}.asc", fpr, fpr),
+            Problem::Duplicate(first) => format!("duplicate of line {}", first),
+            Problem::Whitespace => "stray whitespace".to_string(),
+        }
+    }
+}
+
+/// A problem with one line of a .gpg-id file.
+pub struct Finding {
+    folder: String,
+    line: usize,
+    entry: String,
+    problem: Problem,
+    fixed: bool,
+}
+
+impl Finding {
+    pub fn get_folder(&self) -> &str {
+        &self.folder
+    }
+
+    pub fn get_problem(&self) -> &Problem {
+        &self.problem
+    }
+
+    pub fn is_fixed(&self) -> bool {
+        self.fixed
+    }
+
+    pub fn mark_fixed(&mut self) {
+        self.fixed = true;
+    }
+
+    fn file(&self) -> String {
+        if self.folder == ROOT_FOLDER {
+            ".gpg-id".to_string()
+        } else {
+            format!("{}/.gpg-id", self.folder)
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let mut description = format!(
+            "{}:{}: {:?}: {}",
+            self.file(),
+            self.line,
+            self.entry,
+            self.problem.describe()
+        );
+        if self.fixed {
+            description += " (fixed)";
+        }
+        description
+    }
+}
+
+/// Checks every .gpg-id in the store against `keys` and the keys exported
+/// to `.keys/`.
+pub fn lint_store(keys: &[Key], exported: &[String]) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for folder in folder::get_store_folders() {
+        let dir = folder::get_folder_dir(&folder);
+        if !gpg_id::has_gpg_id_file(&dir) {
+            continue;
+        }
+        let lines = gpg_id::get_gpg_id_lines(&dir);
+        lint_lines(&folder, &lines, keys, exported, &mut findings);
+    }
+    findings
+}
+
+fn lint_lines(
+    folder: &str,
+    lines: &[String],
+    keys: &[Key],
+    exported: &[String],
+    findings: &mut Vec<Finding>,
+) {
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    for (index, line) in lines.iter().enumerate() {
+        let entry = line.trim();
+        let mut report = |problem: Problem| {
+            findings.push(Finding {
+                folder: folder.to_string(),
+                line: index + 1,
+                entry: entry.to_string(),
+                problem,
+                fixed: false,
+            })
+        };
+        if entry.starts_with('#') {
+            continue;
+        }
+        if entry != line {
+            report(Problem::Whitespace);
+        }
+        if entry.is_empty() {
+            continue;
+        }
+        if seen.contains_key(entry) {
+            report(Problem::Duplicate(seen[entry]));
+            continue;
+        }
+        seen.insert(entry, index + 1);
+
+        let matched: Vec<&Key> = keys.iter().filter(|key| matches_key(entry, key)).collect();
+        if matched.is_empty() {
+            report(Problem::Unknown);
+            continue;
+        }
+        if matched.len() > 1 {
+            report(Problem::Ambiguous(
+                matched
+                    .iter()
+                    .map(|key| key.get_fingerprint().to_string())
+                    .collect(),
+            ));
+        }
+        for key in matched {
+            let fpr = key.get_fingerprint().to_string();
+            if key.is_revoked() {
+                report(Problem::Revoked(fpr.clone()));
+            } else if key.is_expired() {
+                report(Problem::Expired(fpr.clone()));
+            } else if !key.can_encrypt() {
+                report(Problem::CannotEncrypt(fpr.clone()));
+            }
+            if !exported.contains(&fpr) {
+                report(Problem::NotExported(fpr));
+            }
+        }
+    }
+}
+
+pub fn describe_findings(findings: &[Finding]) -> String {
+    findings
+        .iter()
+        .map(|finding| finding.describe() + "\n")
+        .collect()
+}
+
+pub fn to_json(findings: &[Finding]) -> String {
+    let findings: Vec<Value> = findings
+        .iter()
+        .map(|finding| {
+            json!({
+                "file": finding.file(),
+                "line": finding.line,
+                "entry": finding.entry,
+                "problem": finding.problem.kind(),
+                "message": finding.problem.describe(),
+                "fixed": finding.fixed,
+            })
+        })
+        .collect();
+    serde_json::to_string_pretty(&findings).unwrap()
+}
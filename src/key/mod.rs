@@ -1,60 +1,60 @@
+pub mod access;
+pub mod backend;
+pub mod compromise;
+pub mod export;
 mod folder;
+pub mod generate;
 pub mod gpg_id;
-use super::prompt;
+pub mod graph;
+pub mod group;
+mod import;
+pub mod lint;
+pub mod policy;
+pub mod recipients;
+pub mod reconcile;
+pub mod reencrypt;
+use backend::KeyBackend;
+use backend::KeyRecord;
+pub use folder::create_pass_dir;
+pub use folder::get_folder_dir;
 pub use folder::get_key_ids;
+pub use folder::get_keys_dir;
 pub use folder::get_pass_dir;
-use gpgme::Context;
-use std::fs;
-
-pub fn get_keys(context: &mut Context) -> Result<Vec<Key>, String> {
+pub use folder::get_password_files;
+pub use folder::get_store_folders;
+pub use folder::quarantine_key;
+pub use folder::resolve_pass_dir;
+pub use folder::retire_key;
+pub use folder::write_metadata;
+pub use folder::PASS_DIR_VAR_NAME;
+pub use folder::ROOT_FOLDER;
+pub use import::import_key;
+pub use import::ImportError;
+
+pub fn get_keys(backend: &mut dyn KeyBackend) -> Result<Vec<Key>, String> {
     let mut pub_keys = Vec::new();
-    let key_iterator = context.keys();
-    if key_iterator.is_err() {
-        return Err(key_iterator.err().unwrap().description().to_string());
-    }
-
-    for key in key_iterator.unwrap() {
-        if key.is_err() {
-            eprintln!("Unable to read key");
-            continue;
-        }
-        let key = key.unwrap();
-
-        let parsed_key = Key::parse_key(&key)?;
+    for record in backend.list_keys()? {
+        let parsed_key = Key::parse_key(&record)?;
         pub_keys.push(parsed_key);
     }
 
     Ok(pub_keys)
 }
 
-pub fn get_secret_keys(context: &mut Context) -> Result<Vec<Key>, String> {
+pub fn get_secret_keys(backend: &mut dyn KeyBackend) -> Result<Vec<Key>, String> {
     let mut priv_keys = Vec::new();
-    let key_iterator = context.secret_keys();
-    if key_iterator.is_err() {
-        return Err(key_iterator.err().unwrap().description().to_string());
-    }
-
-    for key in key_iterator.unwrap() {
-        if key.is_err() {
-            eprintln!("Unable to read secret key");
-            continue;
-        }
-        let key = key.unwrap();
-
-        let parsed_key = Key::parse_key(&key)?;
+    for record in backend.list_secret_keys()? {
+        let parsed_key = Key::parse_key(&record)?;
         priv_keys.push(parsed_key);
     }
 
     Ok(priv_keys)
 }
 
-pub fn get_key(context: &mut Context, fingerprint: &str) -> Option<Key> {
-    let gpg_key = context.get_key(fingerprint);
-    if gpg_key.is_err() {
-        return None;
-    }
+pub fn get_key(backend: &mut dyn KeyBackend, fingerprint: &str) -> Option<Key> {
+    let record = backend.get_key(fingerprint)?;
 
-    let key = Key::parse_key(&gpg_key.unwrap());
+    let key = Key::parse_key(&record);
     if key.is_err() {
         return None;
     }
@@ -62,172 +62,47 @@ pub fn get_key(context: &mut Context, fingerprint: &str) -> Option<Key> {
     Some(key.unwrap())
 }
 
-pub fn import_key(context: &mut Context, fingerprint: String) -> Option<Key> {
-    let keys_dir = folder::get_keys_dir();
-    let key_path = keys_dir.join(fingerprint.clone() + ".asc");
-    if !key_path.exists() {
-        eprintln!("Unable to find pubkey file for key: {}", fingerprint);
-        return None;
-    }
-    let key_contents = fs::read_to_string(&key_path);
-    if key_contents.is_err() {
-        eprintln!("Unable to read key: {}", fingerprint);
-        return None;
-    }
-
-    let import_result = context.import(key_contents.unwrap());
-    if import_result.is_err() {
-        eprintln!("Unable to import key for fingerprint: {}", fingerprint);
-        return None;
-    }
-    let import_result = import_result.unwrap();
-
-    let imported_gpg_key = context.get_key(&fingerprint);
-
-    if imported_gpg_key.is_err() {
-        eprintln!(
-            "FRAUD DETECTED ON IMPORT, PLEASE DOUBLE CHECK KEY AT: {}",
-            fingerprint
-        );
-        return None;
-    }
-    let imported_gpg_key = imported_gpg_key.unwrap();
-    let imported_key = Key::parse_key(&imported_gpg_key);
-    if imported_key.is_err() {
-        return None;
-    }
-    let imported_key = imported_key.unwrap();
-
-    let key_imports = import_result.imports();
-    let mut should_check_sigs = false;
-    for key_import in key_imports {
-        if key_import.fingerprint().unwrap() != fingerprint {
-            println!("FRAUD DETECTED ON IMPORT OF: {}", fingerprint);
-        }
-        if key_import.status() == gpgme::ImportFlags::NEW {
-            should_check_sigs = true;
-        }
-    }
-    if !should_check_sigs {
-        return Some(imported_key);
-    }
-
-    let mut good_signatures = Vec::new();
-
-    for user_id in imported_gpg_key.user_ids() {
-        for sig in user_id.signatures() {
-            if sig.is_invalid() || sig.is_expired() || sig.is_revocation() {
-                continue;
-            }
-            if sig.status() == gpgme::Error::NO_ERROR {
-                let good_uid = sig.signer_user_id();
-                if good_uid.is_ok() {
-                    let good_uid = good_uid.unwrap();
-                    let user_id_id = user_id.id();
-                    if user_id_id.is_ok() && good_uid != user_id_id.unwrap() {
-                        good_signatures.push(good_uid.to_string());
-                    }
-                }
-            }
-        }
-    }
-
-    if good_signatures.is_empty() {
-        let prompt_str = format!(
-            "No recognized signatures found. Would you like to sign key for: {}?",
-            imported_key.get_identity()
-        );
-        let choice = prompt::menu(&prompt_str, &["Yes", "No"], Some(1));
-        println!("Choice is: {}", choice + 1);
-        if choice == 0 {
-            println!(
-                "Please verify the following signature: {}",
-                imported_key.get_pretty_fingerprint()
-            );
-            let confirm = prompt::menu(
-                "Are you sure that you want to sign?",
-                &["Yes", "No"],
-                Some(1),
-            );
-            if confirm == 1 {
-                eprintln!("Key: {} not signed", fingerprint);
-                return None;
-            } else {
-                let all_uids: Vec<&str> = Vec::new();
-                let res = context.sign_key(&imported_gpg_key, all_uids, None);
-                if res.is_err() {
-                    eprintln!("Unable to sign key: {}", fingerprint);
-                    return None;
-                } else {
-                    return Some(imported_key);
-                }
-            }
-        } else {
-            eprintln!("Key: {} not signed", fingerprint);
-            return None;
-        }
-    }
-
-    println!(
-        "The Key: <{}> is signed by the following verified signatures: ",
-        imported_key.get_identity()
-    );
-
-    for identity in good_signatures {
-        println!("\t{}", identity);
-    }
-    let choice = prompt::menu("Would you like to sign the key?", &["Yes", "No"], Some(0));
-
-    if choice == 0 {
-        let empty_uids: Vec<&[u8]> = Vec::new();
-        let res = context.sign_key(&imported_gpg_key, empty_uids, None);
-        if res.is_err() {
-            eprintln!("Unable to sign key");
-            None
-        } else {
-            Some(imported_key)
-        }
-    } else {
-        eprintln!("Didn't sign key");
-        None
-    }
-}
-
 pub struct Key {
     identity: String,
+    user_ids: Vec<String>,
+    emails: Vec<String>,
     fingerprint: String,
     has_secret_key: bool,
+    revoked: bool,
+    expired: bool,
+    can_encrypt: bool,
 }
 
 impl Key {
-    pub fn parse_key(key: &gpgme::Key) -> Result<Key, String> {
-        let mut identity = String::new();
+    pub fn parse_key(key: &KeyRecord) -> Result<Key, String> {
+        let mut user_ids = Vec::new();
+        let mut emails = Vec::new();
         for user_id in key.user_ids() {
-            let identity_result = user_id.email();
-            if identity_result.is_err() {
+            user_ids.push(user_id.get_user_id().to_string());
+            let identity_result = user_id.get_email();
+            if identity_result.is_none() {
                 eprintln!("Unable to read userid identity");
                 continue;
             }
-            identity = identity_result.unwrap().to_string();
+            emails.push(identity_result.unwrap().to_string());
         }
-        if identity.is_empty() {
+        // The primary user id comes first
+        if emails.is_empty() {
             return Err("No userids found for key".to_string());
         }
+        let identity = emails[0].clone();
 
-        let fingerprint = key.fingerprint();
-        if fingerprint.is_err() {
-            return Err(format!(
-                "Unable to read key fingerpring for id: {}",
-                identity
-            ));
-        }
-        let fingerprint = fingerprint.unwrap().to_string();
-
+        let fingerprint = key.get_fingerprint().to_string();
         let has_secret_key = key.has_secret();
         Ok(Key {
             identity,
+            user_ids,
+            emails,
             fingerprint,
             has_secret_key,
+            revoked: key.is_revoked(),
+            expired: key.is_expired(),
+            can_encrypt: key.can_encrypt(),
         })
     }
 
@@ -259,47 +134,54 @@ impl Key {
         &self.identity
     }
 
+    /// Every user id on the key, primary first.
+    pub fn get_user_ids(&self) -> &[String] {
+        &self.user_ids
+    }
+
+    /// The email addresses of every user id on the key, primary first.
+    pub fn get_emails(&self) -> &[String] {
+        &self.emails
+    }
+
     pub fn has_secret_key(&self) -> bool {
         self.has_secret_key
     }
 
+    pub fn is_revoked(&self) -> bool {
+        self.revoked
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expired
+    }
+
+    pub fn can_encrypt(&self) -> bool {
+        self.can_encrypt
+    }
+
     pub fn get_short_fingerprint(&self) -> &str {
         let fingerprint_len = self.fingerprint.len();
         let start_index = fingerprint_len - 16;
         &self.fingerprint[start_index..fingerprint_len]
     }
 
-    pub fn write_key(&self, context: &mut Context) {
+    pub fn write_key(
+        &self,
+        backend: &mut dyn KeyBackend,
+        mode: export::ExportMode,
+        prune: bool,
+    ) -> export::ExportStatus {
         let keys_dir = folder::get_keys_dir();
         let fname = format!("{}.asc", self.fingerprint);
         let abs_path = keys_dir.join(fname);
-        let mut exported_bytes = Vec::new();
 
-        let key_to_export = context.get_key(&self.fingerprint);
-        if key_to_export.is_err() {
-            eprintln!(
-                "Unable to locate key for exporting for fingerprint: {}",
-                self.fingerprint
-            );
-            std::process::exit(1);
-        }
-        let export_res = context.export_keys(
-            vec![&key_to_export.unwrap()],
-            gpgme::ExportMode::empty(),
-            &mut exported_bytes,
-        );
-        if export_res.is_err() {
-            eprintln!("Unable to export key for fingerprint: {}", self.fingerprint);
+        let status = export::write_key_file(backend, &self.fingerprint, mode, &abs_path, prune);
+        if status.is_err() {
+            eprintln!("{}", status.err().unwrap());
             std::process::exit(1);
         }
 
-        let write_res = fs::write(&abs_path, exported_bytes);
-        if write_res.is_err() {
-            eprintln!(
-                "Unable to write exported key for fingerprint: {}",
-                self.fingerprint
-            );
-            std::process::exit(1);
-        }
+        status.unwrap()
     }
 }
@@ -0,0 +1,242 @@
+use super::folder;
+use super::folder::ROOT_FOLDER;
+use super::gpg_id;
+use super::group;
+use super::group::People;
+use super::Key;
+use crate::store;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashSet;
+use toml_edit::ImDocument;
+
+pub const POLICY_FILE_PATH: &str = ".keys/policy.toml";
+const GROUPS_TABLE: &str = "groups";
+const FOLDERS_TABLE: &str = "folders";
+
+pub struct Policy {
+    groups: BTreeMap<String, Vec<String>>,
+    rules: Vec<(String, Vec<String>)>,
+}
+
+pub struct FolderChange {
+    folder: String,
+    current: BTreeSet<String>,
+    wanted: BTreeSet<String>,
+    groups: gpg_id::Groups,
+}
+
+impl FolderChange {
+    pub fn get_folder(&self) -> &str {
+        &self.folder
+    }
+
+    pub fn get_recipients(&self) -> HashSet<String> {
+        self.wanted
+            .iter()
+            .filter(|gpg| !group::is_group(gpg))
+            .cloned()
+            .collect()
+    }
+
+    pub fn get_groups(&self) -> &gpg_id::Groups {
+        &self.groups
+    }
+
+    pub fn is_new(&self) -> bool {
+        self.current.is_empty()
+    }
+
+    pub fn added(&self) -> Vec<&String> {
+        self.wanted.difference(&self.current).collect()
+    }
+
+    pub fn removed(&self) -> Vec<&String> {
+        self.current.difference(&self.wanted).collect()
+    }
+}
+
+impl Policy {
+    pub fn parse(contents: &str) -> Result<Policy, String> {
+        let document = contents.parse::<ImDocument<String>>();
+        if let Err(err) = &document {
+            return Err(err.message().trim().to_string());
+        }
+        let document = document.unwrap();
+
+        let mut policy = Policy {
+            groups: BTreeMap::new(),
+            rules: Vec::new(),
+        };
+        for (name, item) in document.as_table().iter() {
+            let table = item.as_table_like();
+            if table.is_none() || (name != GROUPS_TABLE && name != FOLDERS_TABLE) {
+                return Err(format!(
+                    "{}: unknown entry, expected [{}] or [{}]",
+                    name, GROUPS_TABLE, FOLDERS_TABLE
+                ));
+            }
+            for (key, value) in table.unwrap().iter() {
+                let members = value.as_array().map(|array| {
+                    array
+                        .iter()
+                        .map(|member| member.as_str().map(str::to_string))
+                        .collect::<Option<Vec<String>>>()
+                });
+                if members.is_none() || members.as_ref().unwrap().is_none() {
+                    return Err(format!("{}.{}: must be an array of strings", name, key));
+                }
+                let members = members.unwrap().unwrap();
+                if name == GROUPS_TABLE {
+                    policy.groups.insert(key.to_string(), members);
+                } else {
+                    policy.rules.push((key.to_string(), members));
+                }
+            }
+        }
+
+        for (pattern, _) in &policy.rules {
+            policy.recipients_for_rule(pattern)?;
+        }
+        Ok(policy)
+    }
+
+    pub fn load() -> Result<Option<Policy>, String> {
+        let path = folder::get_pass_dir().join(POLICY_FILE_PATH);
+        if !store::exists(&path) {
+            return Ok(None);
+        }
+        let contents = store::read_to_string(&path);
+        if let Err(err) = &contents {
+            return Err(format!("Unable to read {}: {}", path.display(), err));
+        }
+        let policy = Policy::parse(&contents.unwrap());
+        if policy.is_err() {
+            return Err(format!("{}: {}", path.display(), policy.err().unwrap()));
+        }
+        Ok(Some(policy.unwrap()))
+    }
+
+    /// Recipients for a store-relative folder, from the last rule whose glob
+    /// matches it. Folders no rule matches are left to inherit as before.
+    pub fn recipients_for(&self, folder: &str) -> Option<BTreeSet<String>> {
+        let rule = self
+            .rules
+            .iter()
+            .rev()
+            .find(|(pattern, _)| glob_matches(pattern, folder))?;
+        self.recipients_for_rule(&rule.0).ok()
+    }
+
+    fn recipients_for_rule(&self, pattern: &str) -> Result<BTreeSet<String>, String> {
+        let (_, members) = self.rules.iter().find(|(rule, _)| rule == pattern).unwrap();
+        let mut recipients = BTreeSet::new();
+        for member in members {
+            self.expand(member, &mut Vec::new(), &mut recipients)?;
+        }
+        if recipients.is_empty() {
+            return Err(format!(
+                "{}.\"{}\": expands to no recipients",
+                FOLDERS_TABLE, pattern
+            ));
+        }
+        Ok(recipients)
+    }
+
+    fn expand(
+        &self,
+        member: &str,
+        seen: &mut Vec<String>,
+        recipients: &mut BTreeSet<String>,
+    ) -> Result<(), String> {
+        let group = self.groups.get(member);
+        if group.is_none() {
+            recipients.insert(member.to_string());
+            return Ok(());
+        }
+        if seen.iter().any(|name| name == member) {
+            seen.push(member.to_string());
+            return Err(format!("group cycle: {}", seen.join(" -> ")));
+        }
+        seen.push(member.to_string());
+        for nested in group.unwrap() {
+            self.expand(nested, seen, recipients)?;
+        }
+        seen.pop();
+        Ok(())
+    }
+
+    pub fn plan(&self, people: &People, keys: &[Key]) -> Result<Vec<FolderChange>, String> {
+        let pass_dir = folder::get_pass_dir();
+        let mut changes = Vec::new();
+        for folder in folder::get_store_folders() {
+            let wanted = self.recipients_for(&folder);
+            if wanted.is_none() {
+                continue;
+            }
+            let wanted: HashSet<String> = wanted.unwrap().into_iter().collect();
+            let expanded = group::expand_all(&wanted, people, keys);
+            if expanded.is_err() {
+                return Err(format!("{}: {}", folder, expanded.err().unwrap()));
+            }
+            let (recipients, groups) = expanded.unwrap();
+            let wanted: BTreeSet<String> = recipients
+                .into_iter()
+                .chain(groups.keys().cloned())
+                .collect();
+
+            let dir = pass_dir.join(&folder);
+            let current: BTreeSet<String> = gpg_id::get_base_gpgs_for_dir(&dir)
+                .into_iter()
+                .chain(gpg_id::get_groups_for_dir(&dir).into_keys())
+                .collect();
+            if current != wanted {
+                changes.push(FolderChange {
+                    folder,
+                    current,
+                    wanted,
+                    groups,
+                });
+            }
+        }
+        Ok(changes)
+    }
+}
+
+/// Matches a store-relative folder against a glob where `*` and `?` stay
+/// within one path segment and `**` spans any number of segments.
+pub fn glob_matches(pattern: &str, folder: &str) -> bool {
+    let pattern = pattern.trim_matches('/');
+    if pattern == ROOT_FOLDER || pattern.is_empty() {
+        return folder == ROOT_FOLDER;
+    }
+    if folder == ROOT_FOLDER {
+        return false;
+    }
+    let pattern: Vec<&str> = pattern.split('/').collect();
+    let folder: Vec<&str> = folder.split('/').collect();
+    segments_match(&pattern, &folder)
+}
+
+fn segments_match(pattern: &[&str], folder: &[&str]) -> bool {
+    if pattern.is_empty() {
+        return folder.is_empty();
+    }
+    if pattern[0] == "**" {
+        return (0..=folder.len()).any(|skip| segments_match(&pattern[1..], &folder[skip..]));
+    }
+    !folder.is_empty()
+        && segment_matches(pattern[0].as_bytes(), folder[0].as_bytes())
+        && segments_match(&pattern[1..], &folder[1..])
+}
+
+fn segment_matches(pattern: &[u8], name: &[u8]) -> bool {
+    if pattern.is_empty() {
+        return name.is_empty();
+    }
+    match pattern[0] {
+        b'*' => (0..=name.len()).any(|skip| segment_matches(&pattern[1..], &name[skip..])),
+        b'?' => !name.is_empty() && segment_matches(&pattern[1..], &name[1..]),
+        byte => !name.is_empty() && name[0] == byte && segment_matches(&pattern[1..], &name[1..]),
+    }
+}
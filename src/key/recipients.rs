@@ -0,0 +1,172 @@
+use super::folder;
+use super::folder::ROOT_FOLDER;
+use super::gpg_id;
+use super::Key;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+
+pub const SHORT_ID_LEN: usize = 8;
+const FINGERPRINT_LEN: usize = 40;
+
+/// The effective .gpg-id of a folder: its recipients, the store-relative
+/// folder whose .gpg-id they come from, and the keyring keys each recipient
+/// resolves to.
+pub struct Recipients {
+    gpg_ids: Vec<String>,
+    source: Option<String>,
+    keys: Vec<Vec<String>>,
+}
+
+impl Recipients {
+    pub fn get_gpg_ids(&self) -> &[String] {
+        &self.gpg_ids
+    }
+
+    pub fn get_source(&self) -> Option<&str> {
+        self.source.as_deref()
+    }
+
+    /// Fingerprints of the keys matching the recipient at `index` in
+    /// `get_gpg_ids`, empty if no key in the keyring matches it.
+    pub fn get_keys(&self, index: usize) -> &[String] {
+        &self.keys[index]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.gpg_ids.is_empty()
+    }
+}
+
+pub struct RecipientResolver<'a> {
+    pass_dir: PathBuf,
+    keys: &'a [Key],
+}
+
+impl<'a> RecipientResolver<'a> {
+    pub fn new(keys: &'a [Key]) -> RecipientResolver<'a> {
+        RecipientResolver {
+            pass_dir: folder::get_pass_dir(),
+            keys,
+        }
+    }
+
+    /// Walks from `path` up to the store root and returns the first .gpg-id
+    /// found, like pass does. `path` is relative to the store, or absolute
+    /// inside it.
+    pub fn resolve(&self, path: &Path) -> Result<Recipients, String> {
+        let folders = self.folders(path)?;
+        for depth in (0..=folders.len()).rev() {
+            let relative: PathBuf = folders[..depth].iter().collect();
+            let dir = self.pass_dir.join(&relative);
+            if !gpg_id::has_gpg_id_file(&dir) {
+                continue;
+            }
+            let mut gpg_ids: Vec<String> =
+                gpg_id::get_base_gpgs_for_dir(&dir).into_iter().collect();
+            gpg_ids.sort();
+            let keys = gpg_ids.iter().map(|gpg| self.keys_for(gpg)).collect();
+            let source = if depth == 0 {
+                ROOT_FOLDER.to_string()
+            } else {
+                relative.display().to_string()
+            };
+            return Ok(Recipients {
+                gpg_ids,
+                source: Some(source),
+                keys,
+            });
+        }
+
+        Ok(Recipients {
+            gpg_ids: Vec::new(),
+            source: None,
+            keys: Vec::new(),
+        })
+    }
+
+    fn folders(&self, path: &Path) -> Result<Vec<String>, String> {
+        let relative = if path.is_absolute() {
+            let stripped = path.strip_prefix(&self.pass_dir);
+            if stripped.is_err() {
+                return Err(format!(
+                    "{} is outside the password store {}",
+                    path.display(),
+                    self.pass_dir.display()
+                ));
+            }
+            stripped.unwrap()
+        } else {
+            path
+        };
+
+        let mut folders = Vec::new();
+        for component in relative.components() {
+            match component {
+                Component::Normal(name) => folders.push(name.to_string_lossy().to_string()),
+                Component::CurDir => continue,
+                _ => {
+                    return Err(format!(
+                        "{} is outside the password store {}",
+                        path.display(),
+                        self.pass_dir.display()
+                    ))
+                }
+            }
+        }
+        Ok(folders)
+    }
+
+    fn keys_for(&self, gpg: &str) -> Vec<String> {
+        self.keys
+            .iter()
+            .filter(|key| matches_key(gpg, key))
+            .map(|key| key.get_fingerprint().to_string())
+            .collect()
+    }
+}
+
+/// Whether a .gpg-id entry names `key` the way gpg reads it: its fingerprint
+/// or a key id suffix of it, or a user id search on any of its user ids.
+/// `=` matches a whole user id, `<` an email address and `@` followed by
+/// part of one; anything else matches part of a user id, ignoring case.
+pub fn matches_key(recipient: &str, key: &Key) -> bool {
+    if matches_fingerprint(recipient, key.get_fingerprint()) {
+        return true;
+    }
+    let emails = key.get_emails();
+    let user_ids = key.get_user_ids();
+    if let Some(exact) = recipient.strip_prefix('=') {
+        return user_ids.iter().any(|user_id| user_id == exact);
+    }
+    if let Some(email) = recipient.strip_prefix('<') {
+        let email = email.strip_suffix('>').unwrap_or(email);
+        return emails.iter().any(|e| e.eq_ignore_ascii_case(email));
+    }
+    if let Some(part) = recipient.strip_prefix('@') {
+        let part = part.to_lowercase();
+        return !part.is_empty() && emails.iter().any(|e| e.to_lowercase().contains(&part));
+    }
+    let part = recipient
+        .strip_prefix('*')
+        .unwrap_or(recipient)
+        .to_lowercase();
+    !part.is_empty()
+        && user_ids
+            .iter()
+            .chain(emails)
+            .any(|user_id| user_id.to_lowercase().contains(&part))
+}
+
+/// Whether `id` is a full 40 hex digit fingerprint.
+pub fn is_fingerprint(id: &str) -> bool {
+    id.len() == FINGERPRINT_LEN && id.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Whether a .gpg-id entry is `fingerprint` or a key id suffix of it.
+pub fn matches_fingerprint(recipient: &str, fingerprint: &str) -> bool {
+    let id = recipient.trim_start_matches("0x").to_uppercase();
+    id.len() >= SHORT_ID_LEN
+        && id.chars().all(|c| c.is_ascii_hexdigit())
+        && fingerprint.to_uppercase().ends_with(&id)
+}
@@ -0,0 +1,151 @@
+use super::gpg_id;
+use super::recipients::matches_fingerprint;
+use super::recipients::matches_key;
+use super::Key;
+use serde_json::json;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+
+/// A key some .gpg-id uses that has no `.keys/<fingerprint>.asc`.
+pub struct MissingKey {
+    identity: String,
+    deleted: bool,
+}
+
+/// How the exported keys in `.keys/` line up with the recipients the store's
+/// .gpg-id files actually use.
+pub struct Reconciliation {
+    orphaned: BTreeMap<String, Option<String>>,
+    unexportable: BTreeSet<String>,
+    missing: BTreeMap<String, MissingKey>,
+}
+
+impl Reconciliation {
+    /// `exported` are the fingerprints in `.keys/`, `deleted` the ones whose
+    /// file a commit removed, and `own_key` this device's key, which isn't
+    /// orphaned just because it hasn't been added to a .gpg-id yet.
+    pub fn load(
+        keys: &[Key],
+        exported: &[String],
+        deleted: &[String],
+        own_key: &str,
+    ) -> Reconciliation {
+        let mut reconciliation = Reconciliation {
+            orphaned: BTreeMap::new(),
+            unexportable: BTreeSet::new(),
+            missing: BTreeMap::new(),
+        };
+        let gpgs = gpg_id::get_all_gpgs();
+
+        let mut referenced: BTreeSet<String> = BTreeSet::new();
+        for gpg in &gpgs {
+            let matched: Vec<&Key> = keys.iter().filter(|key| matches_key(gpg, key)).collect();
+            if matched.is_empty() {
+                let exported_fpr = exported.iter().find(|fpr| matches_fingerprint(gpg, fpr));
+                if let Some(exported_fpr) = exported_fpr {
+                    referenced.insert(exported_fpr.clone());
+                } else {
+                    reconciliation.unexportable.insert(gpg.clone());
+                }
+                continue;
+            }
+            for key in matched {
+                let fpr = key.get_fingerprint().to_string();
+                if !exported.contains(&fpr) {
+                    reconciliation.missing.insert(
+                        fpr.clone(),
+                        MissingKey {
+                            identity: key.get_identity().to_string(),
+                            deleted: deleted.contains(&fpr),
+                        },
+                    );
+                }
+                referenced.insert(fpr);
+            }
+        }
+
+        for fpr in exported {
+            if referenced.contains(fpr) || fpr.eq_ignore_ascii_case(own_key) {
+                continue;
+            }
+            let identity = keys
+                .iter()
+                .find(|key| key.get_fingerprint() == fpr)
+                .map(|key| key.get_identity().to_string());
+            reconciliation.orphaned.insert(fpr.clone(), identity);
+        }
+        reconciliation
+    }
+
+    pub fn get_orphaned(&self) -> Vec<&String> {
+        self.orphaned.keys().collect()
+    }
+
+    /// Whether anything needs a human: recipients without a key to export,
+    /// or keys whose export was deliberately deleted but are still in use.
+    pub fn needs_attention(&self) -> bool {
+        !self.unexportable.is_empty() || self.missing.values().any(|missing| missing.deleted)
+    }
+
+    pub fn describe(&self) -> String {
+        let mut description = String::new();
+        if !self.orphaned.is_empty() {
+            description += "Exported keys no .gpg-id uses:\n";
+            for (fpr, identity) in &self.orphaned {
+                let identity = identity.as_deref().unwrap_or("(not in keyring)");
+                description += &format!("  {} {}\n", fpr, identity);
+            }
+        }
+        if !self.unexportable.is_empty() {
+            description += "Recipients without a key in the keyring to export:\n";
+            for gpg in &self.unexportable {
+                description += &format!("  {}\n", gpg);
+            }
+        }
+        if !self.missing.is_empty() {
+            description += "Recipients without an exported key:\n";
+            for (fpr, missing) in &self.missing {
+                description += &format!("  {} {}", fpr, missing.identity);
+                if missing.deleted {
+                    description += " (deleted upstream)";
+                }
+                description += "\n";
+            }
+        }
+        if description.is_empty() {
+            description += ".keys/ matches the .gpg-id files\n";
+        }
+        description
+    }
+
+    pub fn to_json(&self) -> String {
+        let orphaned: Vec<Value> = self
+            .orphaned
+            .iter()
+            .map(|(fpr, identity)| {
+                json!({
+                    "fingerprint": fpr,
+                    "identity": identity,
+                })
+            })
+            .collect();
+        let missing: Vec<Value> = self
+            .missing
+            .iter()
+            .map(|(fpr, missing)| {
+                json!({
+                    "fingerprint": fpr,
+                    "identity": missing.identity,
+                    "deleted_upstream": missing.deleted,
+                })
+            })
+            .collect();
+        let value = json!({
+            "orphaned": orphaned,
+            "unexportable": self.unexportable,
+            "missing": missing,
+        });
+        serde_json::to_string_pretty(&value).unwrap()
+    }
+}
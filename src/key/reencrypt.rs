@@ -0,0 +1,125 @@
+use super::backend::KeyBackend;
+use super::recipients::RecipientResolver;
+use super::recipients::Recipients;
+use super::Key;
+use crate::store;
+use std::path::Path;
+use std::path::PathBuf;
+
+const PASSWORD_FILE_EXTENSION: &str = "gpg";
+
+/// Re-encrypts every password below `dir` for every key in `keys` its
+/// effective .gpg-id names, like gpg does for an email several keys share.
+pub fn reencrypt_dir(
+    backend: &mut dyn KeyBackend,
+    keys: &[Key],
+    pass_dir: &Path,
+    dir: &Path,
+) -> Result<usize, String> {
+    let mut files = Vec::new();
+    collect_password_files(dir, &mut files)?;
+    files.sort();
+
+    let resolver = RecipientResolver::new(keys);
+    for file in &files {
+        let relative_dir = file.parent().unwrap().strip_prefix(pass_dir).unwrap();
+        let resolved = resolver.resolve(relative_dir)?;
+        if resolved.is_empty() {
+            return Err(format!("No recipients found for {}", file.display()));
+        }
+        let fingerprints = usable_keys(&resolved, keys)?;
+        let recipients: Vec<&str> = fingerprints.iter().map(String::as_str).collect();
+
+        let ciphertext = store::read(file);
+        if let Err(err) = &ciphertext {
+            return Err(format!("Unable to read {}: {}", file.display(), err));
+        }
+        let decrypted = backend.decrypt(&ciphertext.unwrap());
+        if decrypted.is_err() {
+            return Err(format!(
+                "Unable to decrypt {}: {}",
+                file.display(),
+                decrypted.err().unwrap()
+            ));
+        }
+        let decrypted = decrypted.unwrap();
+        let reencrypted = backend.encrypt(&recipients, decrypted.get_plaintext());
+        if let Err(err) = &reencrypted {
+            return Err(format!("Unable to encrypt {}: {}", file.display(), err));
+        }
+
+        let tmp_path = file.with_extension("gpg.tmp");
+        let write_res = store::write(&tmp_path, reencrypted.unwrap());
+        if let Err(err) = &write_res {
+            return Err(format!("Unable to write {}: {}", tmp_path.display(), err));
+        }
+        let rename_res = store::rename(&tmp_path, file);
+        if let Err(err) = &rename_res {
+            let _ = store::remove_file(&tmp_path);
+            return Err(format!("Unable to replace {}: {}", file.display(), err));
+        }
+        println!(
+            "{}: reencrypted from {} to {}",
+            file.display(),
+            decrypted.get_recipients().join(" "),
+            recipients.join(" ")
+        );
+    }
+
+    Ok(files.len())
+}
+
+/// The union of the keys each recipient resolves to, leaving out the ones that
+/// can't be encrypted to. A recipient without any such key is an error.
+fn usable_keys(resolved: &Recipients, keys: &[Key]) -> Result<Vec<String>, String> {
+    let mut fingerprints: Vec<String> = Vec::new();
+    for (index, gpg) in resolved.get_gpg_ids().iter().enumerate() {
+        let usable: Vec<&String> = resolved
+            .get_keys(index)
+            .iter()
+            .filter(|fpr| {
+                keys.iter().any(|key| {
+                    key.get_fingerprint() == fpr.as_str()
+                        && !key.is_revoked()
+                        && !key.is_expired()
+                        && key.can_encrypt()
+                })
+            })
+            .collect();
+        if usable.is_empty() {
+            return Err(format!(
+                "{} doesn't match any key usable for encryption",
+                gpg
+            ));
+        }
+        for fpr in usable {
+            if !fingerprints.contains(fpr) {
+                fingerprints.push(fpr.clone());
+            }
+        }
+    }
+    Ok(fingerprints)
+}
+
+fn collect_password_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = store::read_dir(dir);
+    if let Err(err) = &entries {
+        return Err(format!("Unable to read {}: {}", dir.display(), err));
+    }
+
+    for path in entries.unwrap() {
+        let hidden = path
+            .file_name()
+            .map(|name| name.to_string_lossy().starts_with('.'))
+            .unwrap_or(false);
+        if hidden {
+            continue;
+        }
+        if store::is_dir(&path) {
+            collect_password_files(&path, files)?;
+        } else if path.extension() == Some(PASSWORD_FILE_EXTENSION.as_ref()) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
@@ -1,89 +1,327 @@
+mod audit;
+mod cli;
 mod command;
 mod config;
+mod git;
 mod key;
+mod pass_env;
 mod prompt;
+mod store;
 #[cfg(test)]
 mod test;
-use gpgme::Context;
 
-use gpgme::KeyListMode;
+use key::backend::KeyBackend;
+use key::lint::Problem;
+use std::collections::BTreeSet;
 use std::collections::HashSet;
 use std::env;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+
+const GNUPGHOME_VAR_NAME: &str = "GNUPGHOME";
 
 fn main() {
-    let (conf, mut context) = init();
-    let default_key = context.get_key(conf.get_default_key());
-    if default_key.is_err() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let matches = cli::parse_args(&args);
+    if matches.is_err() {
+        eprintln!("{}", matches.err().unwrap());
+        eprintln!("Run '{} --help' for usage", cli::PROGRAM_NAME);
+        std::process::exit(1);
+    }
+    let matches = matches.unwrap();
+
+    if matches.has("help") || matches.command() == "help" {
+        let topic = if matches.command() == "help" {
+            matches.positional(0)
+        } else if matches.command() == "sync" && !args.iter().any(|arg| arg == "sync") {
+            None
+        } else {
+            Some(matches.command())
+        };
+        let usage = cli::usage(topic);
+        if let Err(err) = &usage {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        print!("{}", usage.unwrap());
+        return;
+    }
+    if matches.command() == "completions" {
+        let script = cli::completions(matches.positional(0).unwrap());
+        if let Err(err) = &script {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        print!("{}", script.unwrap());
+        return;
+    }
+    apply_global_flags(&matches);
+    if matches.command() == "config" {
+        config_cmd(&matches);
+        return;
+    }
+    if matches.command() == "init" {
+        init_cmd(&matches);
+        return;
+    }
+
+    let (conf, mut backend) = init(&matches);
+    if backend.get_key(conf.get_default_key()).is_none() {
         eprintln!(
             "Unable to locate default key: {} in keyring",
             conf.get_default_key()
         );
         std::process::exit(1);
     }
-    let signer_res = context.add_signer(&default_key.unwrap());
-    if signer_res.is_err() {
-        eprintln!("Unable to add default key as a signer");
+    let signer_res = backend.add_signer(conf.get_default_key());
+    if let Err(err) = &signer_res {
+        eprintln!("Unable to add default key as a signer: {}", err);
         std::process::exit(1);
     }
 
-    let keys_res = key::get_keys(&mut context);
-    if keys_res.is_err() {
-        eprintln!("Unable to read gpg keys!");
-        std::process::exit(1);
-    }
-    let mut keys = keys_res.unwrap();
-
-    let args: Vec<String> = env::args().collect();
-    if args.len() == 1 {
-        sync(&mut context, &mut keys);
-        return;
-    }
+    let mut keys = load_keys(backend.as_mut(), &conf);
 
-    match args[1].as_str() {
+    match matches.command() {
         "sync" => {
-            sync(&mut context, &mut keys);
+            sync(backend.as_mut(), &mut keys, matches.has("prune"), &conf);
         }
         "gpg-add" => {
-            add_gpgs(&args[2..], &mut context, &conf);
+            let path = matches.value("path").map(str::to_string);
+            let add_res = add_gpgs(path, matches.positionals(), backend.as_mut(), &conf);
+            if let Err(err) = &add_res {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
         }
         "reencrypt" => {
-            reencrypt_cmd(&args[2..]);
+            refresh_groups(matches.positionals(), &keys, backend.as_mut(), &conf);
+            reencrypt_all(matches.positionals(), &conf);
+        }
+        "apply-policy" => {
+            apply_policy(matches.has("dry-run"), &keys, backend.as_mut(), &conf);
+        }
+        "init-store" => {
+            init_store(
+                matches.positional(0),
+                matches.has("fingerprint"),
+                matches.has("git"),
+                backend.as_mut(),
+                &conf,
+            );
+        }
+        "graph" => {
+            let format = matches.value("format").or(conf.get_output_format());
+            graph_cmd(format, backend.as_mut(), &conf);
+        }
+        "access" => {
+            let format = matches.value("format").or(conf.get_output_format());
+            access_cmd(
+                matches.value("who"),
+                matches.value("path"),
+                matches.has("check"),
+                format,
+                &keys,
+                backend.as_mut(),
+                &conf,
+            );
+        }
+        "lint" => {
+            let format = matches.value("format").or(conf.get_output_format());
+            lint_cmd(matches.has("fix"), format, &keys, backend.as_mut(), &conf);
+        }
+        "reconcile" => {
+            let format = matches.value("format").or(conf.get_output_format());
+            reconcile_cmd(format, &keys, &conf);
+        }
+        "compromise" => {
+            compromise_cmd(
+                matches.positional(0).unwrap(),
+                matches.value("revocation-cert"),
+                &keys,
+                backend.as_mut(),
+                &conf,
+            );
+        }
+        "audit" => {
+            let format = matches.value("format").or(conf.get_output_format());
+            audit_cmd(format, backend.as_mut(), &conf);
         }
         _ => {
-            eprintln!("Unknown command: {}", args[1]);
+            eprintln!("Unknown command: {}", matches.command());
             std::process::exit(1);
         }
     }
 }
 
-fn sync(context: &mut Context, keys: &mut Vec<key::Key>) {
-    check_keys_to_import(context);
-    add_fresh_sigs(context);
-    write_missing_keys(context, keys);
+fn apply_global_flags(matches: &cli::Matches) {
+    let gpg_homedir = pass_env::gpg_homedir(&pass_env::gpg_opts());
+    if let Some(gpg_homedir) = gpg_homedir {
+        env::set_var(GNUPGHOME_VAR_NAME, gpg_homedir);
+    }
+    let store = matches.value("store");
+    if let Some(store) = store {
+        env::set_var(key::PASS_DIR_VAR_NAME, store);
+    }
+    let config_file = matches.value("config");
+    if let Some(config_file) = config_file {
+        env::set_var(config::CONFIG_FILE_VAR_NAME, config_file);
+    }
+    let gnupghome = matches.value("gnupghome");
+    if let Some(gnupghome) = gnupghome {
+        env::set_var(GNUPGHOME_VAR_NAME, gnupghome);
+    }
+    let backend = matches.value("backend");
+    if let Some(backend) = backend {
+        env::set_var(key::backend::BACKEND_VAR_NAME, backend);
+    }
+    let profile = matches.value("profile");
+    if let Some(profile) = profile {
+        env::set_var(config::PROFILE_VAR_NAME, profile);
+    }
+    select_prompter(matches);
 }
 
-fn check_keys_to_import(context: &mut Context) {
+fn select_prompter(matches: &cli::Matches) {
+    let answers = matches.value("answers");
+    let mut name = matches.value("prompter");
+    if matches.has("yes") {
+        if let Some(name) = name.filter(|name| *name != "yes") {
+            eprintln!("--yes can't be combined with --prompter {}", name);
+            std::process::exit(1);
+        }
+        name = Some("yes");
+    }
+    if answers.is_some() && name.is_some() {
+        eprintln!("--answers can't be combined with --prompter or --yes");
+        std::process::exit(1);
+    }
+
+    let prompter = if let Some(answers) = answers {
+        let scripted = prompt::ScriptedPrompter::from_file(Path::new(answers));
+        if scripted.is_err() {
+            eprintln!("{}", scripted.err().unwrap());
+            std::process::exit(1);
+        }
+        Arc::new(scripted.unwrap()) as Arc<dyn prompt::Prompter>
+    } else {
+        let opened = prompt::open(name.unwrap_or("terminal"));
+        if opened.is_err() {
+            eprintln!("{}", opened.err().unwrap());
+            std::process::exit(1);
+        }
+        opened.unwrap()
+    };
+    prompt::set_prompter(prompter);
+}
+
+fn sync(backend: &mut dyn KeyBackend, keys: &mut [key::Key], prune: bool, config: &config::Config) {
+    check_keys_to_import(backend, config);
+    add_fresh_sigs(backend, prune, config);
+    write_missing_keys(backend, keys, config);
+    git::commit_changes(config, &[".keys"], "Sync device keys with pass-provision");
+
+    let refreshed = refresh_groups(&[], &load_keys(backend, config), backend, config);
+    if !refreshed.is_empty() {
+        reencrypt_folders(refreshed, config);
+    }
+}
+
+/// The keyring's keys, minus the ones listed as compromised so nothing adds
+/// them back to a .gpg-id.
+fn load_keys(backend: &mut dyn KeyBackend, config: &config::Config) -> Vec<key::Key> {
+    let keys_res = key::get_keys(backend);
+    if keys_res.is_err() {
+        eprintln!("Unable to read gpg keys!");
+        std::process::exit(1);
+    }
+    let compromised = load_compromised(config);
+    let mut keys = keys_res.unwrap();
+    keys.retain(|key| !compromised.iter().any(|fpr| fpr == key.get_fingerprint()));
+    keys
+}
+
+fn load_compromised(config: &config::Config) -> Vec<String> {
+    let compromised = key::compromise::load_compromised(config);
+    if compromised.is_err() {
+        eprintln!("{}", compromised.err().unwrap());
+        std::process::exit(1);
+    }
+    compromised.unwrap()
+}
+
+fn apply_profile(conf: &config::Config, matches: &cli::Matches) {
+    if conf.get_profile().is_some() {
+        eprintln!("Using profile: {}", conf.get_profile().unwrap());
+    }
+    if matches.value("store").is_none() && conf.get_store().is_some() {
+        env::set_var(key::PASS_DIR_VAR_NAME, conf.get_store().unwrap());
+    }
+    if matches.value("gnupghome").is_none() && conf.get_gnupghome().is_some() {
+        env::set_var(GNUPGHOME_VAR_NAME, conf.get_gnupghome().unwrap());
+    }
+    if matches.value("backend").is_none() && conf.get_backend().is_some() {
+        env::set_var(key::backend::BACKEND_VAR_NAME, conf.get_backend().unwrap());
+    }
+    if conf.get_keyring().is_some() {
+        env::set_var(key::backend::KEYRING_VAR_NAME, conf.get_keyring().unwrap());
+    }
+}
+
+fn check_keys_to_import(backend: &mut dyn KeyBackend, config: &config::Config) {
+    let compromised = load_compromised(config);
     let keys_in_folder = key::get_key_ids();
     for key in keys_in_folder {
-        key::import_key(context, key);
+        let import_res = if compromised.contains(&key.to_uppercase()) {
+            Err(key::ImportError::Compromised)
+        } else {
+            key::import_key(backend, key.clone(), config)
+        };
+        if import_res.is_err() {
+            let err = import_res.err().unwrap();
+            if let key::ImportError::Audit(_) = &err {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+            eprintln!("Rejected key: {}: {}", key, err);
+            if err.should_quarantine() {
+                let quarantine_res = key::quarantine_key(&key, &err.to_string());
+                if let Err(err) = &quarantine_res {
+                    eprintln!("{}", err);
+                } else {
+                    eprintln!("Moved to: {}", quarantine_res.unwrap().display());
+                    record_audit(
+                        config,
+                        audit::Event::new("quarantine-key")
+                            .target(&key)
+                            .reason(&err.to_string()),
+                    );
+                }
+            }
+        }
     }
 }
 
-fn add_fresh_sigs(context: &mut Context) {
+fn add_fresh_sigs(backend: &mut dyn KeyBackend, prune: bool, config: &config::Config) {
     let keys_in_folder = key::get_key_ids();
 
     for fpr in keys_in_folder {
-        let key = key::get_key(context, &fpr);
+        let key = key::get_key(backend, &fpr);
         if key.is_none() {
             eprintln!("Unable to find key: {} in keyring", fpr);
             continue;
         }
         let key = key.unwrap();
-        key.write_key(context);
+        let status = key.write_key(backend, config.get_export_mode(), prune);
+        println!("Exported key <{}>: {}", key.get_identity(), status);
     }
 }
 
-fn write_missing_keys(context: &mut Context, keys: &[key::Key]) {
+fn write_missing_keys(backend: &mut dyn KeyBackend, keys: &[key::Key], config: &config::Config) {
     let gpgs = key::gpg_id::get_all_gpgs();
 
     let written_keys = key::get_key_ids();
@@ -91,7 +329,7 @@ fn write_missing_keys(context: &mut Context, keys: &[key::Key]) {
     for gpg in gpgs {
         println!("found gpg: {}", gpg);
         for key in keys {
-            if key.get_identity() == gpg {
+            if key::recipients::matches_key(&gpg, key) {
                 let mut found = false;
                 for written_key in written_keys.iter() {
                     if written_key == key.get_fingerprint() {
@@ -100,129 +338,1156 @@ fn write_missing_keys(context: &mut Context, keys: &[key::Key]) {
                 }
                 if !found {
                     println!("Writing key for identity: {}", key.get_identity());
-                    key.write_key(context);
+                    key.write_key(backend, config.get_export_mode(), false);
                 }
             }
         }
     }
 }
 
-fn add_gpgs(gpgs: &[String], context: &mut Context, config: &config::Config) {
+fn add_gpgs(
+    sub_path: Option<String>,
+    gpgs: &[String],
+    backend: &mut dyn KeyBackend,
+    config: &config::Config,
+) -> Result<(), String> {
+    audit::begin();
     let mut new_gpgs = Vec::new();
     let mut path = key::get_pass_dir();
-    let mut sub_path = None;
+    if let Some(sub_path) = &sub_path {
+        path = path.join(sub_path);
+    }
     if gpgs.is_empty() {
-        let default_key = key::get_key(context, config.get_default_key());
+        let default_key = key::get_key(backend, config.get_default_key());
         if default_key.is_none() {
-            eprintln!("No GPG ids provided and unable to find default key");
-            std::process::exit(1);
+            return Err("No GPG ids provided and unable to find default key".to_string());
         }
         let default_key = default_key.unwrap();
         new_gpgs.push(default_key.get_identity().to_string());
-    } else if gpgs[0] == "-p" {
-        if gpgs.len() == 1 {
-            eprintln!("No path provided to -p argument");
-            std::process::exit(1);
-        }
-        path = path.join(&gpgs[1]);
-        sub_path = Some(gpgs[1].to_string());
-        for gpg in &gpgs[2..] {
-            new_gpgs.push(gpg.to_string());
-        }
     } else {
         for gpg in gpgs {
             new_gpgs.push(gpg.to_string());
         }
     }
 
-    let mut old_gpgs = key::gpg_id::get_gpgs_for_dir(&path);
+    let people = load_people(backend, config);
+    let keys = load_keys(backend, config);
+    let resolved = key::recipients::RecipientResolver::new(&keys).resolve(&path)?;
+    // The recipients may come from a parent's .gpg-id; writing them here signs
+    // them again, so they have to be trusted first. Its groups come along with
+    // them.
+    let mut groups = key::gpg_id::Groups::new();
+    if let Some(source) = resolved.get_source() {
+        let source_dir = key::get_pass_dir().join(source);
+        let verify_res = key::gpg_id::verify_gpg_id_file(&source_dir, config);
+        if let Err(err) = &verify_res {
+            return Err(format!("{}\nRefusing to edit an unverified .gpg-id", err));
+        }
+        groups = key::gpg_id::get_groups_for_dir(&source_dir);
+    }
+    let mut old_gpgs: HashSet<String> = resolved.get_gpg_ids().iter().cloned().collect();
 
-    let mut changed = false;
+    let mut added = Vec::new();
     for new_gpg in new_gpgs {
-        if !old_gpgs.contains(&new_gpg) {
-            changed = true;
-            old_gpgs.insert(new_gpg);
+        if key::group::is_group(&new_gpg) {
+            if groups.contains_key(&new_gpg) {
+                continue;
+            }
+            let members = people.expand(&new_gpg, &keys)?;
+            old_gpgs.extend(members.iter().cloned());
+            groups.insert(new_gpg.clone(), members);
+            added.push(new_gpg);
+        } else if !old_gpgs.contains(&new_gpg) {
+            old_gpgs.insert(new_gpg.clone());
+            added.push(new_gpg);
         }
     }
 
-    if changed {
-        key::gpg_id::write_gpg_ids(&path, &old_gpgs);
-        reencrypt(sub_path);
+    if !added.is_empty() {
+        key::gpg_id::write_gpg_ids_with_groups(&path, &old_gpgs, &groups, config);
+        let audit_path = sub_path.clone().unwrap_or_else(|| ".".to_string());
+        for gpg in &added {
+            record_audit(
+                config,
+                audit::Event::new("add-recipient")
+                    .identity(gpg)
+                    .path(&audit_path),
+            );
+        }
+        reencrypt(sub_path, config);
+    }
+    Ok(())
+}
+
+fn record_audit(config: &config::Config, event: audit::Event) {
+    let res = audit::record(config, event);
+    if let Err(err) = &res {
+        eprintln!("{}", err);
+        std::process::exit(1);
     }
 }
 
-fn reencrypt_cmd(args: &[String]) {
-    if args.is_empty() {
-        reencrypt(None);
+fn reencrypt(path: Option<String>, config: &config::Config) {
+    audit::begin();
+    let res = reencrypt_path(path.clone(), config);
+    if let Err(err) = &res {
+        eprintln!("Reencrypt failed: {}", err);
+        std::process::exit(1);
+    }
+    let path = path.unwrap_or_else(|| ".".to_string());
+    record_audit(config, audit::Event::new("reencrypt").path(&path));
+}
+
+fn reencrypt_all(paths: &[String], config: &config::Config) {
+    if paths.is_empty() {
+        reencrypt(None, config);
         return;
     }
-    reencrypt(Some(args[0].clone()));
+    audit::begin();
+
+    let mut queue: Vec<String> = Vec::new();
+    for path in paths {
+        let nested = paths
+            .iter()
+            .any(|other| other != path && Path::new(path).starts_with(other));
+        if !nested && !queue.contains(path) {
+            queue.push(path.clone());
+        }
+    }
+    queue.reverse();
+
+    let queue = Mutex::new(queue);
+    let failed = AtomicBool::new(false);
+    let runner = command::current_runner();
+    let store = store::current_store();
+    let opener = key::backend::current_opener();
+    // With gpgme every folder goes through `pass init`, which commits when the
+    // store is a git repository, so two running at once race on .git/index.lock
+    let jobs = match key::backend::open_default() {
+        Ok(backend) if backend.name() != "gpgme" => config.get_reencrypt_jobs(),
+        _ => 1,
+    };
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|| loop {
+                command::set_runner(runner.clone());
+                store::set_store(store.clone());
+                key::backend::set_opener(opener.clone());
+                let path = queue.lock().unwrap().pop();
+                if path.is_none() {
+                    break;
+                }
+                let path = path.unwrap();
+                let res = reencrypt_path(Some(path.clone()), config);
+                if let Err(err) = &res {
+                    eprintln!("Reencrypt of {} failed: {}", path, err);
+                    failed.store(true, Ordering::SeqCst);
+                }
+            });
+        }
+    });
+
+    if failed.load(Ordering::SeqCst) {
+        std::process::exit(1);
+    }
+    let mut event = audit::Event::new("reencrypt");
+    for path in paths {
+        event = event.path(path);
+    }
+    record_audit(config, event);
+}
+
+fn apply_policy(
+    dry_run: bool,
+    keys: &[key::Key],
+    backend: &mut dyn KeyBackend,
+    config: &config::Config,
+) {
+    audit::begin();
+    let policy = key::policy::Policy::load();
+    if policy.is_err() {
+        eprintln!("{}", policy.err().unwrap());
+        std::process::exit(1);
+    }
+    let policy = policy.unwrap();
+    if policy.is_none() {
+        eprintln!("No policy found at {}", key::policy::POLICY_FILE_PATH);
+        std::process::exit(1);
+    }
+
+    let changes = policy.unwrap().plan(&load_people(backend, config), keys);
+    if changes.is_err() {
+        eprintln!("{}", changes.err().unwrap());
+        std::process::exit(1);
+    }
+    let changes = changes.unwrap();
+    if changes.is_empty() {
+        println!("All .gpg-id files match the policy");
+        return;
+    }
+    for change in &changes {
+        let new_str = if change.is_new() { " (new)" } else { "" };
+        println!("{}/.gpg-id{}", change.get_folder(), new_str);
+        for gpg in change.added() {
+            println!("  + {}", gpg);
+        }
+        for gpg in change.removed() {
+            println!("  - {}", gpg);
+        }
+    }
+    if dry_run {
+        return;
+    }
+    if prompt::menu("Apply these changes?", &["Yes", "No"], Some(1)) != 0 {
+        return;
+    }
+
+    let pass_dir = key::get_pass_dir();
+    let mut paths = Vec::new();
+    let mut event = audit::Event::new("apply-policy");
+    for change in &changes {
+        key::gpg_id::write_gpg_ids_with_groups(
+            &pass_dir.join(change.get_folder()),
+            &change.get_recipients(),
+            change.get_groups(),
+            config,
+        );
+        event = event.path(change.get_folder());
+        paths.push(change.get_folder().to_string());
+    }
+    record_audit(config, event);
+    reencrypt_folders(paths, config);
+}
+
+/// The device registry, trusting only the keys this device has certified
+/// for identity matches, so a key sync imported but wasn't allowed to sign
+/// never joins a group.
+fn load_people(backend: &mut dyn KeyBackend, config: &config::Config) -> key::group::People {
+    let people = key::group::People::load();
+    if people.is_err() {
+        eprintln!("{}", people.err().unwrap());
+        std::process::exit(1);
+    }
+    let trusted = load_certified(backend, config);
+    people.unwrap().with_trusted(trusted)
+}
+
+/// This device's key and the keys it has certified.
+fn load_certified(backend: &mut dyn KeyBackend, config: &config::Config) -> BTreeSet<String> {
+    let graph = key::graph::TrustGraph::load_keyring(backend);
+    if let Err(err) = &graph {
+        eprintln!("Unable to read keyring: {}", err);
+        std::process::exit(1);
+    }
+    key::get_key(backend, config.get_default_key())
+        .map(|own_key| graph.unwrap().certified_by(own_key.get_fingerprint()))
+        .unwrap_or_default()
+}
+
+fn refresh_groups(
+    paths: &[String],
+    keys: &[key::Key],
+    backend: &mut dyn KeyBackend,
+    config: &config::Config,
+) -> Vec<String> {
+    audit::begin();
+    let people = load_people(backend, config);
+    let folders = if paths.is_empty() {
+        key::get_store_folders()
+    } else {
+        paths.to_vec()
+    };
+
+    let pass_dir = key::get_pass_dir();
+    let mut refreshes = Vec::new();
+    for folder in folders {
+        let res = people.refresh(&pass_dir.join(&folder), keys);
+        if let Err(err) = &res {
+            eprintln!("Unable to expand groups in {}/.gpg-id: {}", folder, err);
+            continue;
+        }
+        if let Some(refresh) = res.unwrap() {
+            refreshes.push((folder, refresh));
+        }
+    }
+    if refreshes.is_empty() {
+        return Vec::new();
+    }
+
+    let describe = |fingerprint: &str| {
+        let key = keys.iter().find(|key| key.get_fingerprint() == fingerprint);
+        if let Some(key) = key {
+            format!("{} <{}>", fingerprint, key.get_identity())
+        } else {
+            fingerprint.to_string()
+        }
+    };
+    for (folder, refresh) in &refreshes {
+        println!("{}/.gpg-id", folder);
+        for gpg in refresh.added() {
+            println!("  + {}", describe(gpg));
+        }
+        for gpg in refresh.removed() {
+            println!("  - {}", describe(gpg));
+        }
+    }
+    let question = "Update these groups and re-encrypt their folders?";
+    if prompt::menu(question, &["Yes", "No"], Some(1)) != 0 {
+        return Vec::new();
+    }
+
+    let mut refreshed = Vec::new();
+    for (folder, refresh) in refreshes {
+        refresh.write(config);
+        println!("Updated groups in {}/.gpg-id", folder);
+        refreshed.push(folder);
+    }
+    if !refreshed.is_empty() {
+        let mut event = audit::Event::new("refresh-groups");
+        for folder in &refreshed {
+            event = event.path(folder);
+        }
+        record_audit(config, event);
+    }
+    refreshed
+}
+
+fn reencrypt_folders(mut folders: Vec<String>, config: &config::Config) {
+    if folders.iter().any(|folder| folder == key::ROOT_FOLDER) {
+        folders.clear();
+    }
+    reencrypt_all(&folders, config);
 }
 
-fn reencrypt(path: Option<String>) {
+fn reencrypt_path(path: Option<String>, config: &config::Config) -> Result<(), String> {
     let mut args = Vec::new();
     args.push("init");
-    let path_str = if path.is_some() {
-        path.as_ref().unwrap().clone()
+    let path_str = if let Some(path) = &path {
+        path.clone()
     } else {
         String::new()
     };
 
     let base_path = key::get_pass_dir();
-    let mut gpgs: Vec<String> = if path.is_some() {
-        let joined_path = base_path.join(path.as_ref().unwrap());
-        key::gpg_id::get_base_gpgs_for_dir(&joined_path)
-            .into_iter()
-            .collect()
+    let gpg_id_dir = if let Some(path) = &path {
+        base_path.join(path)
     } else {
-        key::gpg_id::get_base_gpgs_for_dir(&base_path)
-            .into_iter()
-            .collect()
+        base_path.clone()
     };
-    gpgs.sort();
+    let resolved = key::recipients::RecipientResolver::new(&[]).resolve(&gpg_id_dir)?;
+    if resolved.is_empty() {
+        return Err(format!("No recipients found for {}", gpg_id_dir.display()));
+    }
+    let source = resolved.get_source().unwrap();
+    let inherited = Path::new(source) != Path::new(path.as_deref().unwrap_or(key::ROOT_FOLDER));
+    let verify_res = key::gpg_id::verify_gpg_id_file(&base_path.join(source), config);
+    if let Err(err) = &verify_res {
+        return Err(format!(
+            "{}\nRefusing to reencrypt with an unverified .gpg-id",
+            err
+        ));
+    }
+    let backend = key::backend::open_default();
+    if backend.is_err() {
+        return Err(backend.err().unwrap());
+    }
+    let mut backend = backend.unwrap();
+    if backend.name() != "gpgme" {
+        let keys = load_keys(backend.as_mut(), config);
+        key::reencrypt::reencrypt_dir(backend.as_mut(), &keys, &base_path, &gpg_id_dir)?;
+        return Ok(());
+    }
+    drop(backend);
 
     if path.is_some() {
         args.push("-p");
         args.push(&path_str);
     }
-    for gpg in &gpgs {
+    for gpg in resolved.get_gpg_ids() {
         args.push(gpg);
     }
-    let res = command::oneshot_command("pass", &args);
-    if res.is_err() {
-        println!("Reencrypt failed");
+    let groups = key::gpg_id::get_groups_for_dir(&gpg_id_dir);
+    let res = command::run(&command::Invocation::new("pass", &args).stream(true));
+    if let Err(err) = &res {
+        return Err(err.to_string());
+    }
+    if !inherited && groups.is_empty() {
+        return Ok(());
+    }
+
+    // pass init has just written and committed these
+    let gpg_id_path = gpg_id_dir.join(".gpg-id");
+    let written: Vec<String> = [key::gpg_id::signature_path(&gpg_id_path), gpg_id_path]
+        .iter()
+        .filter(|file| store::exists(file))
+        .map(|file| file.strip_prefix(&base_path).unwrap().display().to_string())
+        .collect();
+    if inherited {
+        // pass init always writes a .gpg-id, drop it so the folder keeps inheriting
+        key::gpg_id::remove_gpg_id_file(&gpg_id_dir)?;
+    } else {
+        let gpgs: HashSet<String> = resolved.get_gpg_ids().iter().cloned().collect();
+        key::gpg_id::write_gpg_ids_with_groups(&gpg_id_dir, &gpgs, &groups, config);
+    }
+    let written: Vec<&str> = written.iter().map(String::as_str).collect();
+    let folder = path.as_deref().unwrap_or(key::ROOT_FOLDER);
+    git::commit_changes(
+        config,
+        &written,
+        &format!("Restore the .gpg-id of {} after reencrypting", folder),
+    );
+    Ok(())
+}
+
+fn config_cmd(matches: &cli::Matches) {
+    let action = matches.positional(0).unwrap();
+    let key_name = matches.positional(1);
+    let value = matches.positional(2);
+    let expected_args = match action {
+        "show" | "validate" => 1,
+        "get" => 2,
+        "set" => 3,
+        _ => {
+            eprintln!(
+                "Unknown config action: {}, expected one of: {}",
+                action,
+                cli::CONFIG_ACTIONS.join(", ")
+            );
+            std::process::exit(1);
+        }
+    };
+    if matches.positionals().len() != expected_args {
+        eprintln!("Wrong number of arguments\nUsage: {}", config_usage(action));
+        std::process::exit(1);
+    }
+
+    let config_env = config::ConfigEnv::from_env();
+    if action == "set" {
+        let set_res = config::set_config_value(
+            &config_env,
+            matches.value("profile"),
+            key_name.unwrap(),
+            value.unwrap(),
+        );
+        if let Err(err) = &set_res {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let config_path = config_env.get_file();
+    if !config_path.exists() {
+        eprintln!("No config file found at {}", config_path.display());
+        std::process::exit(1);
+    }
+    let conf = config::read_config(&config_env);
+    if conf.is_err() {
+        for err in conf.err().unwrap() {
+            eprintln!("{}", err);
+        }
+        std::process::exit(1);
+    }
+    let conf = conf.unwrap();
+
+    match action {
+        "validate" => {
+            if conf.get_default_key().is_empty() {
+                eprintln!(
+                    "{}: default-key isn't set for this store",
+                    config_path.display()
+                );
+                std::process::exit(1);
+            }
+            println!("{}: OK", config_path.display());
+        }
+        "get" => {
+            let setting = conf.get_setting(key_name.unwrap());
+            if let Err(err) = &setting {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+            let setting = setting.unwrap();
+            if setting.is_none() {
+                std::process::exit(1);
+            }
+            let setting = setting.unwrap();
+            if setting.is_str() {
+                println!("{}", setting.as_str().unwrap());
+            } else {
+                println!("{}", setting);
+            }
+        }
+        _ => {
+            let format = matches.value("format").or(conf.get_output_format());
+            if format == Some("json") {
+                let mut settings = serde_json::Map::new();
+                for setting_key in config::SETTINGS {
+                    let setting = conf.get_setting(setting_key).unwrap();
+                    let json_value = if let Some(setting) = setting {
+                        if setting.is_str() {
+                            serde_json::Value::from(setting.as_str().unwrap())
+                        } else if setting.is_bool() {
+                            serde_json::Value::from(setting.as_bool().unwrap())
+                        } else {
+                            serde_json::Value::from(setting.as_integer().unwrap())
+                        }
+                    } else {
+                        serde_json::Value::Null
+                    };
+                    settings.insert(setting_key.to_string(), json_value);
+                }
+                settings.insert(
+                    "profile".to_string(),
+                    serde_json::Value::from(conf.get_profile()),
+                );
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&serde_json::Value::Object(settings)).unwrap()
+                );
+                return;
+            }
+            if conf.get_profile().is_some() {
+                println!("# profile: {}", conf.get_profile().unwrap());
+            }
+            for setting_key in config::SETTINGS {
+                let setting = conf.get_setting(setting_key).unwrap();
+                if let Some(setting) = setting {
+                    println!("{} = {}", setting_key, setting);
+                }
+            }
+        }
+    }
+}
+
+fn config_usage(action: &str) -> String {
+    let args = match action {
+        "get" => " <key>",
+        "set" => " <key> <value>",
+        _ => "",
+    };
+    format!("{} config {}{}", cli::PROGRAM_NAME, action, args)
+}
+
+fn init_store(
+    path: Option<&str>,
+    use_fingerprint: bool,
+    git_init: bool,
+    backend: &mut dyn KeyBackend,
+    config: &config::Config,
+) {
+    if let Some(path) = path {
+        let current_dir = env::current_dir();
+        if current_dir.is_err() {
+            eprintln!("Unable to read current directory");
+            std::process::exit(1);
+        }
+        env::set_var(key::PASS_DIR_VAR_NAME, current_dir.unwrap().join(path));
+    }
+    let pass_dir = key::resolve_pass_dir();
+    if pass_dir.is_none() {
+        eprintln!("Cannot find password store directory");
+        std::process::exit(1);
+    }
+    let pass_dir = pass_dir.unwrap();
+    if pass_dir.join(".gpg-id").exists() {
+        eprintln!("{} is already a password store", pass_dir.display());
+        std::process::exit(1);
+    }
+
+    let default_key = key::get_key(backend, config.get_default_key());
+    if default_key.is_none() {
+        eprintln!("Unable to find default key");
+        std::process::exit(1);
+    }
+    let default_key = default_key.unwrap();
+
+    let create_res = key::create_pass_dir(&pass_dir);
+    if let Err(err) = &create_res {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+
+    let (gpg_id, gpg_id_format) = if use_fingerprint {
+        (default_key.get_fingerprint().to_string(), "fingerprint")
+    } else {
+        (default_key.get_identity().to_string(), "identity")
+    };
+    let mut gpg_ids = HashSet::new();
+    gpg_ids.insert(gpg_id.clone());
+    key::gpg_id::write_gpg_ids(&pass_dir, &gpg_ids, config);
+
+    let status = default_key.write_key(backend, config.get_export_mode(), false);
+    println!("Exported key <{}>: {}", default_key.get_identity(), status);
+
+    let metadata_res = key::write_metadata(gpg_id_format);
+    if let Err(err) = &metadata_res {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+
+    if git_init {
+        let git_res = git::init_repo(&pass_dir, "Initialize password store with pass-provision");
+        if let Err(err) = &git_res {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+
+    println!(
+        "Password store initialized for {} at {}",
+        gpg_id,
+        pass_dir.display()
+    );
+}
+
+fn graph_cmd(format: Option<&str>, backend: &mut dyn KeyBackend, config: &config::Config) {
+    let default_key = key::get_key(backend, config.get_default_key());
+    if default_key.is_none() {
+        eprintln!("Unable to find default key");
+        std::process::exit(1);
+    }
+    let default_key = default_key.unwrap();
+
+    let graph = key::graph::TrustGraph::load(backend, default_key.get_fingerprint());
+    if format == Some("json") {
+        println!("{}", graph.to_json(default_key.get_fingerprint()));
+    } else {
+        print!("{}", graph.to_dot(default_key.get_fingerprint()));
+    }
+}
+
+fn access_cmd(
+    who: Option<&str>,
+    path: Option<&str>,
+    check: bool,
+    format: Option<&str>,
+    keys: &[key::Key],
+    backend: &mut dyn KeyBackend,
+    config: &config::Config,
+) {
+    let mut matrix = key::access::AccessMatrix::load(keys, path);
+    if let Some(who) = who {
+        let res = matrix.filter_who(who, &load_people(backend, config), keys);
+        if let Err(err) = &res {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+    let mismatches = if check {
+        Some(key::access::cross_check(backend, &matrix))
+    } else {
+        None
+    };
+
+    if format == Some("json") {
+        println!("{}", matrix.to_json(mismatches.as_deref()));
+    } else {
+        print!("{}", matrix.to_table());
+        if let Some(mismatches) = &mismatches {
+            print!("{}", key::access::describe_mismatches(mismatches));
+        }
+    }
+
+    if mismatches.is_some_and(|mismatches| !mismatches.is_empty()) {
+        eprintln!("Some passwords aren't encrypted for their folder's .gpg-id");
+        std::process::exit(1);
+    }
+}
+
+fn lint_cmd(
+    fix: bool,
+    format: Option<&str>,
+    keys: &[key::Key],
+    backend: &mut dyn KeyBackend,
+    config: &config::Config,
+) {
+    let mut findings = key::lint::lint_store(keys, &key::get_key_ids());
+    if fix {
+        fix_lint_findings(&mut findings, keys, backend, config);
+    }
+
+    if format == Some("json") {
+        println!("{}", key::lint::to_json(&findings));
+    } else if findings.is_empty() {
+        println!("No problems found in .gpg-id files");
+    } else {
+        print!("{}", key::lint::describe_findings(&findings));
+    }
+
+    if findings.iter().any(|finding| !finding.is_fixed()) {
+        std::process::exit(1);
+    }
+}
+
+/// Rewrites the .gpg-id files with duplicate or padded lines and exports the
+/// keys missing from `.keys/` that this device has certified and that are
+/// still usable. Anything else needs a human.
+fn fix_lint_findings(
+    findings: &mut [key::lint::Finding],
+    keys: &[key::Key],
+    backend: &mut dyn KeyBackend,
+    config: &config::Config,
+) {
+    let mut rewritten: Vec<String> = Vec::new();
+    let mut unverified: Vec<String> = Vec::new();
+    let mut exported: Vec<String> = Vec::new();
+    let certified = load_certified(backend, config);
+    for finding in findings.iter_mut() {
+        match finding.get_problem() {
+            Problem::Duplicate(_) | Problem::Whitespace => {
+                let folder = finding.get_folder().to_string();
+                if unverified.contains(&folder) {
+                    continue;
+                }
+                if !rewritten.contains(&folder) {
+                    let dir = key::get_folder_dir(&folder);
+                    // Rewriting signs the file again, so it has to be trusted first
+                    let verify_res = key::gpg_id::verify_gpg_id_file(&dir, config);
+                    if let Err(err) = &verify_res {
+                        eprintln!("{}\nRefusing to fix an unverified .gpg-id", err);
+                        unverified.push(folder);
+                        continue;
+                    }
+                    let gpgs = key::gpg_id::get_base_gpgs_for_dir(&dir);
+                    key::gpg_id::write_gpg_ids(&dir, &gpgs, config);
+                    rewritten.push(folder);
+                }
+            }
+            Problem::NotExported(fpr) => {
+                let fpr = fpr.clone();
+                let key = keys
+                    .iter()
+                    .find(|key| key.get_fingerprint() == fpr)
+                    .unwrap();
+                if !certified.contains(&fpr) {
+                    eprintln!("Not exporting {}: this device hasn't certified it", fpr);
+                    continue;
+                }
+                if key.is_revoked() || key.is_expired() || !key.can_encrypt() {
+                    eprintln!("Not exporting {}: it can't be used for encryption", fpr);
+                    continue;
+                }
+                if !exported.contains(&fpr) {
+                    key.write_key(backend, config.get_export_mode(), false);
+                    exported.push(fpr);
+                }
+            }
+            _ => continue,
+        }
+        finding.mark_fixed();
+    }
+    if rewritten.is_empty() && exported.is_empty() {
+        return;
+    }
+
+    let pass_dir = key::get_pass_dir();
+    let mut touched: Vec<PathBuf> = Vec::new();
+    for folder in &rewritten {
+        let gpg_id_path = key::get_folder_dir(folder).join(".gpg-id");
+        touched.push(key::gpg_id::signature_path(&gpg_id_path));
+        touched.push(gpg_id_path);
+    }
+    for fpr in &exported {
+        touched.push(key::get_keys_dir().join(format!("{}.asc", fpr)));
+    }
+    let commit_paths: Vec<String> = touched
+        .iter()
+        .filter(|path| store::exists(path))
+        .map(|path| path.strip_prefix(&pass_dir).unwrap().display().to_string())
+        .collect();
+    let commit_paths: Vec<&str> = commit_paths.iter().map(String::as_str).collect();
+    git::commit_changes(config, &commit_paths, "Fix .gpg-id lint findings");
+}
+
+fn reconcile_cmd(format: Option<&str>, keys: &[key::Key], config: &config::Config) {
+    let reconciliation = key::reconcile::Reconciliation::load(
+        keys,
+        &key::get_key_ids(),
+        &git::deleted_key_ids(),
+        config.get_default_key(),
+    );
+
+    if format == Some("json") {
+        println!("{}", reconciliation.to_json());
+    } else {
+        print!("{}", reconciliation.describe());
+        retire_keys(reconciliation.get_orphaned(), config);
+    }
+
+    if reconciliation.needs_attention() {
+        std::process::exit(1);
+    }
+}
+
+fn retire_keys(fingerprints: Vec<&String>, config: &config::Config) {
+    if fingerprints.is_empty() {
+        return;
+    }
+    audit::begin();
+    let question = format!(
+        "Move {} unused key(s) to .keys/retired/?",
+        fingerprints.len()
+    );
+    if prompt::menu(&question, &["Yes", "No"], Some(1)) != 0 {
+        return;
+    }
+
+    for fpr in fingerprints {
+        let retire_res = key::retire_key(fpr);
+        if let Err(err) = &retire_res {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        println!("Retired {}", retire_res.unwrap().display());
+        record_audit(config, audit::Event::new("retire-key").target(fpr));
+    }
+    git::commit_changes(config, &[".keys"], "Retire unused device keys");
+}
+
+fn compromise_cmd(
+    fingerprint: &str,
+    revocation_cert: Option<&str>,
+    keys: &[key::Key],
+    backend: &mut dyn KeyBackend,
+    config: &config::Config,
+) {
+    audit::begin();
+    let fingerprint = fingerprint.trim_start_matches("0x").to_uppercase();
+    if !key::recipients::is_fingerprint(&fingerprint) {
+        eprintln!("{} isn't a full fingerprint", fingerprint);
+        std::process::exit(1);
+    }
+    if fingerprint.eq_ignore_ascii_case(config.get_default_key()) {
+        eprintln!(
+            "{} is this device's own key, run compromise from another device",
+            fingerprint
+        );
+        std::process::exit(1);
+    }
+    let revocation = revocation_cert.map(|path| read_revocation(backend, &fingerprint, path));
+
+    let readable = key::compromise::readable_paths(&fingerprint, keys);
+    let changes = key::compromise::remove_from_gpg_ids(&fingerprint, keys);
+    if changes.is_err() {
+        eprintln!("{}", changes.err().unwrap());
+        std::process::exit(1);
+    }
+    let changes = changes.unwrap();
+
+    if readable.is_empty() {
+        println!("{} couldn't read any password", fingerprint);
+    } else {
+        println!(
+            "{} could read {} password(s), rotate them:",
+            fingerprint,
+            readable.len()
+        );
+        for path in &readable {
+            println!("  {}", path);
+        }
+    }
+    for change in &changes {
+        println!(
+            "Removing {} from {}",
+            fingerprint,
+            Path::new(change.get_folder()).join(".gpg-id").display()
+        );
+    }
+    let question = "Mark the key as compromised and re-encrypt the whole store?";
+    if prompt::menu(question, &["Yes", "No"], Some(1)) != 0 {
+        return;
+    }
+    if let Some(revocation) = &revocation {
+        import_revocation(backend, &fingerprint, revocation);
+    }
+
+    let pass_dir = key::get_pass_dir();
+    let removed_key = key::get_keys_dir().join(format!("{}.asc", fingerprint));
+    let mut touched: Vec<PathBuf> = Vec::new();
+    let mark_res = key::compromise::mark_compromised(&fingerprint, config);
+    if let Err(err) = &mark_res {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+    let compromised_path = pass_dir.join(key::compromise::COMPROMISED_FILE_PATH);
+    touched.push(key::gpg_id::signature_path(&compromised_path));
+    touched.push(compromised_path);
+    if key::get_key_ids().contains(&fingerprint) {
+        let quarantine_res = key::quarantine_key(&fingerprint, "compromised");
+        if let Err(err) = &quarantine_res {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        let quarantine_path = quarantine_res.unwrap();
+        println!("Moved to: {}", quarantine_path.display());
+        // The key file is gone from .keys/, so it's staged as deleted
+        touched.push(removed_key.clone());
+        touched.push(quarantine_path.with_extension("reason"));
+        touched.push(quarantine_path);
+    }
+    let mut event = audit::Event::new("compromise").target(&fingerprint);
+    for change in &changes {
+        change.write(config);
+        event = event.path(change.get_folder());
+        let gpg_id_path = key::get_folder_dir(change.get_folder()).join(".gpg-id");
+        touched.push(key::gpg_id::signature_path(&gpg_id_path));
+        touched.push(gpg_id_path);
+    }
+    record_audit(config, event);
+    touched.push(pass_dir.join(audit::STORE_LOG_PATH));
+
+    // Exits without committing anything if re-encryption fails
+    reencrypt(None, config);
+    for folder in key::get_store_folders() {
+        touched.extend(
+            key::get_password_files(&folder)
+                .into_iter()
+                .map(|file| pass_dir.join(file)),
+        );
+    }
+
+    let commit_paths: Vec<String> = touched
+        .iter()
+        .filter(|path| store::exists(path) || **path == removed_key)
+        .map(|path| path.strip_prefix(&pass_dir).unwrap().display().to_string())
+        .collect();
+    let commit_paths: Vec<&str> = commit_paths.iter().map(String::as_str).collect();
+    git::commit_changes(
+        config,
+        &commit_paths,
+        &format!("Mark key {} as compromised", fingerprint),
+    );
+}
+
+/// Reads the revocation certificate at `path` and checks it in a staging
+/// keyring, without touching the real one yet.
+fn read_revocation(backend: &mut dyn KeyBackend, fingerprint: &str, path: &str) -> Vec<u8> {
+    let data = std::fs::read(path);
+    if let Err(err) = &data {
+        eprintln!("Unable to read {}: {}", path, err);
+        std::process::exit(1);
+    }
+    let data = data.unwrap();
+    let check_res = key::compromise::check_revocation(backend, fingerprint, &data);
+    if let Err(err) = &check_res {
+        eprintln!("{}: {}", path, err);
+        std::process::exit(1);
+    }
+    data
+}
+
+fn import_revocation(backend: &mut dyn KeyBackend, fingerprint: &str, data: &[u8]) {
+    let import_res = backend.import(data);
+    if let Err(err) = &import_res {
+        eprintln!("Unable to import revocation certificate: {}", err);
+        std::process::exit(1);
+    }
+    println!("Imported revocation certificate for {}", fingerprint);
+}
+
+fn audit_cmd(format: Option<&str>, backend: &mut dyn KeyBackend, config: &config::Config) {
+    let path = audit::log_path(config);
+    if let Err(err) = &path {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+    let path = path.unwrap();
+    if path.is_none() {
+        eprintln!("The audit log is off, enable it with: config set audit.log store");
+        std::process::exit(1);
+    }
+    let path = path.unwrap();
+    if !store::exists(&path) {
+        println!("No audit entries in {}", path.display());
+        return;
+    }
+
+    let trusted = audit::trusted_devices(backend, config);
+    let entries = audit::verify_log(backend, &path, &trusted);
+    if entries.is_err() {
+        eprintln!("{}", entries.err().unwrap());
+        std::process::exit(1);
+    }
+    let entries = entries.unwrap();
+    let failed = entries.iter().any(|entry| entry.get_error().is_some());
+
+    if format == Some("json") {
+        let mut values = Vec::new();
+        for entry in &entries {
+            let mut value = entry.get_entry().clone();
+            if value.is_object() {
+                value.as_object_mut().unwrap().remove("signature");
+            } else {
+                value = serde_json::json!({ "raw": value });
+            }
+            value["verified"] = serde_json::Value::from(entry.get_error().is_none());
+            if entry.get_error().is_some() {
+                value["error"] = serde_json::Value::from(entry.get_error().unwrap());
+            }
+            values.push(value);
+        }
+        println!("{}", serde_json::to_string_pretty(&values).unwrap());
+    } else {
+        for entry in &entries {
+            println!("{}", audit::describe(entry));
+        }
+    }
+
+    if failed {
+        eprintln!("The audit log in {} failed verification", path.display());
         std::process::exit(1);
     }
 }
 
-fn init() -> (config::Config, Context) {
-    let context = Context::from_protocol(gpgme::Protocol::OpenPgp);
-    if context.is_err() {
-        eprintln!("Unable to get gpg context. Do you have gpgme installed?");
+fn init(matches: &cli::Matches) -> (config::Config, Box<dyn KeyBackend>) {
+    let conf = load_config(matches);
+    let mut backend = open_backend();
+    if let Some(conf) = conf {
+        return (conf, backend);
+    }
+
+    println!("It looks like you haven't yet set up pass-provision");
+    let fingerprint = select_key_interactively(backend.as_mut());
+    let conf = config::Config::new(fingerprint).with_signing_keys(pass_env::signing_keys());
+    conf.write_config(&config::ConfigEnv::from_env(), matches.value("profile"));
+    (conf, backend)
+}
+
+fn init_cmd(matches: &cli::Matches) {
+    load_config(matches);
+    let mut backend = open_backend();
+
+    let fingerprint = if matches.value("key").is_some() {
+        if matches.has("generate") {
+            eprintln!("--key and --generate can't be used together");
+            std::process::exit(1);
+        }
+        let secret_key = backend.get_secret_key(matches.value("key").unwrap());
+        if secret_key.is_none() {
+            eprintln!("No secret key found for: {}", matches.value("key").unwrap());
+            std::process::exit(1);
+        }
+        secret_key.unwrap().get_fingerprint().to_string()
+    } else if matches.has("generate") {
+        if matches.value("email").is_none() {
+            eprintln!("--generate needs --email");
+            std::process::exit(1);
+        }
+        let spec = key::generate::KeySpec::new(
+            matches.value("name"),
+            matches.value("email").unwrap(),
+            matches.value("algo"),
+            matches.value("expire"),
+            !matches.has("no-passphrase"),
+        );
+        if spec.is_err() {
+            eprintln!("{}", spec.err().unwrap());
+            std::process::exit(1);
+        }
+        let spec = spec.unwrap();
+        println!(
+            "Generating {}/{} key for: {}",
+            spec.get_algo(),
+            spec.get_subkey_algo(),
+            spec.get_user_id()
+        );
+        let generated = backend.generate_key(&spec);
+        if let Err(err) = &generated {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        generated.unwrap()
+    } else {
+        select_key_interactively(backend.as_mut())
+    };
+
+    let config_env = config::ConfigEnv::from_env();
+    config::Config::new(fingerprint.clone()).write_config(&config_env, matches.value("profile"));
+    let conf = parse_config(&config_env);
+    if conf.is_none() {
+        eprintln!("Unable to read back the config");
         std::process::exit(1);
     }
-    let mut context = context.unwrap();
-    context.set_armor(true);
-    context.clear_signers();
-    let mut key_list_mode = KeyListMode::empty();
-    key_list_mode.insert(KeyListMode::LOCAL);
-    key_list_mode.insert(KeyListMode::SIGS);
-    let list_mode_set_result = context.set_key_list_mode(key_list_mode);
-    if list_mode_set_result.is_err() {
-        eprintln!("Unable to read signatures from gpg context");
+    let conf = conf.unwrap();
+    println!("Default key set to: {}", fingerprint);
+
+    let device_key = key::get_key(backend.as_mut(), &fingerprint);
+    if device_key.is_none() {
+        eprintln!("Unable to find key: {} in keyring", fingerprint);
         std::process::exit(1);
     }
+    let device_key = device_key.unwrap();
+
+    let pass_dir = key::resolve_pass_dir();
+    if pass_dir.is_none() || !pass_dir.as_ref().unwrap().exists() {
+        println!("No password store found yet, the key will be exported on the next sync");
+        if matches.has("add-gpg-id") {
+            eprintln!("Unable to add the key to .gpg-id without a password store");
+            std::process::exit(1);
+        }
+        return;
+    }
+    let status = device_key.write_key(backend.as_mut(), conf.get_export_mode(), false);
+    println!("Exported key <{}>: {}", device_key.get_identity(), status);
+
+    if matches.has("add-gpg-id") {
+        let add_res = add_gpgs(
+            None,
+            &[device_key.get_identity().to_string()],
+            backend.as_mut(),
+            &conf,
+        );
+        if let Err(err) = &add_res {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
 
-    let config_path = config::get_config_file_location();
+fn load_config(matches: &cli::Matches) -> Option<config::Config> {
+    let config_env = config::ConfigEnv::from_env();
+    let config_path = config_env.get_file();
+    let mut conf = None;
     if config_path.exists() {
-        let conf = config::Config::parse_config();
-        if conf.is_some() {
-            return (conf.unwrap(), context);
+        conf = parse_config(&config_env);
+    } else if matches.value("profile").is_some() && matches.command() != "init" {
+        eprintln!(
+            "Unknown profile: {}, no config file found at {}",
+            matches.value("profile").unwrap(),
+            config_path.display()
+        );
+        std::process::exit(1);
+    }
+    if let Some(conf) = &conf {
+        apply_profile(conf, matches);
+    }
+    conf
+}
+
+fn parse_config(config_env: &config::ConfigEnv) -> Option<config::Config> {
+    let conf = config::Config::parse_config(config_env);
+    if let Err(errors) = &conf {
+        for err in errors {
+            eprintln!("{}", err);
         }
+        std::process::exit(1);
+    }
+    conf.unwrap()
+}
+
+fn open_backend() -> Box<dyn KeyBackend> {
+    let backend = key::backend::open_default();
+    if backend.is_err() {
+        eprintln!("{}", backend.err().unwrap());
+        std::process::exit(1);
     }
+    backend.unwrap()
+}
 
+fn select_key_interactively(backend: &mut dyn KeyBackend) -> String {
     let mut key_options = Vec::new();
-    let keys = key::get_secret_keys(&mut context);
+    let keys = key::get_secret_keys(backend);
     if keys.is_err() {
         eprintln!("Unable to read keys from GPG");
         std::process::exit(1);
@@ -244,7 +1509,6 @@ fn init() -> (config::Config, Context) {
     key_options.push("Create new key".to_string());
 
     loop {
-        println!("It looks like you haven't yet set up pass-provision");
         let choice = prompt::menu(
             "Please select which key you use to decrypt for pass",
             &(key_options
@@ -253,16 +1517,15 @@ fn init() -> (config::Config, Context) {
                 .collect::<Vec<&str>>()),
             Some(0),
         );
-        println!("choice is: {}", choice);
         if choice == key_options.len() - 1 {
-            let res = command::oneshot_command("gpg", &["--full-gen-key"]);
-            if res.is_err() {
-                eprintln!("Failed key gen");
+            let res = backend.generate_key_interactively();
+            if let Err(err) = &res {
+                eprintln!("Failed key gen: {}", err);
                 eprintln!("Please try again");
                 continue;
             }
 
-            let new_keys = key::get_keys(&mut context);
+            let new_keys = key::get_keys(backend);
             if new_keys.is_err() {
                 eprintln!("Unable to read keys from GPG");
                 std::process::exit(1);
@@ -270,17 +1533,13 @@ fn init() -> (config::Config, Context) {
             let new_keys = new_keys.unwrap();
             for new_key in new_keys {
                 if new_key.has_secret_key() && !seen_before.contains(new_key.get_fingerprint()) {
-                    let conf = config::Config::new(new_key.get_fingerprint().to_string());
-                    conf.write_config();
-                    return (conf, context);
+                    return new_key.get_fingerprint().to_string();
                 }
             }
             eprintln!("Unable to locate newly created key!");
             std::process::exit(1);
         } else {
-            let conf = config::Config::new(key_index[choice].to_string());
-            conf.write_config();
-            return (conf, context);
+            return key_index[choice].to_string();
         }
     }
 }
@@ -0,0 +1,120 @@
+use crate::command;
+use std::env;
+use std::path::PathBuf;
+
+pub const EXTENSION_VAR_NAME: &str = "PASS_PROVISION_EXTENSION";
+const PREFIX_VAR_NAME: &str = "PREFIX";
+const GPG_VAR_NAME: &str = "GPG";
+const GPG_OPTS_VAR_NAME: &str = "GPG_OPTS";
+const PASS_GPG_OPTS_VAR_NAME: &str = "PASSWORD_STORE_GPG_OPTS";
+const SIGNING_KEY_VAR_NAME: &str = "PASSWORD_STORE_SIGNING_KEY";
+const DEFAULT_GPG: &str = "gpg";
+// pass adds these to GPG_OPTS for its own batch runs, they'd break a prompt
+const BATCH_OPTS: &[&str] = &["--batch", "--yes", "--quiet"];
+
+pub fn is_extension() -> bool {
+    env::var_os(EXTENSION_VAR_NAME).is_some()
+}
+
+pub fn store_prefix() -> Option<PathBuf> {
+    if !is_extension() {
+        return None;
+    }
+    let prefix = env::var(PREFIX_VAR_NAME);
+    if prefix.is_err() || prefix.as_ref().unwrap().is_empty() {
+        return None;
+    }
+    Some(PathBuf::from(prefix.unwrap()))
+}
+
+pub fn gpg_program() -> String {
+    if is_extension() {
+        let gpg = env::var(GPG_VAR_NAME);
+        if let Ok(gpg) = gpg {
+            if !gpg.is_empty() {
+                return gpg;
+            }
+        }
+    }
+    DEFAULT_GPG.to_string()
+}
+
+pub fn gpg_opts() -> Vec<String> {
+    if is_extension() {
+        let opts = env::var(GPG_OPTS_VAR_NAME);
+        if let Ok(opts) = &opts {
+            return parse_extension_opts(opts);
+        }
+    }
+
+    let opts = env::var(PASS_GPG_OPTS_VAR_NAME);
+    if opts.is_err() {
+        return Vec::new();
+    }
+    parse_pass_opts(&opts.unwrap())
+}
+
+pub fn signing_keys() -> Vec<String> {
+    let signing_keys = env::var(SIGNING_KEY_VAR_NAME);
+    if signing_keys.is_err() {
+        return Vec::new();
+    }
+    parse_pass_opts(&signing_keys.unwrap())
+}
+
+pub fn parse_extension_opts(opts: &str) -> Vec<String> {
+    opts.lines()
+        .filter(|opt| !opt.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+pub fn parse_pass_opts(opts: &str) -> Vec<String> {
+    opts.split_whitespace().map(str::to_string).collect()
+}
+
+pub fn gpg_homedir(opts: &[String]) -> Option<String> {
+    let mut homedir = None;
+    let mut iter = opts.iter();
+    while let Some(opt) = iter.next() {
+        if opt == "--homedir" {
+            let dir = iter.next();
+            if let Some(dir) = dir {
+                homedir = Some(dir.clone());
+            }
+        } else if let Some(dir) = opt.strip_prefix("--homedir=") {
+            homedir = Some(dir.to_string());
+        }
+    }
+    homedir
+}
+
+/// `opts` without the options that keep gpg from asking the user anything.
+pub fn interactive_opts(opts: &[String]) -> Vec<String> {
+    opts.iter()
+        .filter(|opt| !BATCH_OPTS.contains(&opt.as_str()))
+        .cloned()
+        .collect()
+}
+
+pub fn gpg_interactive_command(args: &[&str]) -> Result<(), command::CommandError> {
+    let mut full_args = interactive_opts(&gpg_opts());
+    full_args.extend(args.iter().map(|arg| arg.to_string()));
+    let full_args: Vec<&str> = full_args.iter().map(String::as_str).collect();
+    command::run(&command::Invocation::new(&gpg_program(), &full_args).interactive())?;
+    Ok(())
+}
+
+pub fn gpg_output_command(args: &[&str]) -> Result<Vec<u8>, command::CommandError> {
+    let full_args = gpg_command_args(args);
+    let full_args: Vec<&str> = full_args.iter().map(String::as_str).collect();
+    command::output_command(&gpg_program(), &full_args)
+}
+
+fn gpg_command_args(args: &[&str]) -> Vec<String> {
+    let mut full_args = gpg_opts();
+    for arg in args {
+        full_args.push(arg.to_string());
+    }
+    full_args
+}
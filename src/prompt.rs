@@ -1,64 +1,448 @@
+use serde_json::json;
+use serde_json::Value;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs;
+use std::fs::File;
 use std::io::stdout;
+use std::io::BufRead;
 use std::io::Write;
+use std::os::fd::AsFd;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::Mutex;
 
-#[cfg(test)]
-use std::io::BufRead;
+pub const PROMPTERS: &[&str] = &["terminal", "yes", "no", "json-rpc"];
 
-pub fn prompt(prompt: &str) -> String {
-    print!("{} ", prompt);
-    stdout().flush().unwrap();
-    let mut input = String::new();
+thread_local! {
+    static PROMPTER: RefCell<Option<Arc<dyn Prompter>>> = const { RefCell::new(None) };
+    static ANSWERS: RefCell<Vec<(String, String)>> = const { RefCell::new(Vec::new()) };
+}
 
-    #[cfg(not(test))]
-    let stdin_source = std::io::stdin();
+pub trait Prompter: Send + Sync {
+    fn ask(&self, question: &str) -> Result<String, String>;
+    fn choose(
+        &self,
+        question: &str,
+        options: &[&str],
+        default: Option<usize>,
+    ) -> Result<usize, String>;
 
-    #[cfg(test)]
-    let mut stdin_source = unsafe { super::test::TEST_STDIN };
+    /// Like `choose`, for questions that grant trust, which a blanket answer
+    /// must never accept. `safe` is the option that leaves things as they are.
+    fn choose_safely(
+        &self,
+        question: &str,
+        options: &[&str],
+        safe: usize,
+    ) -> Result<usize, String> {
+        self.choose(question, options, Some(safe))
+    }
 
-    let res = stdin_source.read_line(&mut input);
-    if res.is_err() {
-        eprintln!("No input received");
-        std::process::exit(1);
+    fn is_interactive(&self) -> bool {
+        false
+    }
+}
+
+pub struct TerminalPrompter;
+
+impl Prompter for TerminalPrompter {
+    fn ask(&self, question: &str) -> Result<String, String> {
+        print!("{} ", question);
+        stdout().flush().unwrap();
+        let mut input = String::new();
+        let res = std::io::stdin().read_line(&mut input);
+        if res.is_err() || res.unwrap() == 0 {
+            return Err("No input received".to_string());
+        }
+        Ok(input.trim_end_matches(['\r', '\n']).to_string())
+    }
+
+    fn choose(
+        &self,
+        question: &str,
+        options: &[&str],
+        default: Option<usize>,
+    ) -> Result<usize, String> {
+        loop {
+            println!("{}", question);
+            for (index, option) in options.iter().enumerate() {
+                let default_str = if default == Some(index) {
+                    "[default]"
+                } else {
+                    ""
+                };
+                println!("[ {} ]: {} {}", index + 1, option, default_str);
+            }
+
+            let answer = self.ask(">")?;
+            let choice = parse_choice(&answer, options, default);
+            if let Err(err) = &choice {
+                eprintln!("{}, please try again!", err);
+                continue;
+            }
+            return choice;
+        }
+    }
+
+    fn is_interactive(&self) -> bool {
+        true
+    }
+}
+
+/// Answers prompts from a fixed list, one answer per line. An empty answer
+/// takes the default, and so does running out of answers.
+pub struct ScriptedPrompter {
+    answers: Mutex<VecDeque<String>>,
+}
+
+impl ScriptedPrompter {
+    pub fn new(answers: &[&str]) -> ScriptedPrompter {
+        ScriptedPrompter {
+            answers: Mutex::new(answers.iter().map(|answer| answer.to_string()).collect()),
+        }
+    }
+
+    pub fn from_file(path: &Path) -> Result<ScriptedPrompter, String> {
+        let contents = fs::read_to_string(path);
+        if let Err(err) = &contents {
+            return Err(format!(
+                "Unable to read answers file {}: {}",
+                path.display(),
+                err
+            ));
+        }
+        let contents = contents.unwrap();
+        let answers: Vec<&str> = contents
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .collect();
+        Ok(ScriptedPrompter::new(&answers))
+    }
+
+    fn next_answer(&self) -> Option<String> {
+        self.answers.lock().unwrap().pop_front()
+    }
+}
+
+impl Prompter for ScriptedPrompter {
+    fn ask(&self, question: &str) -> Result<String, String> {
+        let answer = self.next_answer();
+        if answer.is_none() {
+            return Err(format!("{}: no scripted answer left", question));
+        }
+        Ok(answer.unwrap())
+    }
+
+    fn choose(
+        &self,
+        question: &str,
+        options: &[&str],
+        default: Option<usize>,
+    ) -> Result<usize, String> {
+        let answer = self.next_answer().unwrap_or_default();
+        let choice = parse_choice(&answer, options, default);
+        if let Err(err) = &choice {
+            return Err(format!("{}: {}", question, err));
+        }
+        choice
+    }
+}
+
+/// Picks the named option ("Yes" or "No") whenever it's offered and the
+/// default otherwise. Free-form questions can't be answered, and questions
+/// that grant trust always get the safe answer.
+pub struct AssumePrompter {
+    answer: &'static str,
+}
+
+impl AssumePrompter {
+    pub fn yes() -> AssumePrompter {
+        AssumePrompter { answer: "Yes" }
+    }
+
+    pub fn no() -> AssumePrompter {
+        AssumePrompter { answer: "No" }
+    }
+}
+
+impl Prompter for AssumePrompter {
+    fn ask(&self, question: &str) -> Result<String, String> {
+        Err(format!(
+            "{}: no answer available with --prompter {}",
+            question,
+            self.answer.to_lowercase()
+        ))
+    }
+
+    fn choose(
+        &self,
+        question: &str,
+        options: &[&str],
+        default: Option<usize>,
+    ) -> Result<usize, String> {
+        let choice = options.iter().position(|option| *option == self.answer);
+        let choice = choice.or(default);
+        if choice.is_none() {
+            return Err(format!(
+                "{}: no answer available with --prompter {}",
+                question,
+                self.answer.to_lowercase()
+            ));
+        }
+        Ok(choice.unwrap())
+    }
+
+    fn choose_safely(
+        &self,
+        _question: &str,
+        _options: &[&str],
+        safe: usize,
+    ) -> Result<usize, String> {
+        Ok(safe)
     }
+}
 
-    input
+/// Sends every question as a JSON-RPC 2.0 request, one per line, and reads
+/// the matching response line back.
+pub struct JsonRpcPrompter {
+    channel: Mutex<JsonRpcChannel>,
 }
 
-pub fn menu(prompt_str: &str, options: &[&str], default: Option<usize>) -> usize {
-    loop {
-        println!("{}", prompt_str);
+struct JsonRpcChannel {
+    reader: Box<dyn BufRead + Send>,
+    writer: Box<dyn Write + Send>,
+    next_id: u64,
+}
 
-        let mut index = 0;
-        while index < options.len() {
-            let is_default = if default.is_none() {
-                false
-            } else {
-                &index == default.as_ref().unwrap()
-            };
+impl JsonRpcPrompter {
+    pub fn new(reader: Box<dyn BufRead + Send>, writer: Box<dyn Write + Send>) -> JsonRpcPrompter {
+        JsonRpcPrompter {
+            channel: Mutex::new(JsonRpcChannel {
+                reader,
+                writer,
+                next_id: 1,
+            }),
+        }
+    }
+
+    /// Speaks the protocol over stdin and stdout. Stdout carries nothing but
+    /// requests from here on: everything else written to it, by us or by the
+    /// commands we run, goes to stderr.
+    pub fn stdio() -> Result<JsonRpcPrompter, String> {
+        let protocol = take_stdout()?;
+        Ok(JsonRpcPrompter::new(
+            Box::new(std::io::BufReader::new(std::io::stdin())),
+            Box::new(protocol),
+        ))
+    }
 
-            let default_str = if is_default { "[default]" } else { "" };
-            println!("[ {} ]: {} {}", index + 1, options[index], default_str);
-            index += 1;
+    fn call(&self, method: &str, params: Value) -> Result<Value, String> {
+        let mut channel = self.channel.lock().unwrap();
+        let id = channel.next_id;
+        channel.next_id += 1;
+
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        });
+        let write_res = writeln!(channel.writer, "{}", request);
+        if write_res.is_err() || channel.writer.flush().is_err() {
+            return Err("Unable to send prompt request".to_string());
         }
 
-        let choice_full = prompt("> ");
-        let choice = choice_full.trim();
-        if choice.is_empty() && default.is_some() {
-            return default.unwrap();
+        let mut line = String::new();
+        let read_res = channel.reader.read_line(&mut line);
+        if read_res.is_err() || read_res.unwrap() == 0 {
+            return Err("No response received for prompt request".to_string());
+        }
+        let response = serde_json::from_str::<Value>(&line);
+        if response.is_err() {
+            return Err(format!("Malformed prompt response: {}", line.trim()));
+        }
+        let mut response = response.unwrap();
+        if response["id"] != json!(id) {
+            return Err(format!(
+                "Prompt response id {} doesn't match request id {}",
+                response["id"], id
+            ));
         }
+        if !response["error"].is_null() {
+            let message = response["error"]["message"]
+                .as_str()
+                .unwrap_or("unknown error");
+            return Err(format!("Prompt request failed: {}", message));
+        }
+        Ok(response["result"].take())
+    }
+}
 
-        let usize_parse = choice.parse::<usize>();
-        if usize_parse.is_err() {
-            eprintln!("Unable to parse input, please try again!");
-            continue;
+impl Prompter for JsonRpcPrompter {
+    fn ask(&self, question: &str) -> Result<String, String> {
+        let result = self.call("ask", json!({ "question": question }))?;
+        let answer = result.as_str();
+        if answer.is_none() {
+            return Err(format!(
+                "{}: expected a string answer, got {}",
+                question, result
+            ));
         }
+        Ok(answer.unwrap().to_string())
+    }
+
+    fn choose(
+        &self,
+        question: &str,
+        options: &[&str],
+        default: Option<usize>,
+    ) -> Result<usize, String> {
+        let result = self.call(
+            "choose",
+            json!({ "question": question, "options": options, "default": default }),
+        )?;
+        if let Some(default) = default.filter(|_| result.is_null()) {
+            return Ok(default);
+        }
+        let choice = result.as_u64();
+        if choice.is_none() || choice.unwrap() as usize >= options.len() {
+            return Err(format!(
+                "{}: expected an option index below {}, got {}",
+                question,
+                options.len(),
+                result
+            ));
+        }
+        Ok(choice.unwrap() as usize)
+    }
+}
 
-        let num_choice = usize_parse.unwrap();
-        if 0 < num_choice && num_choice > options.len() {
-            eprintln!("Choice is out of bounds, please try again!");
-            continue;
+/// Returns a handle on the original stdout and points fd 1 at stderr.
+fn take_stdout() -> Result<File, String> {
+    let _ = stdout().flush();
+    let protocol = stdout().as_fd().try_clone_to_owned();
+    if let Err(err) = &protocol {
+        return Err(format!("Unable to duplicate stdout: {}", err));
+    }
+    let redirected = unsafe { libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) };
+    if redirected == -1 {
+        return Err(format!(
+            "Unable to redirect stdout to stderr: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(File::from(protocol.unwrap()))
+}
+
+/// Accepts a 1-based option number or the option's name, or an empty answer
+/// for the default.
+pub fn parse_choice(
+    answer: &str,
+    options: &[&str],
+    default: Option<usize>,
+) -> Result<usize, String> {
+    let answer = answer.trim();
+    if answer.is_empty() {
+        if default.is_none() {
+            return Err("An answer is required".to_string());
         }
+        return Ok(default.unwrap());
+    }
+
+    let named = options
+        .iter()
+        .position(|option| option.eq_ignore_ascii_case(answer));
+    if let Some(named) = named {
+        return Ok(named);
+    }
 
-        return num_choice - 1;
+    let number = answer.parse::<usize>();
+    if number.is_err() {
+        return Err(format!("Unable to parse choice: {}", answer));
+    }
+    let number = number.unwrap();
+    if number == 0 || number > options.len() {
+        return Err(format!(
+            "Choice {} is out of bounds, expected 1 to {}",
+            number,
+            options.len()
+        ));
     }
+    Ok(number - 1)
+}
+
+pub fn open(name: &str) -> Result<Arc<dyn Prompter>, String> {
+    match name {
+        "terminal" => Ok(Arc::new(TerminalPrompter)),
+        "yes" => Ok(Arc::new(AssumePrompter::yes())),
+        "no" => Ok(Arc::new(AssumePrompter::no())),
+        "json-rpc" => Ok(Arc::new(JsonRpcPrompter::stdio()?)),
+        _ => Err(format!(
+            "Unknown prompter: {}, expected one of: {}",
+            name,
+            PROMPTERS.join(", ")
+        )),
+    }
+}
+
+pub fn current_prompter() -> Arc<dyn Prompter> {
+    let prompter = PROMPTER.with(|prompter| prompter.borrow().clone());
+    if let Some(prompter) = prompter {
+        return prompter;
+    }
+    #[cfg(test)]
+    return Arc::new(ScriptedPrompter::new(&[]));
+    #[cfg(not(test))]
+    Arc::new(TerminalPrompter)
+}
+
+pub fn set_prompter(prompter: Arc<dyn Prompter>) {
+    PROMPTER.with(|current| *current.borrow_mut() = Some(prompter));
+}
+
+pub fn menu(question: &str, options: &[&str], default: Option<usize>) -> usize {
+    let prompter = current_prompter();
+    let choice = prompter.choose(question, options, default);
+    record_choice(prompter.as_ref(), question, options, choice)
+}
+
+/// A menu whose answer grants trust, like signing a key nobody vouched for.
+/// `--yes` never accepts it; it gets `safe`, which must change nothing.
+pub fn security_menu(question: &str, options: &[&str], safe: usize) -> usize {
+    let prompter = current_prompter();
+    let choice = prompter.choose_safely(question, options, safe);
+    record_choice(prompter.as_ref(), question, options, choice)
+}
+
+fn record_choice(
+    prompter: &dyn Prompter,
+    question: &str,
+    options: &[&str],
+    choice: Result<usize, String>,
+) -> usize {
+    if let Err(err) = &choice {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+    let choice = choice.unwrap();
+    if !prompter.is_interactive() {
+        println!("{} > {}", question, options[choice]);
+    }
+    ANSWERS.with(|answers| {
+        answers
+            .borrow_mut()
+            .push((question.to_string(), options[choice].to_string()))
+    });
+    choice
+}
+
+/// Forgets the answers given through `menu` so far.
+pub fn clear_answers() {
+    ANSWERS.with(|answers| answers.borrow_mut().clear());
+}
+
+/// Returns the answers given through `menu` since the last call.
+pub fn take_answers() -> Vec<(String, String)> {
+    ANSWERS.with(|answers| answers.borrow_mut().split_off(0))
 }
@@ -0,0 +1,252 @@
+use std::cell::RefCell;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+thread_local! {
+    static STORE: RefCell<Option<Arc<dyn StoreFs>>> = const { RefCell::new(None) };
+}
+
+pub trait StoreFs: Send + Sync {
+    fn root(&self) -> Option<PathBuf>;
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()>;
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+    fn exists(&self, path: &Path) -> bool;
+    fn is_dir(&self, path: &Path) -> bool;
+}
+
+pub struct DiskStore;
+
+impl StoreFs for DiskStore {
+    fn root(&self) -> Option<PathBuf> {
+        None
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        fs::write(path, contents)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(path)? {
+            paths.push(entry?.path());
+        }
+        Ok(paths)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+}
+
+pub fn current_store() -> Arc<dyn StoreFs> {
+    let store = STORE.with(|store| store.borrow().clone());
+    if let Some(store) = store {
+        return store;
+    }
+    Arc::new(DiskStore)
+}
+
+pub fn set_store(store: Arc<dyn StoreFs>) {
+    STORE.with(|current| *current.borrow_mut() = Some(store));
+}
+
+pub fn root() -> Option<PathBuf> {
+    current_store().root()
+}
+
+pub fn read(path: &Path) -> io::Result<Vec<u8>> {
+    current_store().read(path)
+}
+
+pub fn read_to_string(path: &Path) -> io::Result<String> {
+    let contents = read(path)?;
+    let contents = String::from_utf8(contents);
+    if contents.is_err() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "stream did not contain valid UTF-8",
+        ));
+    }
+    Ok(contents.unwrap())
+}
+
+pub fn write<C: AsRef<[u8]>>(path: &Path, contents: C) -> io::Result<()> {
+    current_store().write(path, contents.as_ref())
+}
+
+pub fn rename(from: &Path, to: &Path) -> io::Result<()> {
+    current_store().rename(from, to)
+}
+
+pub fn remove_file(path: &Path) -> io::Result<()> {
+    current_store().remove_file(path)
+}
+
+pub fn create_dir_all(path: &Path) -> io::Result<()> {
+    current_store().create_dir_all(path)
+}
+
+pub fn read_dir(path: &Path) -> io::Result<Vec<PathBuf>> {
+    current_store().read_dir(path)
+}
+
+pub fn exists(path: &Path) -> bool {
+    current_store().exists(path)
+}
+
+pub fn is_dir(path: &Path) -> bool {
+    current_store().is_dir(path)
+}
+
+pub fn is_file(path: &Path) -> bool {
+    exists(path) && !is_dir(path)
+}
+
+#[cfg(test)]
+pub struct MemoryStore {
+    root: PathBuf,
+    files: std::sync::Mutex<std::collections::BTreeMap<PathBuf, Vec<u8>>>,
+    dirs: std::sync::Mutex<std::collections::BTreeSet<PathBuf>>,
+}
+
+#[cfg(test)]
+impl MemoryStore {
+    pub fn new(root: &str) -> MemoryStore {
+        let store = MemoryStore {
+            root: PathBuf::from(root),
+            files: std::sync::Mutex::new(std::collections::BTreeMap::new()),
+            dirs: std::sync::Mutex::new(std::collections::BTreeSet::new()),
+        };
+        store.create_dir_all(Path::new(root)).unwrap();
+        store
+    }
+
+    pub fn with_file<C: AsRef<[u8]>>(self, relative_path: &str, contents: C) -> MemoryStore {
+        let path = self.root.join(relative_path);
+        self.create_dir_all(path.parent().unwrap()).unwrap();
+        self.write(&path, contents.as_ref()).unwrap();
+        self
+    }
+
+    pub fn file(&self, relative_path: &str) -> Option<Vec<u8>> {
+        let files = self.files.lock().unwrap();
+        files.get(&self.root.join(relative_path)).cloned()
+    }
+
+    fn not_found(path: &Path) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{}: no such file or directory", path.display()),
+        )
+    }
+
+    fn check_parent(&self, path: &Path) -> io::Result<()> {
+        let parent = path.parent();
+        if let Some(parent) = parent.filter(|parent| !self.is_dir(parent)) {
+            return Err(MemoryStore::not_found(parent));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl StoreFs for MemoryStore {
+    fn root(&self) -> Option<PathBuf> {
+        Some(self.root.clone())
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let files = self.files.lock().unwrap();
+        let contents = files.get(path);
+        if contents.is_none() {
+            return Err(MemoryStore::not_found(path));
+        }
+        Ok(contents.unwrap().clone())
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        self.check_parent(path)?;
+        let mut files = self.files.lock().unwrap();
+        files.insert(path.to_path_buf(), contents.to_vec());
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.check_parent(to)?;
+        let mut files = self.files.lock().unwrap();
+        let contents = files.remove(from);
+        if contents.is_none() {
+            return Err(MemoryStore::not_found(from));
+        }
+        files.insert(to.to_path_buf(), contents.unwrap());
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        let mut files = self.files.lock().unwrap();
+        if files.remove(path).is_none() {
+            return Err(MemoryStore::not_found(path));
+        }
+        Ok(())
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        let mut dirs = self.dirs.lock().unwrap();
+        for ancestor in path.ancestors() {
+            if !ancestor.as_os_str().is_empty() {
+                dirs.insert(ancestor.to_path_buf());
+            }
+        }
+        Ok(())
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        if !self.is_dir(path) {
+            return Err(MemoryStore::not_found(path));
+        }
+        let files = self.files.lock().unwrap();
+        let dirs = self.dirs.lock().unwrap();
+        let children = files
+            .keys()
+            .chain(dirs.iter())
+            .filter(|child| child.parent() == Some(path))
+            .cloned()
+            .collect();
+        Ok(children)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.files.lock().unwrap().contains_key(path) || self.is_dir(path)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.dirs.lock().unwrap().contains(path)
+    }
+}
@@ -0,0 +1,137 @@
+use super::virtual_store;
+use super::Fixture;
+use crate::key::access;
+use crate::key::access::AccessMatrix;
+use crate::key::backend::ExportFilter;
+use crate::key::backend::FakeBackend;
+use crate::key::backend::KeyBackend;
+use crate::key::get_keys;
+use crate::key::group::People;
+use serde_json::Value;
+
+fn folder_readers(matrix: &AccessMatrix) -> Vec<(String, Vec<String>)> {
+    let value: Value = serde_json::from_str(&matrix.to_json(None)).unwrap();
+    value["folders"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|folder| {
+            let readers = folder["readers"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|reader| reader.as_str().unwrap().to_string())
+                .collect();
+            (folder["folder"].as_str().unwrap().to_string(), readers)
+        })
+        .collect()
+}
+
+#[test]
+fn test_fake_access_matrix() {
+    let mut fixture = Fixture::new();
+    let laptop = fixture.laptop.clone();
+    let phone = fixture.key("phone@localhost");
+    fixture.install(
+        virtual_store()
+            .with_file(".gpg-id", "laptop@localhost\n")
+            .with_file("team/test.gpg", "")
+            .with_file("prod/.gpg-id", format!("laptop@localhost\n{}\n", phone))
+            .with_file("prod/db/test.gpg", "")
+            .with_file("old/.gpg-id", "gone@localhost\n"),
+    );
+    let keys = get_keys(&mut fixture.backend).unwrap();
+
+    let matrix = AccessMatrix::load(&keys, None);
+    let readers = folder_readers(&matrix);
+    let folders: Vec<&str> = readers.iter().map(|(folder, _)| folder.as_str()).collect();
+    assert_eq!(folders, [".", "old", "prod", "prod/db", "team"]);
+    assert_eq!(readers[0].1, vec![laptop.clone()]);
+    assert_eq!(readers[1].1, ["gone@localhost"]);
+    assert_eq!(readers[3].1.len(), 2);
+    assert_eq!(readers[4].1, vec![laptop.clone()]);
+
+    let table = matrix.to_table();
+    let lines: Vec<&str> = table.lines().collect();
+    assert!(lines[0].starts_with("FOLDER   "));
+    assert!(lines[0].contains(&format!("laptop@localhost ({})", &laptop[32..])));
+    assert!(lines[0].contains("gone@localhost (no key)"));
+    assert!(lines[4].starts_with("prod/db"));
+    assert_eq!(lines[4].matches('x').count(), 2);
+
+    let mut matrix = AccessMatrix::load(&keys, None);
+    matrix
+        .filter_who("phone@localhost", &People::default(), &keys)
+        .unwrap();
+    let readers = folder_readers(&matrix);
+    assert_eq!(readers.len(), 2);
+    assert_eq!(readers[0], ("prod".to_string(), vec![phone.clone()]));
+    assert!(matrix
+        .filter_who("nobody@localhost", &People::default(), &keys)
+        .is_err());
+
+    let matrix = AccessMatrix::load(&keys, Some("prod/"));
+    let readers = folder_readers(&matrix);
+    assert_eq!(readers.len(), 2);
+    assert_eq!(readers[1].0, "prod/db");
+}
+
+#[test]
+fn test_fake_access_cross_check() {
+    let mut fixture = Fixture::new();
+    let laptop = fixture.laptop.clone();
+    let phone = fixture.key("phone@localhost");
+    let good = fixture.encrypt_for(&[&laptop, &phone], "good\n");
+    let stale = fixture.encrypt_for(&[&laptop], "stale\n");
+    let leaked = fixture.encrypt_for(&[&phone], "leaked\n");
+    fixture.install(
+        virtual_store()
+            .with_file(".gpg-id", "laptop@localhost\n")
+            .with_file("leaked.gpg", leaked)
+            .with_file("prod/.gpg-id", "laptop@localhost\nphone@localhost\n")
+            .with_file("prod/good.gpg", good)
+            .with_file("prod/stale.gpg", stale),
+    );
+    let keys = get_keys(&mut fixture.backend).unwrap();
+
+    let matrix = AccessMatrix::load(&keys, None);
+    let mismatches = access::cross_check(&mut fixture.backend, &matrix);
+    assert_eq!(
+        access::describe_mismatches(&mismatches),
+        format!(
+            "leaked.gpg not encrypted for: laptop@localhost also encrypted for: {}\n\
+             prod/stale.gpg not encrypted for: phone@localhost\n",
+            phone
+        )
+    );
+    let value: Value = serde_json::from_str(&matrix.to_json(Some(&mismatches))).unwrap();
+    assert_eq!(value["mismatches"][1]["missing"][0], "phone@localhost");
+}
+
+#[test]
+fn test_fake_access_cross_check_without_secret_key() {
+    let mut fixture = Fixture::new();
+    let laptop = fixture.laptop.clone();
+    let mut remote = FakeBackend::new();
+    let server = remote.with_secret_key("server@localhost");
+    let public = remote.export(&[&server], ExportFilter::Full).unwrap();
+    fixture.backend.import(&public).unwrap();
+    let unreadable = fixture.encrypt_for(&[&server], "unreadable\n");
+    fixture.install(
+        virtual_store()
+            .with_file(".gpg-id", "laptop@localhost\n")
+            .with_file("unreadable.gpg", unreadable),
+    );
+    let keys = get_keys(&mut fixture.backend).unwrap();
+
+    let matrix = AccessMatrix::load(&keys, None);
+    let mismatches = access::cross_check(&mut fixture.backend, &matrix);
+    assert_eq!(
+        access::describe_mismatches(&mismatches),
+        format!(
+            "unreadable.gpg not encrypted for: laptop@localhost also encrypted for: {}\n",
+            server
+        )
+    );
+    assert!(fixture.backend.get_secret_key(&laptop).is_some());
+}
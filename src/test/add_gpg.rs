@@ -1,16 +1,22 @@
 use super::clean_up_scenario;
+use super::encrypt_for;
 use super::get_recipients;
+use super::get_virtual_recipients;
 use super::set_up;
+use super::set_up_virtual;
+use super::virtual_store;
+use crate::key::backend::FakeBackend;
+use crate::key::backend::KeyBackend;
 
 #[test]
 pub fn test_add_default_gpg() {
     let scenario_name = "add_gpg_default";
-    let mut context = set_up(scenario_name);
+    let mut backend = set_up(scenario_name);
     let test_file = "test.gpg";
     let default_key_name = "126DF511181C21E94E688C44AA8A6BE01EB30743";
     let default_key_encrypt_id = "9C722DBE12764E6A";
 
-    let initial_recipients = get_recipients(&mut context, scenario_name, test_file);
+    let initial_recipients = get_recipients(&mut backend, scenario_name, test_file);
     if initial_recipients.is_none() {
         panic!("Unable to decode test file");
     }
@@ -21,9 +27,9 @@ pub fn test_add_default_gpg() {
     }
     let conf = crate::config::Config::new(default_key_name.to_string());
     let empty_gpgs = Vec::new();
-    crate::add_gpgs(&empty_gpgs, &mut context, &conf);
+    crate::add_gpgs(None, &empty_gpgs, &mut backend, &conf).unwrap();
 
-    let final_recipients = get_recipients(&mut context, scenario_name, test_file);
+    let final_recipients = get_recipients(&mut backend, scenario_name, test_file);
     if final_recipients.is_none() {
         panic!("Unable to decode test file");
     }
@@ -38,13 +44,13 @@ pub fn test_add_default_gpg() {
 #[test]
 pub fn test_add_tld_gpgs() {
     let scenario_name = "add_gpg_tld";
-    let mut context = set_up(scenario_name);
+    let mut backend = set_up(scenario_name);
     let test_file = "test.gpg";
     let default_key_name = "126DF511181C21E94E688C44AA8A6BE01EB30743";
     let new_key1 = "13F785075D7EBE21";
     let new_key2 = "BF2DF468D5540F34";
 
-    let initial_recipients = get_recipients(&mut context, scenario_name, test_file);
+    let initial_recipients = get_recipients(&mut backend, scenario_name, test_file);
     if initial_recipients.is_none() {
         panic!("Unable to decode test file");
     }
@@ -62,9 +68,9 @@ pub fn test_add_tld_gpgs() {
         "temp1@localhost".to_string(),
         "signer@pubkey.localhost".to_string(),
     ];
-    crate::add_gpgs(&new_gpgs, &mut context, &conf);
+    crate::add_gpgs(None, &new_gpgs, &mut backend, &conf).unwrap();
 
-    let final_recipients = get_recipients(&mut context, scenario_name, test_file);
+    let final_recipients = get_recipients(&mut backend, scenario_name, test_file);
     if final_recipients.is_none() {
         panic!("Unable to decode test file");
     }
@@ -82,13 +88,13 @@ pub fn test_add_tld_gpgs() {
 #[test]
 pub fn test_add_subdir_gpgs() {
     let scenario_name = "add_gpg_subdir";
-    let mut context = set_up(scenario_name);
+    let mut backend = set_up(scenario_name);
     let test_file = "subdir/test.gpg";
     let default_key_name = "126DF511181C21E94E688C44AA8A6BE01EB30743";
     let new_key1 = "13F785075D7EBE21";
     let new_key2 = "BF2DF468D5540F34";
 
-    let initial_recipients = get_recipients(&mut context, scenario_name, test_file);
+    let initial_recipients = get_recipients(&mut backend, scenario_name, test_file);
     if initial_recipients.is_none() {
         panic!("Unable to decode test file");
     }
@@ -103,14 +109,12 @@ pub fn test_add_subdir_gpgs() {
 
     let conf = crate::config::Config::new(default_key_name.to_string());
     let new_gpgs = vec![
-        "-p".to_string(),
-        "subdir".to_string(),
         "temp1@localhost".to_string(),
         "signer@pubkey.localhost".to_string(),
     ];
-    crate::add_gpgs(&new_gpgs, &mut context, &conf);
+    crate::add_gpgs(Some("subdir".to_string()), &new_gpgs, &mut backend, &conf).unwrap();
 
-    let final_recipients = get_recipients(&mut context, scenario_name, test_file);
+    let final_recipients = get_recipients(&mut backend, scenario_name, test_file);
     if final_recipients.is_none() {
         panic!("Unable to decode test file");
     }
@@ -124,3 +128,80 @@ pub fn test_add_subdir_gpgs() {
     }
     clean_up_scenario(scenario_name);
 }
+
+#[test]
+pub fn test_fake_add_default_gpg() {
+    let mut backend = FakeBackend::new();
+    let laptop = backend.with_secret_key("laptop@localhost");
+    let phone = backend.with_secret_key("phone@localhost");
+    let secret = encrypt_for(&mut backend, &["phone@localhost"], "hunter2\n");
+    let store = set_up_virtual(
+        virtual_store()
+            .with_file(".gpg-id", "phone@localhost\n")
+            .with_file("test.gpg", secret),
+        &backend,
+    );
+
+    let conf = crate::config::Config::new(laptop.clone());
+    crate::add_gpgs(None, &Vec::new(), &mut backend, &conf).unwrap();
+
+    let recipients = get_virtual_recipients(&mut backend, &store, "test.gpg").unwrap();
+    assert!(recipients.contains(&laptop[24..]));
+    assert!(recipients.contains(&phone[24..]));
+    let decrypted = backend.decrypt(&store.file("test.gpg").unwrap()).unwrap();
+    assert_eq!(decrypted.get_plaintext(), b"hunter2\n");
+}
+
+#[test]
+pub fn test_fake_add_subdir_gpgs() {
+    let mut backend = FakeBackend::new();
+    let laptop = backend.with_secret_key("laptop@localhost");
+    let phone = backend.with_secret_key("phone@localhost");
+    let top = encrypt_for(&mut backend, &["laptop@localhost"], "top\n");
+    let sub = encrypt_for(&mut backend, &["laptop@localhost"], "sub\n");
+    let store = set_up_virtual(
+        virtual_store()
+            .with_file(".gpg-id", "laptop@localhost\n")
+            .with_file("test.gpg", top.clone())
+            .with_file("subdir/.gpg-id", "laptop@localhost\n")
+            .with_file("subdir/test.gpg", sub),
+        &backend,
+    );
+
+    let conf = crate::config::Config::new(laptop);
+    let new_gpgs = vec!["phone@localhost".to_string()];
+    crate::add_gpgs(Some("subdir".to_string()), &new_gpgs, &mut backend, &conf).unwrap();
+
+    assert_eq!(store.file("test.gpg"), Some(top));
+    let recipients = get_virtual_recipients(&mut backend, &store, "subdir/test.gpg").unwrap();
+    assert!(recipients.contains(&phone[24..]));
+}
+
+#[test]
+fn test_fake_add_gpgs_tampered_parent() {
+    let mut backend = FakeBackend::new();
+    let laptop = backend.with_secret_key("laptop@localhost");
+    backend.with_secret_key("phone@localhost");
+    backend.with_secret_key("mallory@localhost");
+    let store = set_up_virtual(
+        virtual_store()
+            .with_file(".gpg-id", "laptop@localhost\n")
+            .with_file("subdir/test.gpg", ""),
+        &backend,
+    );
+    let conf = crate::config::Config::new(laptop.clone()).with_signing_keys(vec![laptop]);
+    let pass_dir = crate::key::get_pass_dir();
+    crate::key::gpg_id::sign_gpg_id_file(&pass_dir, &conf).unwrap();
+    crate::store::write(
+        &pass_dir.join(".gpg-id"),
+        "laptop@localhost\nmallory@localhost\n",
+    )
+    .unwrap();
+
+    let new_gpgs = vec!["phone@localhost".to_string()];
+    let err =
+        crate::add_gpgs(Some("subdir".to_string()), &new_gpgs, &mut backend, &conf).unwrap_err();
+    assert!(err.contains("Refusing to edit an unverified .gpg-id"));
+    assert!(store.file("subdir/.gpg-id").is_none());
+    assert!(store.file("subdir/.gpg-id.sig").is_none());
+}
@@ -0,0 +1,145 @@
+use super::script_answers;
+use super::virtual_store;
+use super::Fixture;
+use crate::audit;
+use crate::config::Config;
+use crate::key::backend::ExportFilter;
+use crate::key::backend::FakeBackend;
+use crate::key::backend::KeyBackend;
+use crate::store::MemoryStore;
+use serde_json::Value;
+use std::path::Path;
+
+fn audited_config(default_key: &str) -> Config {
+    let contents = format!(
+        "default-key = \"{}\"\n[audit]\nlog = \"store\"\n",
+        default_key
+    );
+    Config::from_toml(&contents, None, None).ok().unwrap()
+}
+
+fn log_entries(store: &MemoryStore) -> Vec<Value> {
+    let log = store.file(audit::STORE_LOG_PATH).unwrap();
+    String::from_utf8(log)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_audit_records_signing() {
+    let mut remote = FakeBackend::new();
+    let laptop = remote.with_secret_key("laptop@localhost");
+    let phone = remote.with_secret_key("phone@localhost");
+    remote.add_signer(&laptop).unwrap();
+    remote.certify(&phone).unwrap();
+    let phone_file = remote.export(&[&phone], ExportFilter::Full).unwrap();
+
+    let mut fixture = Fixture::new();
+    fixture.config = audited_config(&fixture.laptop);
+    let store =
+        fixture.install(virtual_store().with_file(&format!(".keys/{}.asc", phone), phone_file));
+
+    // Answered before the import starts, so it's not part of its entry
+    script_answers(&["No", ""]);
+    crate::prompt::menu("Unaudited question?", &["Yes", "No"], Some(0));
+    crate::check_keys_to_import(&mut fixture.backend, &fixture.config);
+
+    let entries = log_entries(&store);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["seq"], 1);
+    assert_eq!(entries[0]["action"], "sign-key");
+    assert_eq!(entries[0]["device"], laptop.as_str());
+    assert_eq!(entries[0]["target"], phone.as_str());
+    assert_eq!(entries[0]["identity"], "phone@localhost");
+    assert_eq!(entries[0]["trusted-by"][0], laptop.as_str());
+    assert_eq!(
+        entries[0]["answers"][0]["question"],
+        "Would you like to sign the key?"
+    );
+    assert_eq!(entries[0]["answers"][0]["answer"], "Yes");
+    assert_eq!(entries[0]["answers"].as_array().unwrap().len(), 1);
+
+    let log_path = Path::new("/store").join(audit::STORE_LOG_PATH);
+    let trusted = audit::trusted_devices(&mut fixture.backend, &fixture.config);
+    let verified = audit::verify_log(&mut fixture.backend, &log_path, &trusted).unwrap();
+    assert!(verified[0].get_error().is_none());
+    assert!(audit::describe(&verified[0]).contains("sign-key <phone@localhost>"));
+}
+
+#[test]
+fn test_audit_detects_tampering() {
+    let mut fixture = Fixture::new();
+    fixture.config = audited_config(&fixture.laptop);
+    fixture.key("phone@localhost");
+    let secret = fixture.encrypt_for(&["laptop@localhost"], "hunter2\n");
+    let store = fixture.install(
+        virtual_store()
+            .with_file(".gpg-id", "laptop@localhost\n")
+            .with_file("test.gpg", secret),
+    );
+
+    let new_gpgs = vec!["phone@localhost".to_string()];
+    crate::add_gpgs(None, &new_gpgs, &mut fixture.backend, &fixture.config).unwrap();
+
+    let entries = log_entries(&store);
+    let actions: Vec<&str> = entries
+        .iter()
+        .map(|entry| entry["action"].as_str().unwrap())
+        .collect();
+    assert_eq!(actions, vec!["add-recipient", "reencrypt"]);
+    assert_eq!(entries[0]["identity"], "phone@localhost");
+    assert_eq!(entries[1]["paths"][0], ".");
+
+    let log_path = Path::new("/store").join(audit::STORE_LOG_PATH);
+    let trusted = audit::trusted_devices(&mut fixture.backend, &fixture.config);
+    let verified = audit::verify_log(&mut fixture.backend, &log_path, &trusted).unwrap();
+    assert!(verified.iter().all(|entry| entry.get_error().is_none()));
+
+    let log = String::from_utf8(store.file(audit::STORE_LOG_PATH).unwrap()).unwrap();
+    let forged = log.replace("phone@localhost", "attacker@localhost");
+    crate::store::write(&log_path, forged).unwrap();
+    let verified = audit::verify_log(&mut fixture.backend, &log_path, &trusted).unwrap();
+    assert!(verified[0].get_error().unwrap().contains("signature"));
+    assert_eq!(
+        verified[1].get_error(),
+        Some("entry doesn't follow the one before it")
+    );
+
+    crate::store::write(&log_path, log.clone()).unwrap();
+    let untrusted = audit::verify_log(&mut fixture.backend, &log_path, &Default::default());
+    assert_eq!(
+        untrusted.unwrap()[0].get_error().unwrap(),
+        format!("{} isn't a trusted device", fixture.laptop)
+    );
+}
+
+#[test]
+fn test_audit_detects_truncation() {
+    let mut fixture = Fixture::new();
+    fixture.config = audited_config(&fixture.laptop);
+    let store = fixture.install(virtual_store());
+    for path in ["a", "b", "c"] {
+        audit::record(&fixture.config, audit::Event::new("reencrypt").path(path)).unwrap();
+    }
+    let log_path = Path::new("/store").join(audit::STORE_LOG_PATH);
+    let trusted = audit::trusted_devices(&mut fixture.backend, &fixture.config);
+    let log = String::from_utf8(store.file(audit::STORE_LOG_PATH).unwrap()).unwrap();
+    let lines: Vec<&str> = log.lines().collect();
+
+    crate::store::write(&log_path, lines[1..].join("\n")).unwrap();
+    let verified = audit::verify_log(&mut fixture.backend, &log_path, &trusted).unwrap();
+    assert_eq!(
+        verified[0].get_error(),
+        Some("entry follows one that's missing from the log")
+    );
+
+    crate::store::write(&log_path, [lines[0], lines[2]].join("\n")).unwrap();
+    let verified = audit::verify_log(&mut fixture.backend, &log_path, &trusted).unwrap();
+    assert!(verified[0].get_error().is_none());
+    assert_eq!(
+        verified[1].get_error(),
+        Some("entry doesn't follow the one before it")
+    );
+}
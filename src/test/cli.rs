@@ -0,0 +1,69 @@
+use crate::cli;
+
+fn args(raw: &[&str]) -> Vec<String> {
+    raw.iter().map(|arg| arg.to_string()).collect()
+}
+
+#[test]
+fn test_parse_default_command() {
+    let matches = cli::parse_args(&args(&["--store", "/tmp/store", "-y"])).unwrap();
+    assert_eq!(matches.command(), "sync");
+    assert_eq!(matches.value("store"), Some("/tmp/store"));
+    assert!(matches.has("yes"));
+    assert!(matches.positionals().is_empty());
+}
+
+#[test]
+fn test_parse_command_flags() {
+    let matches = cli::parse_args(&args(&[
+        "gpg-add",
+        "user1@localhost",
+        "-p",
+        "subdir",
+        "--format=json",
+        "user2@localhost",
+    ]))
+    .unwrap();
+    assert_eq!(matches.command(), "gpg-add");
+    assert_eq!(matches.value("path"), Some("subdir"));
+    assert_eq!(matches.value("format"), Some("json"));
+    assert_eq!(
+        matches.positionals(),
+        &["user1@localhost".to_string(), "user2@localhost".to_string()]
+    );
+
+    let matches = cli::parse_args(&args(&["gpg-add", "--", "-p"])).unwrap();
+    assert_eq!(matches.value("path"), None);
+    assert_eq!(matches.positional(0), Some("-p"));
+}
+
+#[test]
+fn test_parse_errors() {
+    assert!(cli::parse_args(&args(&["frobnicate"])).is_err());
+    assert!(cli::parse_args(&args(&["sync", "--path", "subdir"])).is_err());
+    assert!(cli::parse_args(&args(&["gpg-add", "--path"])).is_err());
+    assert!(cli::parse_args(&args(&["graph", "--format", "yaml"])).is_err());
+    assert!(cli::parse_args(&args(&["graph", "one"])).is_err());
+    assert!(cli::parse_args(&args(&["config", "set", "a", "b", "c"])).is_err());
+    assert!(cli::parse_args(&args(&["completions"])).is_err());
+    assert!(cli::parse_args(&args(&["--yes=no"])).is_err());
+    assert!(cli::parse_args(&args(&["--prompter", "maybe"])).is_err());
+}
+
+#[test]
+fn test_usage_and_completions() {
+    let usage = cli::usage(None).unwrap();
+    for spec in cli::COMMANDS {
+        assert!(usage.contains(spec.name));
+    }
+    assert!(cli::usage(Some("gpg-add")).unwrap().contains("--path"));
+    assert!(cli::usage(Some("frobnicate")).is_err());
+
+    let bash = cli::completions("bash").unwrap();
+    assert!(bash.contains("__password_store_extension_complete_provision"));
+    assert!(cli::completions("zsh").unwrap().starts_with("#compdef"));
+    assert!(cli::completions("fish")
+        .unwrap()
+        .contains("__fish_pass_uses_command provision"));
+    assert!(cli::completions("tcsh").is_err());
+}
@@ -0,0 +1,92 @@
+use crate::command;
+use crate::command::CommandError;
+use crate::command::CommandOutput;
+use crate::command::FakeRunner;
+use crate::command::Invocation;
+use crate::git;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+#[test]
+fn test_command_captures_output() {
+    let output = command::output_command("sh", &["-c", "echo provisioned"]);
+    assert_eq!(output.unwrap(), b"provisioned\n");
+}
+
+#[test]
+fn test_command_error_has_exit_code_and_stderr() {
+    let res = command::oneshot_command("sh", &["-c", "echo 'no .gpg-id found' >&2; exit 3"]);
+    let err = res.unwrap_err();
+    match &err {
+        CommandError::Failed { code, stderr, .. } => {
+            assert_eq!(*code, Some(3));
+            assert_eq!(stderr.trim(), "no .gpg-id found");
+        }
+        _ => panic!("unexpected error: {}", err),
+    }
+    let message = err.to_string();
+    assert!(message.contains("exited with status: 3"));
+    assert!(message.contains("no .gpg-id found"));
+}
+
+#[test]
+fn test_command_timeout() {
+    let invocation = Invocation::new("sleep", &["5"]).timeout(Duration::from_millis(100));
+    let err = command::run(&invocation).unwrap_err();
+    assert!(matches!(err, CommandError::TimedOut { .. }));
+}
+
+#[test]
+fn test_command_timeout_kills_spawned_processes() {
+    let started = Instant::now();
+    let invocation =
+        Invocation::new("sh", &["-c", "sleep 5 | cat"]).timeout(Duration::from_millis(100));
+    let err = command::run(&invocation).unwrap_err();
+    assert!(matches!(err, CommandError::TimedOut { .. }));
+    assert!(started.elapsed() < Duration::from_secs(3));
+}
+
+#[test]
+fn test_command_missing_binary() {
+    let err = command::oneshot_command("pass-provision-missing-binary", &[]).unwrap_err();
+    assert!(matches!(err, CommandError::Spawn { .. }));
+}
+
+#[test]
+fn test_fake_runner() {
+    let runner = Arc::new(FakeRunner::new(|invocation| {
+        if invocation.command_line().contains(" commit ") {
+            return Ok(CommandOutput::new(
+                Some(128),
+                Vec::new(),
+                b"Author identity unknown\n".to_vec(),
+            ));
+        }
+        Ok(CommandOutput::new(Some(0), Vec::new(), Vec::new()))
+    }));
+    command::set_runner(runner.clone());
+
+    let res = git::init_repo(Path::new("/store"), "Initialize store");
+    let err = res.unwrap_err();
+    assert!(err.contains("exited with status: 128"));
+    assert!(err.contains("Author identity unknown"));
+
+    let calls: Vec<String> = runner
+        .calls()
+        .iter()
+        .map(Invocation::command_line)
+        .collect();
+    assert_eq!(
+        calls,
+        vec![
+            "git -C /store init -q",
+            "git -C /store add -A",
+            "git -C /store commit -q -m Initialize store",
+        ]
+    );
+
+    command::set_runner(Arc::new(FakeRunner::succeeding()));
+    assert!(git::init_repo(Path::new("/store"), "Initialize store").is_ok());
+}
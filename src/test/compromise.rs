@@ -0,0 +1,259 @@
+use super::get_virtual_recipients;
+use super::script_answers;
+use super::virtual_store;
+use super::Fixture;
+use crate::command;
+use crate::command::CommandOutput;
+use crate::command::FakeRunner;
+use crate::config::Config;
+use crate::key::backend::FakeBackend;
+use crate::key::backend::KeyBackend;
+use crate::key::compromise;
+use crate::key::get_keys;
+use crate::key::get_pass_dir;
+use crate::key::gpg_id;
+use crate::store::MemoryStore;
+use std::sync::Arc;
+
+fn read(store: &MemoryStore, path: &str) -> String {
+    String::from_utf8(store.file(path).unwrap()).unwrap()
+}
+
+#[test]
+fn test_fake_compromise_removes_key() {
+    let mut fixture = Fixture::for_device("Laptop", "alice@localhost");
+    let laptop = fixture.laptop.clone();
+    let phone = fixture.device("Phone", "alice@localhost");
+    fixture.key("bob@localhost");
+    let store = fixture.install(
+        virtual_store()
+            .with_file(".gpg-id", "alice@localhost\nbob@localhost\n")
+            .with_file("a.gpg", "")
+            .with_file("prod/.gpg-id", "bob@localhost\n")
+            .with_file("prod/b.gpg", "")
+            .with_file(
+                "team/.gpg-id",
+                format!("#@alice: {} {}\n{}\n{}\n", laptop, phone, laptop, phone),
+            )
+            .with_file("team/c.gpg", "")
+            .with_file("team/sub/d.gpg", ""),
+    );
+    let keys = get_keys(&mut fixture.backend).unwrap();
+
+    assert_eq!(
+        compromise::readable_paths(&phone, &keys),
+        ["a.gpg", "team/c.gpg", "team/sub/d.gpg"]
+    );
+
+    let changes = compromise::remove_from_gpg_ids(&phone, &keys).unwrap();
+    let folders: Vec<&str> = changes.iter().map(|change| change.get_folder()).collect();
+    assert_eq!(folders, [".", "team"]);
+    for change in &changes {
+        change.write(&fixture.config);
+    }
+    assert_eq!(
+        read(&store, ".gpg-id"),
+        format!("{}\nbob@localhost\n", laptop)
+    );
+    assert_eq!(
+        read(&store, "team/.gpg-id"),
+        format!("#@alice: {}\n{}\n", laptop, laptop)
+    );
+    assert_eq!(read(&store, "prod/.gpg-id"), "bob@localhost\n");
+    assert!(compromise::readable_paths(&phone, &keys).is_empty());
+}
+
+#[test]
+fn test_fake_compromise_list() {
+    let mut fixture = Fixture::new();
+    let laptop = fixture.laptop.clone();
+    let phone = fixture.key("phone@localhost");
+    let store = fixture.install(
+        virtual_store()
+            .with_file(".gpg-id", "laptop@localhost\n")
+            .with_file("phone/.gpg-id", "phone@localhost\n"),
+    );
+    let keys = get_keys(&mut fixture.backend).unwrap();
+
+    assert!(compromise::load_compromised(&fixture.config)
+        .unwrap()
+        .is_empty());
+    assert!(compromise::mark_compromised(&phone, &fixture.config).unwrap());
+    assert!(!compromise::mark_compromised(&phone.to_lowercase(), &fixture.config).unwrap());
+    assert!(compromise::mark_compromised(&laptop, &fixture.config).unwrap());
+    assert_eq!(
+        compromise::load_compromised(&fixture.config).unwrap(),
+        vec![phone.clone(), laptop.clone()]
+    );
+    assert!(read(&store, compromise::COMPROMISED_FILE_PATH).starts_with("# Keys reported"));
+
+    let err = compromise::remove_from_gpg_ids(&phone, &keys)
+        .err()
+        .unwrap();
+    assert!(err.starts_with("phone/.gpg-id would have no recipients left"));
+}
+
+#[test]
+fn test_fake_compromise_list_is_signed() {
+    let mut fixture = Fixture::new();
+    let laptop = fixture.laptop.clone();
+    let phone = fixture.key("phone@localhost");
+    fixture.config = fixture.config.with_signing_keys(vec![laptop]);
+    let store = fixture.install(virtual_store().with_file(".gpg-id", "laptop@localhost\n"));
+
+    assert!(compromise::mark_compromised(&phone, &fixture.config).unwrap());
+    assert!(store
+        .file(&format!("{}.sig", compromise::COMPROMISED_FILE_PATH))
+        .is_some());
+    assert_eq!(
+        compromise::load_compromised(&fixture.config).unwrap(),
+        vec![phone]
+    );
+
+    crate::store::write(
+        &get_pass_dir().join(compromise::COMPROMISED_FILE_PATH),
+        "compromised = []\n",
+    )
+    .unwrap();
+    let err = compromise::load_compromised(&fixture.config).unwrap_err();
+    assert!(err.contains("Refusing to use an unverified revocation list"));
+}
+
+#[test]
+fn test_fake_compromise_commits_touched_paths() {
+    let mut fixture = Fixture::new();
+    let laptop = fixture.laptop.clone();
+    let phone = fixture.key("phone@localhost");
+    let contents = format!("default-key = \"{}\"\n[git]\ncommit = true\n", laptop);
+    fixture.config = Config::from_toml(&contents, None, None)
+        .unwrap()
+        .with_signing_keys(vec![laptop.clone()]);
+    let shared = fixture.encrypt_for(&[&laptop, &phone], "shared\n");
+    let prod = fixture.encrypt_for(&[&laptop], "prod\n");
+    let store = fixture.install(
+        virtual_store()
+            .with_file(".git/HEAD", "")
+            .with_file(".gpg-id", "laptop@localhost\nphone@localhost\n")
+            .with_file("a.gpg", shared)
+            .with_file("prod/.gpg-id", "laptop@localhost\n")
+            .with_file("prod/b.gpg", prod)
+            .with_file("notes.txt", "")
+            .with_file(&format!(".keys/{}.asc", phone), ""),
+    );
+    for dir in [get_pass_dir(), get_pass_dir().join("prod")] {
+        gpg_id::sign_gpg_id_file(&dir, &fixture.config).unwrap();
+    }
+    let runner = Arc::new(FakeRunner::new(|invocation| {
+        let stdout = if invocation.command_line().contains(" status ") {
+            b" M a.gpg\n".to_vec()
+        } else {
+            Vec::new()
+        };
+        Ok(CommandOutput::new(Some(0), stdout, Vec::new()))
+    }));
+    command::set_runner(runner.clone());
+    let keys = get_keys(&mut fixture.backend).unwrap();
+
+    script_answers(&["Yes"]);
+    crate::compromise_cmd(&phone, None, &keys, &mut fixture.backend, &fixture.config);
+
+    let recipients = get_virtual_recipients(&mut fixture.backend, &store, "a.gpg").unwrap();
+    assert!(!recipients.contains(&phone[24..]));
+    let add = runner
+        .calls()
+        .into_iter()
+        .map(|call| call.command_line())
+        .find(|line| line.contains(" add "))
+        .unwrap();
+    let mut paths: Vec<&str> = add.split(" -- ").nth(1).unwrap().split(' ').collect();
+    paths.sort();
+    let mut expected = vec![
+        ".gpg-id".to_string(),
+        ".gpg-id.sig".to_string(),
+        ".keys/compromised.toml".to_string(),
+        ".keys/compromised.toml.sig".to_string(),
+        format!(".keys/quarantine/{}.asc", phone),
+        format!(".keys/quarantine/{}.reason", phone),
+        format!(".keys/{}.asc", phone),
+        "a.gpg".to_string(),
+        "prod/b.gpg".to_string(),
+    ];
+    expected.sort();
+    assert_eq!(paths, expected);
+}
+
+fn revocation_for(fingerprint: &str, extra: &str) -> String {
+    format!(
+        "-----BEGIN FAKE PGP PUBLIC KEY BLOCK-----\n\
+         key {} phone@localhost revoked\n{}\
+         -----END FAKE PGP PUBLIC KEY BLOCK-----\n",
+        fingerprint, extra
+    )
+}
+
+#[test]
+fn test_fake_compromise_checks_revocation() {
+    let mut fixture = Fixture::new();
+    let laptop = fixture.laptop.clone();
+    let phone = fixture.key("phone@localhost");
+    fixture.install(virtual_store().with_file(".gpg-id", "laptop@localhost\n"));
+    let check = |backend: &mut FakeBackend, data: String| {
+        compromise::check_revocation(backend, &phone, data.as_bytes())
+    };
+
+    check(&mut fixture.backend, revocation_for(&phone, "")).unwrap();
+    assert!(!fixture.backend.get_key(&phone).unwrap().is_revoked());
+    let err = check(&mut fixture.backend, revocation_for(&laptop, "")).unwrap_err();
+    assert!(err.contains(&format!("is for {}", laptop)));
+    let extra = format!("cert {} good\n", laptop);
+    let err = check(&mut fixture.backend, revocation_for(&phone, &extra)).unwrap_err();
+    assert!(err.contains("brings more than a revocation"));
+    let unrevoked = revocation_for(&phone, "").replace(" revoked", "");
+    let err = check(&mut fixture.backend, unrevoked).unwrap_err();
+    assert!(err.contains("doesn't revoke"));
+
+    fixture
+        .backend
+        .import(revocation_for(&phone, "").as_bytes())
+        .unwrap();
+    let err = check(&mut fixture.backend, revocation_for(&phone, "")).unwrap_err();
+    assert!(err.contains("doesn't revoke"));
+}
+
+#[test]
+fn test_fake_compromise_imports_revocation_once_confirmed() {
+    let mut fixture = Fixture::new();
+    let phone = fixture.key("phone@localhost");
+    let tablet = fixture.key("tablet@localhost");
+    fixture.install(
+        virtual_store()
+            .with_file(".gpg-id", "laptop@localhost\nphone@localhost\n")
+            .with_file("tablet/.gpg-id", "tablet@localhost\n"),
+    );
+    let keys = get_keys(&mut fixture.backend).unwrap();
+    let path = std::env::temp_dir().join(format!("pass-provision-{}.rev", phone));
+    std::fs::write(&path, revocation_for(&phone, "")).unwrap();
+    let path = path.to_str().unwrap();
+
+    script_answers(&["No"]);
+    crate::compromise_cmd(
+        &phone,
+        Some(path),
+        &keys,
+        &mut fixture.backend,
+        &fixture.config,
+    );
+    assert!(!fixture.backend.get_key(&phone).unwrap().is_revoked());
+
+    script_answers(&["Yes"]);
+    crate::compromise_cmd(
+        &phone,
+        Some(path),
+        &keys,
+        &mut fixture.backend,
+        &fixture.config,
+    );
+    assert!(fixture.backend.get_key(&phone).unwrap().is_revoked());
+    assert!(!fixture.backend.get_key(&tablet).unwrap().is_revoked());
+    std::fs::remove_file(path).unwrap();
+}
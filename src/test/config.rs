@@ -0,0 +1,193 @@
+use crate::config::Config;
+use crate::config::ConfigEnv;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+const PROFILE_CONFIG: &str = r#"
+default-key = "126DF511181C21E94E688C44AA8A6BE01EB30743"
+
+[trust]
+threshold = 2
+
+[profile.team]
+store = "/tmp/pass-provision-team-store"
+default-key = "722C5318085E06991A2605710B85B503ABEFD58F"
+gnupghome = "/tmp/pass-provision-team-gnupg"
+
+[profile.team.git]
+commit = true
+push = true
+
+[profile.personal]
+store = "/tmp/pass-provision-personal-store"
+
+[profile.personal.export]
+mode = "minimal"
+"#;
+
+fn write_config(name: &str, contents: &str) -> PathBuf {
+    let config_path = env::temp_dir().join(format!("pass-provision-{}.conf", name));
+    fs::write(&config_path, contents).unwrap();
+    config_path
+}
+
+fn parse_config(config_env: ConfigEnv) -> Config {
+    Config::parse_config(&config_env).unwrap().unwrap()
+}
+
+#[test]
+fn test_config_profiles() {
+    let config_path = write_config("profiles", PROFILE_CONFIG);
+
+    let unknown_store = Path::new("/tmp/pass-provision-unknown-store");
+    let conf = parse_config(ConfigEnv::new(&config_path).store(unknown_store));
+    assert_eq!(conf.get_profile(), None);
+    assert_eq!(
+        conf.get_default_key(),
+        "126DF511181C21E94E688C44AA8A6BE01EB30743"
+    );
+    assert_eq!(conf.get_trust_threshold(), 2);
+    assert!(!conf.get_git_commit());
+
+    let conf = parse_config(
+        ConfigEnv::new(&config_path)
+            .store(unknown_store)
+            .profile("team"),
+    );
+    assert_eq!(conf.get_profile(), Some("team"));
+    assert_eq!(
+        conf.get_default_key(),
+        "722C5318085E06991A2605710B85B503ABEFD58F"
+    );
+    assert_eq!(
+        conf.get_gnupghome().unwrap().to_str(),
+        Some("/tmp/pass-provision-team-gnupg")
+    );
+    assert_eq!(conf.get_trust_threshold(), 2);
+    assert!(conf.get_git_commit());
+    assert!(conf.get_git_push());
+
+    let personal_store = Path::new("/tmp/pass-provision-personal-store");
+    let conf = parse_config(ConfigEnv::new(&config_path).store(personal_store));
+    assert_eq!(conf.get_profile(), Some("personal"));
+    assert_eq!(
+        conf.get_default_key(),
+        "126DF511181C21E94E688C44AA8A6BE01EB30743"
+    );
+    assert_eq!(
+        conf.get_export_mode(),
+        crate::key::export::ExportMode::Minimal
+    );
+
+    let errors = Config::parse_config(&ConfigEnv::new(&config_path).profile("missing"))
+        .err()
+        .unwrap();
+    assert!(errors[0].ends_with("Unknown profile: missing"));
+
+    fs::remove_file(config_path).unwrap();
+}
+
+#[test]
+fn test_config_errors_have_line_numbers() {
+    let contents = "default-key = \"126DF511181C21E94E688C44AA8A6BE01EB30743\"\n\
+                    \n\
+                    [trust]\n\
+                    threshold = \"two\"\n\
+                    treshold = 2\n\
+                    \n\
+                    [git]\n\
+                    commit = 1\n\
+                    \n\
+                    [profile.team.exports]\n\
+                    mode = \"minimal\"\n";
+    let errors: Vec<String> = crate::config::Config::from_toml(contents, None, None)
+        .err()
+        .unwrap()
+        .iter()
+        .map(|err| err.to_string())
+        .collect();
+    assert_eq!(
+        errors,
+        vec![
+            "line 4: trust.threshold: must be a positive integer".to_string(),
+            "line 5: trust.treshold: unknown key".to_string(),
+            "line 8: git.commit: must be true or false".to_string(),
+            "line 10: profile.team.exports: unknown table".to_string(),
+        ]
+    );
+
+    let errors = crate::config::Config::from_toml("default-key = [\n", None, None)
+        .err()
+        .unwrap();
+    assert_eq!(errors.len(), 1);
+
+    let errors = crate::config::Config::from_toml("", Some("missing"), None)
+        .err()
+        .unwrap();
+    assert_eq!(errors[0].to_string(), "Unknown profile: missing");
+}
+
+#[test]
+fn test_config_schema_values() {
+    let contents = "default-key = \"126DF511181C21E94E688C44AA8A6BE01EB30743\"\n\
+                    [reencrypt]\n\
+                    jobs = 4\n\
+                    [output]\n\
+                    format = \"json\"\n";
+    let conf = crate::config::Config::from_toml(contents, None, None).unwrap();
+    assert_eq!(conf.get_reencrypt_jobs(), 4);
+    assert_eq!(conf.get_output_format(), Some("json"));
+    assert_eq!(
+        conf.get_setting("reencrypt.jobs")
+            .unwrap()
+            .unwrap()
+            .as_integer(),
+        Some(4)
+    );
+    assert!(conf.get_setting("trust.max-depth").unwrap().is_none());
+    assert!(conf.get_setting("no-such-key").is_err());
+
+    let conf = crate::config::Config::from_toml("", None, None).unwrap();
+    assert_eq!(conf.get_reencrypt_jobs(), 1);
+    assert_eq!(conf.get_output_format(), None);
+    assert_eq!(conf.get_backend(), None);
+
+    let contents = "backend = \"gpgme\"\nkeyring = \"/tmp/pass-provision-keyring\"\n";
+    let conf = crate::config::Config::from_toml(contents, None, None).unwrap();
+    assert_eq!(conf.get_backend(), Some("gpgme"));
+    assert_eq!(
+        conf.get_keyring().unwrap().to_str(),
+        Some("/tmp/pass-provision-keyring")
+    );
+    let errors = crate::config::Config::from_toml("backend = \"gpg2\"\n", None, None)
+        .err()
+        .unwrap();
+    assert!(errors[0]
+        .to_string()
+        .contains("backend: must be one of: gpgme"));
+}
+
+#[test]
+fn test_config_set() {
+    let config_path = write_config(
+        "set",
+        "# device key\ndefault-key = \"126DF511181C21E94E688C44AA8A6BE01EB30743\"\n",
+    );
+    let config_env = ConfigEnv::new(&config_path);
+    crate::config::set_config_value(&config_env, None, "trust.threshold", "3").unwrap();
+    crate::config::set_config_value(&config_env, Some("team"), "git.commit", "true").unwrap();
+    assert!(
+        crate::config::set_config_value(&config_env, None, "trust.threshold", "three").is_err()
+    );
+    assert!(crate::config::set_config_value(&config_env, None, "trust.treshold", "3").is_err());
+
+    let contents = fs::read_to_string(&config_path).unwrap();
+    assert!(contents.starts_with("# device key\n"));
+    let conf = Config::from_toml(&contents, Some("team"), None).unwrap();
+    assert_eq!(conf.get_trust_threshold(), 3);
+    assert!(conf.get_git_commit());
+
+    fs::remove_file(config_path).unwrap();
+}
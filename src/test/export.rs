@@ -0,0 +1,89 @@
+use super::set_up_virtual;
+use super::virtual_store;
+use crate::key::backend::FakeBackend;
+use crate::key::backend::KeyBackend;
+use crate::key::export::export_key;
+use crate::key::export::write_key_file;
+use crate::key::export::ExportMode;
+use crate::key::export::ExportStatus;
+use crate::key::get_pass_dir;
+
+fn certs(exported: Vec<u8>) -> Vec<String> {
+    String::from_utf8(exported)
+        .unwrap()
+        .lines()
+        .filter_map(|line| line.strip_prefix("cert "))
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn test_fake_export_modes() {
+    let mut backend = FakeBackend::new();
+    let laptop = backend.with_secret_key("laptop@localhost");
+    let phone = backend.with_secret_key("phone@localhost");
+    let tablet = backend.with_secret_key("tablet@localhost");
+    let stranger = backend.with_secret_key("stranger@localhost");
+    set_up_virtual(
+        virtual_store()
+            .with_file(&format!(".keys/{}.asc", phone), "")
+            .with_file(&format!(".keys/{}.asc", tablet), ""),
+        &backend,
+    );
+    for signer in [&tablet, &stranger] {
+        let mut signing = backend.clone();
+        signing.add_signer(signer).unwrap();
+        signing.certify(&phone).unwrap();
+    }
+    let from_tablet = format!("{} good", &tablet[24..]);
+    let from_stranger = format!("{} good", &stranger[24..]);
+
+    let full = export_key(&mut backend, &phone, ExportMode::Full).unwrap();
+    assert_eq!(certs(full), [from_tablet.clone(), from_stranger.clone()]);
+
+    let minimal = export_key(&mut backend, &phone, ExportMode::Minimal).unwrap();
+    assert!(certs(minimal).is_empty());
+
+    let known = export_key(&mut backend, &phone, ExportMode::KnownSigners).unwrap();
+    assert_eq!(certs(known), std::slice::from_ref(&from_tablet));
+
+    backend.add_signer(&laptop).unwrap();
+    backend.certify(&phone).unwrap();
+    let known = export_key(&mut backend, &phone, ExportMode::KnownSigners).unwrap();
+    assert_eq!(
+        certs(known),
+        [from_tablet, format!("{} good", &laptop[24..])]
+    );
+}
+
+#[test]
+fn test_fake_write_key_file_status() {
+    let mut backend = FakeBackend::new();
+    let laptop = backend.with_secret_key("laptop@localhost");
+    let phone = backend.with_secret_key("phone@localhost");
+    let published = format!(".keys/{}.asc", phone);
+    let store = set_up_virtual(
+        virtual_store().with_file(&format!(".keys/{}.asc", laptop), ""),
+        &backend,
+    );
+    let path = get_pass_dir().join(&published);
+
+    let status = write_key_file(&mut backend, &phone, ExportMode::Full, &path, false);
+    assert!(matches!(status, Ok(ExportStatus::Created)));
+    let status = write_key_file(&mut backend, &phone, ExportMode::Full, &path, false);
+    assert!(matches!(status, Ok(ExportStatus::Unchanged)));
+
+    backend.add_signer(&laptop).unwrap();
+    backend.certify(&phone).unwrap();
+    let status = write_key_file(&mut backend, &phone, ExportMode::Full, &path, false);
+    assert!(matches!(status, Ok(ExportStatus::Gained(_))));
+    let signed = store.file(&published).unwrap();
+
+    let status = write_key_file(&mut backend, &phone, ExportMode::Minimal, &path, false);
+    assert!(matches!(status, Ok(ExportStatus::Kept(_))));
+    assert_eq!(store.file(&published).unwrap(), signed);
+
+    let status = write_key_file(&mut backend, &phone, ExportMode::Minimal, &path, true);
+    assert!(matches!(status, Ok(ExportStatus::Pruned(_))));
+    assert_ne!(store.file(&published).unwrap(), signed);
+}
@@ -1,5 +1,6 @@
 use super::clean_up_scenario;
 use super::set_up;
+use crate::key::recipients::RecipientResolver;
 use std::collections::HashSet;
 use std::iter::FromIterator;
 use std::path::Path;
@@ -25,7 +26,7 @@ fn test_all_gpgs() {
 fn test_subdir_gpgs() {
     let _ = set_up("all_gpgs");
     let all_gpgs =
-        crate::key::gpg_id::get_base_gpgs_for_dir(&Path::new("testing/all_gpgs_run/pass/subdir"));
+        crate::key::gpg_id::get_base_gpgs_for_dir(Path::new("testing/all_gpgs_run/pass/subdir"));
     let expected_hash_set = HashSet::from_iter(vec!["user4".to_string(), "user5".to_string()]);
     assert_eq!(expected_hash_set, all_gpgs);
     clean_up_scenario("all_gpgs")
@@ -34,22 +35,30 @@ fn test_subdir_gpgs() {
 #[test]
 fn test_get_dir_gpgs_with_file() {
     let _ = set_up("all_gpgs");
-    let sub_gpgs = crate::key::gpg_id::get_gpgs_for_dir(&Path::new("subdir"));
+    let resolved = RecipientResolver::new(&[])
+        .resolve(Path::new("subdir"))
+        .unwrap();
+    let sub_gpgs: HashSet<String> = HashSet::from_iter(resolved.get_gpg_ids().iter().cloned());
     let expected_hash_set = HashSet::from_iter(vec!["user4".to_string(), "user5".to_string()]);
     assert_eq!(expected_hash_set, sub_gpgs);
+    assert_eq!(resolved.get_source(), Some("subdir"));
     clean_up_scenario("all_gpgs")
 }
 
 #[test]
 fn test_get_dir_gpgs_inherit() {
     let _ = set_up("all_gpgs_inherit");
-    let sub_gpgs = crate::key::gpg_id::get_gpgs_for_dir(&Path::new("subdir"));
+    let resolved = RecipientResolver::new(&[])
+        .resolve(Path::new("subdir"))
+        .unwrap();
+    let sub_gpgs: HashSet<String> = HashSet::from_iter(resolved.get_gpg_ids().iter().cloned());
     let expected_hash_set = HashSet::from_iter(vec![
         "user1".to_string(),
         "user2".to_string(),
         "user3".to_string(),
     ]);
     assert_eq!(expected_hash_set, sub_gpgs);
+    assert_eq!(resolved.get_source(), Some("."));
     clean_up_scenario("all_gpgs_inherit")
 }
 
@@ -66,10 +75,31 @@ fn test_write_gpgs() {
         "user7".to_string(),
     ]);
     let base_path = Path::new("testing/all_gpgs_run/pass");
-    let old_gpgs = crate::key::gpg_id::get_base_gpgs_for_dir(&base_path);
+    let old_gpgs = crate::key::gpg_id::get_base_gpgs_for_dir(base_path);
     assert_ne!(old_gpgs, new_hash);
-    crate::key::gpg_id::write_gpg_ids(&base_path, &new_hash);
-    let read_gpgs = crate::key::gpg_id::get_base_gpgs_for_dir(&base_path);
+    let conf = crate::config::Config::new(String::new());
+    crate::key::gpg_id::write_gpg_ids(base_path, &new_hash, &conf);
+    let read_gpgs = crate::key::gpg_id::get_base_gpgs_for_dir(base_path);
     assert_eq!(read_gpgs, new_hash);
     clean_up_scenario("all_gpgs")
 }
+
+#[test]
+fn test_gpg_id_signature() {
+    let _ = set_up("gpg_id_signed");
+    let conf = crate::config::Config::new(String::new())
+        .with_signing_keys(vec!["126DF511181C21E94E688C44AA8A6BE01EB30743".to_string()]);
+    let base_path = Path::new("testing/gpg_id_signed_run/pass");
+    assert!(crate::key::gpg_id::verify_gpg_id_file(base_path, &conf).is_ok());
+
+    std::fs::write(base_path.join(".gpg-id"), "attacker@localhost\n").unwrap();
+    assert!(crate::key::gpg_id::verify_gpg_id_file(base_path, &conf).is_err());
+
+    let new_hash = HashSet::from_iter(vec!["privkeyencode@localhost".to_string()]);
+    crate::key::gpg_id::write_gpg_ids(base_path, &new_hash, &conf);
+    assert!(crate::key::gpg_id::verify_gpg_id_file(base_path, &conf).is_ok());
+
+    std::fs::remove_file(base_path.join(".gpg-id.sig")).unwrap();
+    assert!(crate::key::gpg_id::verify_gpg_id_file(base_path, &conf).is_err());
+    clean_up_scenario("gpg_id_signed")
+}
@@ -0,0 +1,98 @@
+use crate::key::graph::TrustGraph;
+
+const DEFAULT_KEY: &str = "126DF511181C21E94E688C44AA8A6BE01EB30743";
+const NEWKEY1: &str = "A849644DA452281D2EB637EA9FEBCD1F791BC6B9";
+const NEWKEY2: &str = "D54BA99B7CD92448901306F46AD31A01C5E25FA2";
+const STRANGER: &str = "57B9D3C2978F4273D0E4E7D3E5EE8B8496C528EF";
+
+fn build_graph() -> TrustGraph {
+    let mut graph = TrustGraph::new();
+    graph.add_node(DEFAULT_KEY, "test@localhost");
+    graph.add_node(NEWKEY1, "temp1@localhost");
+    graph.add_node(NEWKEY2, "temp2@localhost");
+    graph.add_node(STRANGER, "stranger@localhost");
+    graph.add_edge(DEFAULT_KEY, NEWKEY1);
+    graph.add_edge(DEFAULT_KEY, NEWKEY2);
+    graph.add_edge(NEWKEY1, NEWKEY2);
+    graph.add_edge(STRANGER, STRANGER);
+    graph
+}
+
+#[test]
+fn test_graph_reachability() {
+    let graph = build_graph();
+    let reachable = graph.reachable_from(DEFAULT_KEY);
+    assert!(reachable.contains(DEFAULT_KEY));
+    assert!(reachable.contains(NEWKEY1));
+    assert!(reachable.contains(NEWKEY2));
+    assert!(!reachable.contains(STRANGER));
+
+    assert_eq!(graph.signers_of(NEWKEY1), vec![DEFAULT_KEY]);
+    assert_eq!(graph.signers_of(NEWKEY2).len(), 2);
+    assert!(graph.signers_of(STRANGER).is_empty());
+    assert_eq!(
+        graph.find_fingerprint("6AD31A01C5E25FA2"),
+        Some(NEWKEY2.to_string())
+    );
+}
+
+#[test]
+fn test_graph_dot_highlights() {
+    let dot = build_graph().to_dot(DEFAULT_KEY);
+    assert!(dot.starts_with("digraph trust {"));
+    assert!(dot.contains(&format!("\"{}\" -> \"{}\";", DEFAULT_KEY, NEWKEY1)));
+    let stranger_line = dot.lines().find(|line| line.contains(STRANGER)).unwrap();
+    assert!(stranger_line.contains("color=red"));
+    let newkey1_line = dot
+        .lines()
+        .find(|line| line.trim_start().starts_with(&format!("\"{}\" [", NEWKEY1)))
+        .unwrap();
+    assert!(newkey1_line.contains("color=orange"));
+}
+
+#[test]
+fn test_graph_json() {
+    let json: serde_json::Value =
+        serde_json::from_str(&build_graph().to_json(DEFAULT_KEY)).unwrap();
+    assert_eq!(json["default_key"], DEFAULT_KEY);
+    assert_eq!(json["edges"].as_array().unwrap().len(), 3);
+    for node in json["nodes"].as_array().unwrap() {
+        if node["fingerprint"] == STRANGER {
+            assert_eq!(node["reachable"], false);
+        }
+        if node["fingerprint"] == NEWKEY1 {
+            assert_eq!(node["single_signer"], true);
+        }
+    }
+}
+
+#[test]
+fn test_graph_trusted_signers() {
+    let mut graph = build_graph();
+    let candidate = "E127C35D3FCDE55F4DFFB64F95DB537600D07C30";
+    graph.add_node(candidate, "candidate@localhost");
+    graph.add_edge(NEWKEY2, candidate);
+    graph.add_edge(STRANGER, candidate);
+
+    let paths = graph.trusted_signers(DEFAULT_KEY, candidate, None);
+    assert_eq!(paths.len(), 1);
+    assert_eq!(
+        paths[0],
+        vec![
+            DEFAULT_KEY.to_string(),
+            NEWKEY2.to_string(),
+            candidate.to_string()
+        ]
+    );
+    assert!(graph
+        .trusted_signers(DEFAULT_KEY, candidate, Some(1))
+        .is_empty());
+
+    graph.add_edge(NEWKEY1, candidate);
+    let paths = graph.trusted_signers(DEFAULT_KEY, candidate, Some(2));
+    assert_eq!(paths.len(), 2);
+
+    graph.add_edge(candidate, STRANGER);
+    let paths = graph.trusted_signers(DEFAULT_KEY, candidate, None);
+    assert_eq!(paths.len(), 2);
+}
@@ -0,0 +1,162 @@
+use super::get_virtual_recipients;
+use super::script_answers;
+use super::virtual_store;
+use super::with_device;
+use super::Fixture;
+use crate::key::backend::ExportFilter;
+use crate::key::backend::FakeBackend;
+use crate::key::backend::KeyBackend;
+use crate::key::get_keys;
+use crate::key::group::People;
+use std::collections::BTreeSet;
+
+#[test]
+fn test_people_expand() {
+    let mut backend = FakeBackend::new();
+    let laptop = with_device(&mut backend, "Laptop", "alice@localhost");
+    let phone = with_device(&mut backend, "Phone", "alice@localhost");
+    let tablet = with_device(&mut backend, "Tablet", "alice@localhost");
+    let bob = backend.with_secret_key("bob@localhost");
+    let keys = get_keys(&mut backend).unwrap();
+
+    let people = People::parse(&format!(
+        "alice = [\"alice@localhost\"]\nbob = [\"{}\", \"{}\"]\nold = [\"{}\"]\n",
+        bob.to_lowercase(),
+        "0".repeat(40),
+        tablet
+    ))
+    .unwrap()
+    .with_trusted(BTreeSet::from([laptop.clone(), phone.clone(), bob.clone()]));
+    let alice = people.expand("@alice", &keys).unwrap();
+    assert_eq!(alice.len(), 2);
+    assert!(alice.contains(&laptop) && alice.contains(&phone));
+    assert_eq!(people.expand("@bob", &keys).unwrap().len(), 1);
+    assert!(people
+        .expand("@bob@localhost", &keys)
+        .unwrap()
+        .contains(&bob));
+    assert_eq!(people.expand("@old", &keys).unwrap().len(), 1);
+    assert!(people
+        .expand("@carol", &keys)
+        .unwrap_err()
+        .contains("@carol doesn't match any device key"));
+    assert!(People::parse("alice = \"alice@localhost\"\n").is_err());
+}
+
+#[test]
+fn test_fake_add_group() {
+    let mut fixture = Fixture::for_device("Laptop", "alice@localhost");
+    let laptop = fixture.laptop.clone();
+    let phone = fixture.device("Phone", "alice@localhost");
+    fixture.backend.certify(&phone).unwrap();
+    fixture.device("Tablet", "alice@localhost");
+    let sub = fixture.encrypt_for(&[&laptop], "sub\n");
+    let store = fixture.install(
+        virtual_store()
+            .with_file(".keys/people.toml", "alice = [\"alice@localhost\"]\n")
+            .with_file(".gpg-id", format!("{}\n", laptop))
+            .with_file("subdir/.gpg-id", format!("{}\n", laptop))
+            .with_file("subdir/test.gpg", sub),
+    );
+
+    let new_gpgs = vec!["@alice".to_string()];
+    crate::add_gpgs(
+        Some("subdir".to_string()),
+        &new_gpgs,
+        &mut fixture.backend,
+        &fixture.config,
+    )
+    .unwrap();
+
+    let mut members = [laptop.clone(), phone.clone()];
+    members.sort();
+    let gpg_id = String::from_utf8(store.file("subdir/.gpg-id").unwrap()).unwrap();
+    assert_eq!(
+        gpg_id,
+        format!(
+            "#@alice: {} {}\n{}\n{}\n",
+            members[0], members[1], members[0], members[1]
+        )
+    );
+    let recipients =
+        get_virtual_recipients(&mut fixture.backend, &store, "subdir/test.gpg").unwrap();
+    assert!(recipients.contains(&phone[24..]));
+}
+
+#[test]
+fn test_fake_add_to_inherited_group() {
+    let mut fixture = Fixture::for_device("Laptop", "alice@localhost");
+    let laptop = fixture.laptop.clone();
+    let phone = fixture.device("Phone", "alice@localhost");
+    let bob = fixture.key("bob@localhost");
+    let mut members = [laptop.clone(), phone.clone()];
+    members.sort();
+    let group = format!("#@alice: {} {}\n", members[0], members[1]);
+    let sub = fixture.encrypt_for(&[&laptop, &phone], "sub\n");
+    let store = fixture.install(
+        virtual_store()
+            .with_file(
+                ".gpg-id",
+                format!("{}{}\n{}\n", group, members[0], members[1]),
+            )
+            .with_file("subdir/test.gpg", sub),
+    );
+
+    let new_gpgs = vec!["@alice".to_string(), bob.clone()];
+    crate::add_gpgs(
+        Some("subdir".to_string()),
+        &new_gpgs,
+        &mut fixture.backend,
+        &fixture.config,
+    )
+    .unwrap();
+
+    let gpg_id = String::from_utf8(store.file("subdir/.gpg-id").unwrap()).unwrap();
+    assert!(gpg_id.starts_with(&group));
+    assert_eq!(gpg_id.matches("#@alice").count(), 1);
+    assert!(gpg_id.contains(&bob));
+}
+
+#[test]
+fn test_fake_sync_expands_new_device() {
+    let mut remote = FakeBackend::new();
+    let tablet = with_device(&mut remote, "Tablet", "alice@localhost");
+    let tablet_file = remote.export(&[&tablet], ExportFilter::Full).unwrap();
+
+    let mut fixture = Fixture::for_device("Laptop", "alice@localhost");
+    let laptop = fixture.laptop.clone();
+    let top = fixture.encrypt_for(&[&laptop], "top\n");
+    let team = fixture.encrypt_for(&[&laptop], "team\n");
+    let store = fixture.install(
+        virtual_store()
+            .with_file(&format!(".keys/{}.asc", tablet), tablet_file)
+            .with_file(".gpg-id", format!("{}\n", laptop))
+            .with_file("test.gpg", top.clone())
+            .with_file(
+                "team/.gpg-id",
+                format!("#@alice@localhost: {}\n{}\n", laptop, laptop),
+            )
+            .with_file("team/test.gpg", team),
+    );
+
+    let phone = fixture.device("Phone", "alice@localhost");
+    fixture.backend.certify(&phone).unwrap();
+    let team_gpg_id = store.file("team/.gpg-id");
+
+    // Tablet's key gets imported but not signed, then the refresh is declined
+    script_answers(&["No", "No"]);
+    let mut keys = get_keys(&mut fixture.backend).unwrap();
+    crate::sync(&mut fixture.backend, &mut keys, false, &fixture.config);
+    assert!(fixture.backend.get_key(&tablet).is_some());
+    assert_eq!(store.file("team/.gpg-id"), team_gpg_id);
+
+    script_answers(&["Yes"]);
+    crate::sync(&mut fixture.backend, &mut keys, false, &fixture.config);
+    let gpg_id = String::from_utf8(store.file("team/.gpg-id").unwrap()).unwrap();
+    assert!(gpg_id.starts_with("#@alice@localhost: "));
+    assert!(gpg_id.contains(&format!("\n{}\n", phone)));
+    assert!(!gpg_id.contains(&tablet));
+    let recipients = get_virtual_recipients(&mut fixture.backend, &store, "team/test.gpg").unwrap();
+    assert!(recipients.contains(&phone[24..]));
+    assert_eq!(store.file("test.gpg"), Some(top));
+}
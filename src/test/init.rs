@@ -0,0 +1,98 @@
+use super::clean_up_scenario;
+use super::get_scenario_runtime_path;
+use super::set_up;
+use crate::key::backend::KeyBackend;
+use crate::key::generate;
+use std::fs;
+
+#[test]
+fn test_parse_algo() {
+    assert_eq!(
+        generate::parse_algo("ed25519/cv25519").unwrap(),
+        ("ed25519".to_string(), "cv25519".to_string())
+    );
+    assert_eq!(
+        generate::parse_algo("ed25519").unwrap(),
+        ("ed25519".to_string(), "cv25519".to_string())
+    );
+    assert_eq!(
+        generate::parse_algo("RSA4096").unwrap(),
+        ("rsa4096".to_string(), "rsa4096".to_string())
+    );
+    assert!(generate::parse_algo("").is_err());
+    assert!(generate::parse_algo("ed25519/").is_err());
+    assert!(generate::parse_algo("a/b/c").is_err());
+}
+
+#[test]
+fn test_parse_expire() {
+    let day = 24 * 60 * 60;
+    assert_eq!(generate::parse_expire("never").unwrap(), None);
+    assert_eq!(generate::parse_expire("0").unwrap(), None);
+    assert_eq!(generate::parse_expire("30").unwrap(), Some(30 * day));
+    assert_eq!(generate::parse_expire("6w").unwrap(), Some(42 * day));
+    assert_eq!(generate::parse_expire("2y").unwrap(), Some(730 * day));
+    assert!(generate::parse_expire("2x").is_err());
+    assert!(generate::parse_expire("y").is_err());
+    assert!(generate::parse_expire("0d").is_err());
+}
+
+#[test]
+fn test_key_spec() {
+    let spec =
+        generate::KeySpec::new(Some("Laptop"), "laptop@localhost", None, None, false).unwrap();
+    assert_eq!(spec.get_user_id(), "Laptop <laptop@localhost>");
+    assert_eq!(spec.get_algo(), "ed25519");
+    assert_eq!(spec.get_subkey_algo(), "cv25519");
+
+    let spec =
+        generate::KeySpec::new(None, "phone@localhost", Some("rsa3072"), Some("1y"), true).unwrap();
+    assert_eq!(spec.get_user_id(), "<phone@localhost>");
+    assert!(generate::KeySpec::new(None, "not an email", None, None, false).is_err());
+}
+
+#[test]
+fn test_generate_key() {
+    let scenario_name = "init_generate";
+    let mut backend = set_up(scenario_name);
+    let spec = generate::KeySpec::new(Some("Device"), "device@localhost", None, Some("2y"), false)
+        .unwrap();
+    let fingerprint = backend.generate_key(&spec).unwrap();
+
+    let key = crate::key::get_key(&mut backend, &fingerprint).unwrap();
+    assert_eq!(key.get_identity(), "device@localhost");
+    let mut context = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp).unwrap();
+    let gpg_key = context.get_secret_key(fingerprint.as_str()).unwrap();
+    assert!(gpg_key.has_secret());
+    assert!(gpg_key.can_encrypt());
+    assert!(gpg_key.can_sign());
+    clean_up_scenario(scenario_name);
+}
+
+#[test]
+fn test_init_store() {
+    let scenario_name = "init_store";
+    let mut backend = set_up(scenario_name);
+    let default_key = "126DF511181C21E94E688C44AA8A6BE01EB30743";
+    let conf = crate::config::Config::new(default_key.to_string());
+    let store_path = get_scenario_runtime_path(scenario_name).join("new-store");
+
+    crate::init_store(
+        Some(store_path.to_str().unwrap()),
+        true,
+        false,
+        &mut backend,
+        &conf,
+    );
+
+    let gpg_ids = crate::key::gpg_id::get_base_gpgs_for_dir(&store_path);
+    assert_eq!(gpg_ids.len(), 1);
+    assert!(gpg_ids.contains(default_key));
+    assert!(store_path
+        .join(".keys")
+        .join(format!("{}.asc", default_key))
+        .exists());
+    let metadata = fs::read_to_string(store_path.join(".keys").join("provision.toml")).unwrap();
+    assert!(metadata.contains("gpg-id = \"fingerprint\""));
+    clean_up_scenario(scenario_name);
+}
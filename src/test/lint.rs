@@ -0,0 +1,194 @@
+use super::virtual_store;
+use super::Fixture;
+use crate::command;
+use crate::command::CommandOutput;
+use crate::command::FakeRunner;
+use crate::config::Config;
+use crate::key::backend::FakeBackend;
+use crate::key::backend::KeyBackend;
+use crate::key::get_keys;
+use crate::key::get_pass_dir;
+use crate::key::gpg_id;
+use crate::key::lint;
+use serde_json::Value;
+use std::sync::Arc;
+
+const REVOKED: &str = "1111111111111111111111111111111111111111";
+const SIGN_ONLY: &str = "2222222222222222222222222222222222222222";
+
+fn with_odd_keys(backend: &mut FakeBackend) {
+    let data = format!(
+        "-----BEGIN FAKE PGP PUBLIC KEY BLOCK-----\n\
+         key {} old@localhost revoked\n\
+         key {} signer@localhost sign-only\n\
+         -----END FAKE PGP PUBLIC KEY BLOCK-----\n",
+        REVOKED, SIGN_ONLY
+    );
+    backend.import(data.as_bytes()).unwrap();
+}
+
+fn problems(findings: &[lint::Finding]) -> Vec<(String, String)> {
+    let value: Value = serde_json::from_str(&lint::to_json(findings)).unwrap();
+    value
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|finding| {
+            (
+                format!("{}:{}", finding["file"].as_str().unwrap(), finding["line"]),
+                finding["problem"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_fake_lint_store() {
+    let mut fixture = Fixture::new();
+    let laptop = fixture.laptop.clone();
+    fixture.device("Phone", "shared@localhost");
+    fixture.device("Tablet", "shared@localhost");
+    with_odd_keys(&mut fixture.backend);
+    fixture.install(
+        virtual_store()
+            .with_file(".gpg-id", "laptop@localhost\n")
+            .with_file(&format!(".keys/{}.asc", laptop), "")
+            .with_file(
+                "team/.gpg-id",
+                format!(
+                    "#@laptop: {}\nlaptop@localhost \n\nlaptop@localhost\nshared@localhost\ngone@localhost\n",
+                    laptop
+                ),
+            )
+            .with_file(
+                "old/.gpg-id",
+                format!("{}\n{}\n", REVOKED, &SIGN_ONLY[24..]),
+            ),
+    );
+    let keys = get_keys(&mut fixture.backend).unwrap();
+
+    let findings = lint::lint_store(&keys, std::slice::from_ref(&laptop));
+    assert_eq!(
+        problems(&findings),
+        [
+            ("old/.gpg-id:1", "revoked"),
+            ("old/.gpg-id:1", "not-exported"),
+            ("old/.gpg-id:2", "cannot-encrypt"),
+            ("old/.gpg-id:2", "not-exported"),
+            ("team/.gpg-id:2", "whitespace"),
+            ("team/.gpg-id:4", "duplicate"),
+            ("team/.gpg-id:5", "ambiguous"),
+            ("team/.gpg-id:5", "not-exported"),
+            ("team/.gpg-id:5", "not-exported"),
+            ("team/.gpg-id:6", "unknown"),
+        ]
+        .map(|(line, problem)| (line.to_string(), problem.to_string()))
+    );
+    assert!(lint::describe_findings(&findings[5..6])
+        .starts_with("team/.gpg-id:4: \"laptop@localhost\": duplicate of line 2"));
+}
+
+#[test]
+fn test_fake_lint_fix_rewrites_gpg_id() {
+    let mut fixture = Fixture::new();
+    let laptop = fixture.laptop.clone();
+    let contents = format!("default-key = \"{}\"\n[git]\ncommit = true\n", laptop);
+    fixture.config = Config::from_toml(&contents, None, None)
+        .unwrap()
+        .with_signing_keys(vec![laptop.clone()]);
+    let store = fixture.install(
+        virtual_store()
+            .with_file(".git/HEAD", "")
+            .with_file(&format!(".keys/{}.asc", laptop), "")
+            .with_file("notes.gpg", "")
+            .with_file(
+                "team/.gpg-id",
+                format!("#@me: {}\n  laptop@localhost\nlaptop@localhost\n", laptop),
+            )
+            .with_file("tampered/.gpg-id", "laptop@localhost\nlaptop@localhost\n"),
+    );
+    let pass_dir = get_pass_dir();
+    for folder in ["team", "tampered"] {
+        gpg_id::sign_gpg_id_file(&pass_dir.join(folder), &fixture.config).unwrap();
+    }
+    crate::store::write(
+        &pass_dir.join("tampered/.gpg-id"),
+        "laptop@localhost\nlaptop@localhost\nmallory@localhost\n",
+    )
+    .unwrap();
+    let runner = Arc::new(FakeRunner::new(|invocation| {
+        let stdout = if invocation.command_line().contains(" status ") {
+            b" M team/.gpg-id\n".to_vec()
+        } else {
+            Vec::new()
+        };
+        Ok(CommandOutput::new(Some(0), stdout, Vec::new()))
+    }));
+    command::set_runner(runner.clone());
+    let keys = get_keys(&mut fixture.backend).unwrap();
+    let mut findings = lint::lint_store(&keys, std::slice::from_ref(&laptop));
+    assert_eq!(findings.len(), 4);
+
+    crate::fix_lint_findings(&mut findings, &keys, &mut fixture.backend, &fixture.config);
+
+    assert_eq!(
+        String::from_utf8(store.file("team/.gpg-id").unwrap()).unwrap(),
+        format!("#@me: {}\n{}\nlaptop@localhost\n", laptop, laptop)
+    );
+    gpg_id::verify_gpg_id_file(&pass_dir.join("team"), &fixture.config).unwrap();
+    assert_eq!(
+        String::from_utf8(store.file("tampered/.gpg-id").unwrap()).unwrap(),
+        "laptop@localhost\nlaptop@localhost\nmallory@localhost\n"
+    );
+    let fixed: Vec<bool> = findings.iter().map(|finding| finding.is_fixed()).collect();
+    assert_eq!(fixed, [false, false, true, true]);
+    let add = runner
+        .calls()
+        .into_iter()
+        .map(|call| call.command_line())
+        .find(|line| line.contains(" add "))
+        .unwrap();
+    assert!(add.ends_with(" -- team/.gpg-id.sig team/.gpg-id"));
+}
+
+#[test]
+fn test_fake_lint_fix_exports_only_certified_keys() {
+    let mut fixture = Fixture::new();
+    let laptop = fixture.laptop.clone();
+    let phone = fixture.device("Phone", "phone@localhost");
+    let tablet = fixture.device("Tablet", "tablet@localhost");
+    with_odd_keys(&mut fixture.backend);
+    fixture.backend.certify(&phone).unwrap();
+    fixture.backend.certify(REVOKED).unwrap();
+    let store = fixture.install(
+        virtual_store()
+            .with_file(&format!(".keys/{}.asc", laptop), "")
+            .with_file(
+                ".gpg-id",
+                format!("{}\n{}\n{}\n{}\n", laptop, phone, tablet, REVOKED),
+            ),
+    );
+    let keys = get_keys(&mut fixture.backend).unwrap();
+    let mut findings = lint::lint_store(&keys, std::slice::from_ref(&laptop));
+
+    crate::fix_lint_findings(&mut findings, &keys, &mut fixture.backend, &fixture.config);
+
+    assert!(store.file(&format!(".keys/{}.asc", phone)).is_some());
+    assert!(store.file(&format!(".keys/{}.asc", tablet)).is_none());
+    assert!(store.file(&format!(".keys/{}.asc", REVOKED)).is_none());
+    let unfixed: Vec<(String, String)> = problems(&findings)
+        .into_iter()
+        .zip(&findings)
+        .filter(|(_, finding)| !finding.is_fixed())
+        .map(|(problem, _)| problem)
+        .collect();
+    assert_eq!(
+        unfixed,
+        [
+            (".gpg-id:3", "not-exported"),
+            (".gpg-id:4", "revoked"),
+            (".gpg-id:4", "not-exported"),
+        ]
+        .map(|(line, problem)| (line.to_string(), problem.to_string()))
+    );
+}
@@ -1,19 +1,47 @@
+mod access;
 mod add_gpg;
+mod audit;
+mod cli;
+mod command;
+mod compromise;
+mod config;
+mod export;
 mod gpgid;
+mod graph;
+mod group;
+mod init;
+mod lint;
+mod packets;
+mod pass_env;
+mod policy;
+mod prompt;
+mod recipients;
+mod reconcile;
 mod reencrypt;
+#[cfg(feature = "sequoia")]
+mod sequoia;
 mod sync;
 
-use gpgme::Context;
-use gpgme::KeyListMode;
-use gpgme::Protocol;
+use crate::command::FakeRunner;
+use crate::config::Config;
+use crate::key::backend;
+use crate::key::backend::CertStatus;
+use crate::key::backend::FakeBackend;
+use crate::key::backend::GpgmeBackend;
+use crate::key::backend::KeyBackend;
+use crate::key::generate::KeySpec;
+use crate::prompt::ScriptedPrompter;
+use crate::store;
+use crate::store::MemoryStore;
 use std::collections::HashSet;
 use std::fs;
 use std::iter::FromIterator;
 use std::path::Path;
 use std::path::PathBuf;
 use std::process::Command;
+use std::sync::Arc;
 
-pub static mut TEST_STDIN: &[u8] = b"";
+const VIRTUAL_STORE_ROOT: &str = "/store";
 
 fn get_scenario_runtime_path(scenario_name: &str) -> PathBuf {
     Path::new("testing").join(scenario_name.to_string() + "_run")
@@ -24,44 +52,52 @@ fn clean_up_scenario(scenario_name: &str) {
     fs::remove_dir_all(runtime_path).unwrap();
 }
 
-fn write_to_stdin(input: &'static str) {
-    unsafe {
-        TEST_STDIN = input.as_bytes();
+fn script_answers(answers: &[&str]) {
+    crate::prompt::set_prompter(Arc::new(ScriptedPrompter::new(answers)));
+}
+
+fn is_signed_by(backend: &mut dyn KeyBackend, key_id: &str, signer_identity: &str) -> bool {
+    let key = backend.get_key(key_id);
+    if key.is_none() {
+        return false;
+    }
+    let key = key.unwrap();
+
+    for user_id in key.user_ids() {
+        for certification in user_id.certifications() {
+            if certification.get_status() == CertStatus::Good
+                && certification.get_signer() == Some(signer_identity)
+            {
+                return true;
+            }
+        }
     }
+    false
 }
 
 fn get_recipients(
-    context: &mut Context,
+    backend: &mut dyn KeyBackend,
     scenario_name: &str,
     pass_path: &str,
 ) -> Option<HashSet<String>> {
     let file_path = get_scenario_runtime_path(scenario_name)
         .join("pass")
         .join(pass_path);
-    let ciphertext = gpgme::Data::load(file_path.to_str().unwrap());
+    let ciphertext = fs::read(file_path);
     if ciphertext.is_err() {
         return None;
     }
-    let mut ciphertext = ciphertext.unwrap();
 
-    let mut plaintext = Vec::new();
-    let decrypt_result = context.decrypt(&mut ciphertext, &mut plaintext);
-    if decrypt_result.is_err() {
+    let decrypted = backend.decrypt(&ciphertext.unwrap());
+    if decrypted.is_err() {
         return None;
     }
-    let decrypt_result = decrypt_result.unwrap();
-    let recipients = decrypt_result.recipients();
-    let mut receivers = Vec::new();
-    for receiver in recipients {
-        let key_id = receiver.key_id();
-        if key_id.is_ok() {
-            receivers.push(key_id.unwrap().to_string());
-        }
-    }
-    Some(HashSet::from_iter(receivers))
+    Some(HashSet::from_iter(
+        decrypted.unwrap().get_recipients().iter().cloned(),
+    ))
 }
 
-fn set_up(scenario_name: &str) -> Context {
+fn set_up(scenario_name: &str) -> GpgmeBackend {
     let from_path = Path::new("testing").join(scenario_name);
     let to_path = get_scenario_runtime_path(scenario_name);
     let _clean = fs::remove_dir_all(&to_path);
@@ -77,11 +113,91 @@ fn set_up(scenario_name: &str) -> Context {
 
     std::env::set_var("GNUPGHOME", to_path.join("gnupg"));
     std::env::set_var("PASSWORD_STORE_DIR", to_path.join("pass"));
-    let mut context = Context::from_protocol(Protocol::OpenPgp).unwrap();
-    context.set_armor(true);
-    let mut key_list_mode = KeyListMode::empty();
-    key_list_mode.insert(KeyListMode::LOCAL);
-    key_list_mode.insert(KeyListMode::SIGS);
-    context.set_key_list_mode(key_list_mode).unwrap();
-    context
+    GpgmeBackend::new().unwrap()
+}
+
+fn set_up_virtual(store: MemoryStore, backend: &FakeBackend) -> Arc<MemoryStore> {
+    let store = Arc::new(store);
+    store::set_store(store.clone());
+    crate::command::set_runner(Arc::new(FakeRunner::succeeding()));
+    let opener_backend = backend.clone();
+    backend::set_opener(Some(Arc::new(move || {
+        Ok(Box::new(opener_backend.clone()) as Box<dyn KeyBackend>)
+    })));
+    store
+}
+
+fn virtual_store() -> MemoryStore {
+    MemoryStore::new(VIRTUAL_STORE_ROOT)
+}
+
+fn encrypt_for(backend: &mut FakeBackend, recipients: &[&str], plaintext: &str) -> Vec<u8> {
+    backend.encrypt(recipients, plaintext.as_bytes()).unwrap()
+}
+
+fn get_virtual_recipients(
+    backend: &mut FakeBackend,
+    store: &MemoryStore,
+    pass_path: &str,
+) -> Option<HashSet<String>> {
+    let decrypted = backend.decrypt(&store.file(pass_path)?);
+    if decrypted.is_err() {
+        return None;
+    }
+    Some(HashSet::from_iter(
+        decrypted.unwrap().get_recipients().iter().cloned(),
+    ))
+}
+
+/// A fake keyring and virtual store where `laptop` is this device's secret
+/// key, the backend's signer and the config's default key.
+struct Fixture {
+    backend: FakeBackend,
+    laptop: String,
+    config: Config,
+}
+
+impl Fixture {
+    fn new() -> Fixture {
+        let mut backend = FakeBackend::new();
+        let laptop = backend.with_secret_key("laptop@localhost");
+        Fixture::with_laptop(backend, laptop)
+    }
+
+    fn for_device(device: &str, email: &str) -> Fixture {
+        let mut backend = FakeBackend::new();
+        let laptop = with_device(&mut backend, device, email);
+        Fixture::with_laptop(backend, laptop)
+    }
+
+    fn with_laptop(mut backend: FakeBackend, laptop: String) -> Fixture {
+        backend.add_signer(&laptop).unwrap();
+        let config = Config::new(laptop.clone());
+        Fixture {
+            backend,
+            laptop,
+            config,
+        }
+    }
+
+    fn key(&mut self, email: &str) -> String {
+        self.backend.with_secret_key(email)
+    }
+
+    fn device(&mut self, device: &str, email: &str) -> String {
+        with_device(&mut self.backend, device, email)
+    }
+
+    fn encrypt_for(&mut self, recipients: &[&str], plaintext: &str) -> Vec<u8> {
+        encrypt_for(&mut self.backend, recipients, plaintext)
+    }
+
+    fn install(&self, store: MemoryStore) -> Arc<MemoryStore> {
+        set_up_virtual(store, &self.backend)
+    }
+}
+
+fn with_device(backend: &mut FakeBackend, device: &str, email: &str) -> String {
+    let spec = KeySpec::new(Some(device), email, None, None, false).unwrap();
+    backend.generate_key(&spec).unwrap()
 }
@@ -0,0 +1,48 @@
+use crate::key::backend::packets::encrypted_to;
+
+const V3_PKESK: &[u8] = &[
+    0xc1, 0x0c, 0x03, 0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef, 0x01, 0xaa, 0xbb,
+];
+const OLD_FORMAT_PKESK: &[u8] = &[
+    0x84, 0x0c, 0x03, 0xfe, 0xdc, 0xba, 0x98, 0x76, 0x54, 0x32, 0x10, 0x01, 0xcc, 0xdd,
+];
+const SEIPD: &[u8] = &[0xd2, 0x03, 0x01, 0x02, 0x03];
+
+fn message(packets: &[&[u8]]) -> Vec<u8> {
+    packets.concat()
+}
+
+#[test]
+fn test_packets_encrypted_to() {
+    let recipients = encrypted_to(&message(&[V3_PKESK, OLD_FORMAT_PKESK, SEIPD])).unwrap();
+    assert_eq!(recipients, vec!["0123456789ABCDEF", "FEDCBA9876543210"]);
+}
+
+#[test]
+fn test_packets_encrypted_to_v6() {
+    let mut pkesk = vec![0xc1, 0x25, 0x06, 0x21, 0x06];
+    pkesk.extend([0x11; 32]);
+    pkesk.extend([0x09, 0xee]);
+    let recipients = encrypted_to(&message(&[&pkesk, SEIPD])).unwrap();
+    assert_eq!(recipients, vec!["11".repeat(32)]);
+}
+
+#[test]
+fn test_packets_encrypted_to_armored() {
+    // V3_PKESK followed by SEIPD, base64 encoded
+    let armored = "-----BEGIN PGP MESSAGE-----\n\
+                   Comment: test\n\
+                   \n\
+                   wQwDASNFZ4mrze8BqrvSAwECAw==\n\
+                   =abcd\n\
+                   -----END PGP MESSAGE-----\n";
+    let recipients = encrypted_to(armored.as_bytes()).unwrap();
+    assert_eq!(recipients, vec!["0123456789ABCDEF"]);
+}
+
+#[test]
+fn test_packets_encrypted_to_errors() {
+    assert!(encrypted_to(b"plain text").is_err());
+    assert!(encrypted_to(SEIPD).is_err());
+    assert!(encrypted_to(&V3_PKESK[..6]).is_err());
+}
@@ -0,0 +1,68 @@
+use crate::pass_env;
+
+fn opts(raw: &[&str]) -> Vec<String> {
+    raw.iter().map(|opt| opt.to_string()).collect()
+}
+
+#[test]
+fn test_parse_extension_opts() {
+    let parsed = pass_env::parse_extension_opts(
+        "--trust-model\nalways\n--keyring\n/tmp/my keys.kbx\n--quiet\n--yes\n",
+    );
+    assert_eq!(
+        parsed,
+        opts(&[
+            "--trust-model",
+            "always",
+            "--keyring",
+            "/tmp/my keys.kbx",
+            "--quiet",
+            "--yes"
+        ])
+    );
+    assert!(pass_env::parse_extension_opts("").is_empty());
+}
+
+#[test]
+fn test_parse_pass_opts() {
+    let parsed = pass_env::parse_pass_opts("  --trust-model always\t--armor ");
+    assert_eq!(parsed, opts(&["--trust-model", "always", "--armor"]));
+}
+
+#[test]
+fn test_gpg_homedir() {
+    assert_eq!(pass_env::gpg_homedir(&opts(&["--quiet", "--yes"])), None);
+    assert_eq!(
+        pass_env::gpg_homedir(&opts(&["--homedir", "/tmp/gnupg", "--quiet"])),
+        Some("/tmp/gnupg".to_string())
+    );
+    assert_eq!(
+        pass_env::gpg_homedir(&opts(&["--homedir=/tmp/a", "--homedir", "/tmp/b"])),
+        Some("/tmp/b".to_string())
+    );
+    assert_eq!(pass_env::gpg_homedir(&opts(&["--homedir"])), None);
+}
+
+#[test]
+fn test_interactive_opts() {
+    let pass_opts = opts(&[
+        "--homedir",
+        "/tmp/gnupg",
+        "--quiet",
+        "--yes",
+        "--compress-algo=none",
+        "--no-encrypt-to",
+        "--batch",
+        "--use-agent",
+    ]);
+    assert_eq!(
+        pass_env::interactive_opts(&pass_opts),
+        opts(&[
+            "--homedir",
+            "/tmp/gnupg",
+            "--compress-algo=none",
+            "--no-encrypt-to",
+            "--use-agent"
+        ])
+    );
+}
@@ -0,0 +1,120 @@
+use super::get_virtual_recipients;
+use super::script_answers;
+use super::virtual_store;
+use super::Fixture;
+use crate::key::get_keys;
+use crate::key::group::People;
+use crate::key::policy::glob_matches;
+use crate::key::policy::Policy;
+
+const POLICY: &str = r#"
+[groups]
+ops = ["laptop@localhost", "phone@localhost"]
+everyone = ["ops", "tablet@localhost"]
+
+[folders]
+"." = ["laptop@localhost"]
+"team/*" = ["ops"]
+"team/shared" = ["everyone"]
+"#;
+
+#[test]
+fn test_glob_matches() {
+    assert!(glob_matches(".", "."));
+    assert!(!glob_matches(".", "team"));
+    assert!(!glob_matches("*", "."));
+    assert!(glob_matches("team/*", "team/infra"));
+    assert!(!glob_matches("team/*", "team"));
+    assert!(!glob_matches("team/*", "team/infra/db"));
+    assert!(glob_matches("team/**", "team"));
+    assert!(glob_matches("team/**", "team/infra/db"));
+    assert!(glob_matches("**/db", "team/infra/db"));
+    assert!(glob_matches("team/in?ra", "team/infra"));
+    assert!(glob_matches("team/*-prod", "team/web-prod"));
+    assert!(!glob_matches("team/*-prod", "team/web-staging"));
+}
+
+#[test]
+fn test_policy_last_rule_wins() {
+    let policy = Policy::parse(POLICY).unwrap();
+    let root = policy.recipients_for(".").unwrap();
+    assert_eq!(root.into_iter().collect::<Vec<_>>(), ["laptop@localhost"]);
+    let infra = policy.recipients_for("team/infra").unwrap();
+    assert_eq!(
+        infra.into_iter().collect::<Vec<_>>(),
+        ["laptop@localhost", "phone@localhost"]
+    );
+    let shared = policy.recipients_for("team/shared").unwrap();
+    assert_eq!(shared.len(), 3);
+    assert!(policy.recipients_for("team").is_none());
+}
+
+#[test]
+fn test_policy_parse_errors() {
+    let cycle = Policy::parse("[groups]\na = [\"b\"]\nb = [\"a\"]\n[folders]\nx = [\"a\"]\n");
+    assert!(cycle.err().unwrap().contains("group cycle: a -> b -> a"));
+    let empty = Policy::parse("[folders]\nx = []\n");
+    assert!(empty.err().unwrap().contains("expands to no recipients"));
+    let unknown = Policy::parse("[owners]\nx = [\"a\"]\n");
+    assert!(unknown.err().unwrap().contains("unknown entry"));
+    let not_strings = Policy::parse("[folders]\nx = [1]\n");
+    assert!(not_strings
+        .err()
+        .unwrap()
+        .contains("must be an array of strings"));
+}
+
+#[test]
+fn test_fake_apply_policy() {
+    let mut fixture = Fixture::new();
+    let phone = fixture.key("phone@localhost");
+    fixture.key("tablet@localhost");
+    let top = fixture.encrypt_for(&["laptop@localhost"], "top\n");
+    let infra = fixture.encrypt_for(&["laptop@localhost"], "infra\n");
+    let store = fixture.install(
+        virtual_store()
+            .with_file(".keys/policy.toml", POLICY)
+            .with_file(".gpg-id", "laptop@localhost\n")
+            .with_file("test.gpg", top.clone())
+            .with_file("team/infra/.gpg-id", "laptop@localhost\n")
+            .with_file("team/infra/test.gpg", infra.clone())
+            .with_file("team/shared/test.gpg", infra.clone()),
+    );
+
+    let keys = get_keys(&mut fixture.backend).unwrap();
+    let people = People::default();
+    let changes = Policy::load()
+        .unwrap()
+        .unwrap()
+        .plan(&people, &keys)
+        .unwrap();
+    let folders: Vec<&str> = changes.iter().map(|change| change.get_folder()).collect();
+    assert_eq!(folders, ["team/infra", "team/shared"]);
+    assert!(!changes[0].is_new());
+    assert_eq!(changes[0].added(), ["phone@localhost"]);
+    assert!(changes[0].removed().is_empty());
+    assert!(changes[1].is_new());
+
+    crate::apply_policy(true, &keys, &mut fixture.backend, &fixture.config);
+    assert_eq!(
+        store.file("team/infra/.gpg-id"),
+        Some(b"laptop@localhost\n".to_vec())
+    );
+
+    script_answers(&["yes"]);
+    crate::apply_policy(false, &keys, &mut fixture.backend, &fixture.config);
+    assert_eq!(
+        store.file("team/infra/.gpg-id"),
+        Some(b"laptop@localhost\nphone@localhost\n".to_vec())
+    );
+    assert_eq!(
+        store.file("team/shared/.gpg-id"),
+        Some(b"laptop@localhost\nphone@localhost\ntablet@localhost\n".to_vec())
+    );
+    assert_eq!(store.file("test.gpg"), Some(top));
+    let recipients =
+        get_virtual_recipients(&mut fixture.backend, &store, "team/infra/test.gpg").unwrap();
+    assert!(recipients.contains(&phone[24..]));
+    let policy = Policy::load().unwrap().unwrap();
+    assert!(policy.plan(&people, &keys).unwrap().is_empty());
+}
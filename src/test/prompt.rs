@@ -0,0 +1,172 @@
+use super::is_signed_by;
+use super::virtual_store;
+use super::Fixture;
+use crate::key::backend::ExportFilter;
+use crate::key::backend::FakeBackend;
+use crate::key::backend::KeyBackend;
+use crate::key::get_keys;
+use crate::prompt::parse_choice;
+use crate::prompt::AssumePrompter;
+use crate::prompt::JsonRpcPrompter;
+use crate::prompt::Prompter;
+use crate::prompt::ScriptedPrompter;
+use serde_json::json;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Cursor;
+use std::io::Write;
+use std::process::Command;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+const YES_NO: &[&str] = &["Yes", "No"];
+
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_parse_choice_bounds() {
+    assert_eq!(parse_choice("1", YES_NO, None), Ok(0));
+    assert_eq!(parse_choice(" 2\n", YES_NO, None), Ok(1));
+    assert_eq!(parse_choice("no", YES_NO, None), Ok(1));
+    assert_eq!(parse_choice("", YES_NO, Some(1)), Ok(1));
+    assert!(parse_choice("", YES_NO, None).is_err());
+    assert!(parse_choice("0", YES_NO, Some(0)).is_err());
+    assert!(parse_choice("3", YES_NO, Some(0)).is_err());
+    assert!(parse_choice("-1", YES_NO, Some(0)).is_err());
+    assert!(parse_choice("maybe", YES_NO, Some(0)).is_err());
+}
+
+#[test]
+fn test_scripted_prompter() {
+    let prompter = ScriptedPrompter::new(&["2", "", "laptop", "0"]);
+    assert_eq!(prompter.choose("Sign?", YES_NO, Some(0)), Ok(1));
+    assert_eq!(prompter.choose("Sign?", YES_NO, Some(0)), Ok(0));
+    assert_eq!(prompter.ask("Name:"), Ok("laptop".to_string()));
+    let err = prompter.choose("Sign?", YES_NO, Some(0)).unwrap_err();
+    assert!(err.starts_with("Sign?: Choice 0 is out of bounds"));
+
+    assert_eq!(prompter.choose("Sign?", YES_NO, Some(1)), Ok(1));
+    assert!(prompter.choose("Key?", &["a", "b"], None).is_err());
+    assert!(prompter.ask("Name:").is_err());
+}
+
+#[test]
+fn test_assume_prompters() {
+    let yes = AssumePrompter::yes();
+    let no = AssumePrompter::no();
+    assert_eq!(yes.choose("Sign?", YES_NO, Some(1)), Ok(0));
+    assert_eq!(no.choose("Sign?", YES_NO, Some(0)), Ok(1));
+    assert_eq!(yes.choose("Key?", &["a", "b"], Some(1)), Ok(1));
+    assert!(no.choose("Key?", &["a", "b"], None).is_err());
+    assert!(yes.ask("Name:").is_err());
+    assert_eq!(yes.choose_safely("Sign?", YES_NO, 1), Ok(1));
+    assert_eq!(no.choose_safely("Sign?", YES_NO, 1), Ok(1));
+}
+
+#[test]
+fn test_json_rpc_prompter() {
+    let responses = "{\"jsonrpc\":\"2.0\",\"id\":1,\"result\":1}\n\
+                     {\"jsonrpc\":\"2.0\",\"id\":2,\"result\":\"laptop\"}\n\
+                     {\"jsonrpc\":\"2.0\",\"id\":3,\"result\":null}\n\
+                     {\"jsonrpc\":\"2.0\",\"id\":4,\"result\":2}\n\
+                     {\"jsonrpc\":\"2.0\",\"id\":5,\"error\":{\"code\":1,\"message\":\"cancelled\"}}\n\
+                     {\"jsonrpc\":\"2.0\",\"id\":7,\"result\":0}\n";
+    let requests = SharedBuffer::default();
+    let prompter = JsonRpcPrompter::new(
+        Box::new(Cursor::new(responses.as_bytes().to_vec())),
+        Box::new(requests.clone()),
+    );
+
+    assert_eq!(prompter.choose("Sign?", YES_NO, Some(0)), Ok(1));
+    assert_eq!(prompter.ask("Name:"), Ok("laptop".to_string()));
+    assert_eq!(prompter.choose("Sign?", YES_NO, Some(1)), Ok(1));
+    assert!(prompter.choose("Sign?", YES_NO, Some(0)).is_err());
+    assert!(prompter.ask("Name:").unwrap_err().contains("cancelled"));
+    assert!(prompter.ask("Name:").unwrap_err().contains("doesn't match"));
+    assert!(prompter.ask("Name:").is_err());
+
+    let requests = String::from_utf8(requests.0.lock().unwrap().clone()).unwrap();
+    let first: serde_json::Value = serde_json::from_str(requests.lines().next().unwrap()).unwrap();
+    assert_eq!(first["jsonrpc"], "2.0");
+    assert_eq!(first["method"], "choose");
+    assert_eq!(first["params"]["options"][1], "No");
+    assert_eq!(first["params"]["default"], 0);
+    assert_eq!(requests.lines().count(), 7);
+}
+
+const JSON_RPC_CHILD_VAR: &str = "PASS_PROVISION_TEST_JSON_RPC_CHILD";
+
+/// Runs in the child process spawned by `test_json_rpc_sync_stdout`.
+#[test]
+fn test_json_rpc_sync_child() {
+    if std::env::var_os(JSON_RPC_CHILD_VAR).is_none() {
+        return;
+    }
+    let mut remote = FakeBackend::new();
+    let phone = remote.with_secret_key("phone@localhost");
+    let phone_file = remote.export(&[&phone], ExportFilter::Full).unwrap();
+
+    let mut fixture = Fixture::new();
+    fixture.install(virtual_store().with_file(&format!(".keys/{}.asc", phone), phone_file));
+    crate::prompt::set_prompter(Arc::new(JsonRpcPrompter::stdio().unwrap()));
+    let mut keys = get_keys(&mut fixture.backend).unwrap();
+    crate::sync(&mut fixture.backend, &mut keys, false, &fixture.config);
+
+    assert!(is_signed_by(
+        &mut fixture.backend,
+        &phone,
+        &fixture.laptop[24..]
+    ));
+}
+
+#[test]
+fn test_json_rpc_sync_stdout() {
+    let mut child = Command::new(std::env::current_exe().unwrap())
+        .args([
+            "--exact",
+            "test::prompt::test_json_rpc_sync_child",
+            "--nocapture",
+            "--test-threads=1",
+        ])
+        .env(JSON_RPC_CHILD_VAR, "1")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut answers = child.stdin.take().unwrap();
+    let requests = BufReader::new(child.stdout.take().unwrap());
+
+    let mut methods = Vec::new();
+    for line in requests.lines() {
+        let line = line.unwrap();
+        // The test harness announces itself before the child takes stdout over
+        if line.is_empty() || line == "running 1 test" {
+            continue;
+        }
+        let line = line.trim_start_matches("test test::prompt::test_json_rpc_sync_child ... ");
+        let request: serde_json::Value = serde_json::from_str(line).unwrap();
+        methods.push(request["method"].as_str().unwrap().to_string());
+        let response = json!({ "jsonrpc": "2.0", "id": request["id"], "result": 0 });
+        writeln!(answers, "{}", response).unwrap();
+    }
+
+    let output = child.wait_with_output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert_eq!(methods, ["choose", "choose"]);
+    assert!(stderr.contains("Are you sure that you want to sign? > Yes"));
+}
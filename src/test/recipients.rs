@@ -0,0 +1,204 @@
+use super::set_up_virtual;
+use super::virtual_store;
+use super::Fixture;
+use crate::key::backend::FakeBackend;
+use crate::key::get_keys;
+use crate::key::recipients::matches_key;
+use crate::key::recipients::RecipientResolver;
+use crate::store::MemoryStore;
+use crate::store::StoreFs;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+const NAMES: &[&str] = &["a", "b", "c"];
+const IDS: &[&str] = &["user1", "user2", "user3", "user4"];
+
+/// xorshift64, so every run explores the same layouts.
+struct Rng(u64);
+
+impl Rng {
+    fn below(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % bound as u64) as usize
+    }
+}
+
+/// A random tree of folders, each holding a password and, some of the time,
+/// a .gpg-id (possibly empty) with a few of `IDS`.
+fn random_layout(rng: &mut Rng) -> BTreeMap<String, Option<Vec<String>>> {
+    let mut layout = BTreeMap::new();
+    layout.insert(String::new(), None);
+    for _ in 0..rng.below(10) {
+        let parents: Vec<String> = layout
+            .keys()
+            .filter(|folder| folder.matches('/').count() < 3)
+            .cloned()
+            .collect();
+        let parent = &parents[rng.below(parents.len())];
+        let name = NAMES[rng.below(NAMES.len())];
+        let folder = if parent.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{}", parent, name)
+        };
+        layout.insert(folder, None);
+    }
+    for gpg_ids in layout.values_mut() {
+        if rng.below(2) == 0 {
+            let mut ids: Vec<String> = (0..rng.below(3))
+                .map(|_| IDS[rng.below(IDS.len())].to_string())
+                .collect();
+            ids.sort();
+            ids.dedup();
+            *gpg_ids = Some(ids);
+        }
+    }
+    layout
+}
+
+fn expected_source(layout: &BTreeMap<String, Option<Vec<String>>>, folder: &str) -> Option<String> {
+    let mut current = folder.to_string();
+    loop {
+        if layout[&current].is_some() {
+            return Some(current);
+        }
+        if current.is_empty() {
+            return None;
+        }
+        current = match current.rfind('/') {
+            Some(index) => current[..index].to_string(),
+            None => String::new(),
+        };
+    }
+}
+
+#[test]
+fn test_resolver_matches_nearest_gpg_id() {
+    let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+    for _ in 0..200 {
+        let layout = random_layout(&mut rng);
+        let mut store = MemoryStore::new("/outer/store");
+        for (folder, gpg_ids) in &layout {
+            let prefix = if folder.is_empty() {
+                String::new()
+            } else {
+                format!("{}/", folder)
+            };
+            store = store.with_file(&format!("{}pw.gpg", prefix), "");
+            if gpg_ids.is_some() {
+                let contents: String = gpg_ids
+                    .as_ref()
+                    .unwrap()
+                    .iter()
+                    .map(|id| format!("{}\n", id))
+                    .collect();
+                store = store.with_file(&format!("{}.gpg-id", prefix), contents);
+            }
+        }
+        let store = set_up_virtual(store, &FakeBackend::new());
+        store
+            .write(Path::new("/outer/.gpg-id"), b"outside\n")
+            .unwrap();
+
+        let resolver = RecipientResolver::new(&[]);
+        for folder in layout.keys() {
+            let relative = resolver.resolve(Path::new(folder)).unwrap();
+            let absolute = resolver
+                .resolve(&Path::new("/outer/store").join(folder))
+                .unwrap();
+            assert_eq!(relative.get_gpg_ids(), absolute.get_gpg_ids());
+            assert_eq!(relative.get_source(), absolute.get_source());
+
+            let source = expected_source(&layout, folder);
+            if source.is_none() {
+                assert!(relative.is_empty(), "{} in {:?}", folder, layout);
+                assert_eq!(relative.get_source(), None);
+                continue;
+            }
+            let source = source.unwrap();
+            let expected_ids = layout[&source].as_ref().unwrap();
+            assert_eq!(relative.get_gpg_ids(), &expected_ids[..], "{:?}", layout);
+            let source = if source.is_empty() {
+                ".".to_string()
+            } else {
+                source
+            };
+            assert_eq!(relative.get_source(), Some(source.as_str()));
+        }
+    }
+}
+
+#[test]
+fn test_resolver_rejects_paths_outside_store() {
+    set_up_virtual(
+        MemoryStore::new("/outer/store").with_file(".gpg-id", "user1\n"),
+        &FakeBackend::new(),
+    );
+    let resolver = RecipientResolver::new(&[]);
+    assert!(resolver.resolve(Path::new("..")).is_err());
+    assert!(resolver.resolve(Path::new("a/../../b")).is_err());
+    assert!(resolver.resolve(Path::new("/outer")).is_err());
+    assert_eq!(
+        resolver.resolve(Path::new("./a/b")).unwrap().get_source(),
+        Some(".")
+    );
+}
+
+#[test]
+fn test_resolver_resolves_keys() {
+    let mut fixture = Fixture::new();
+    let laptop = fixture.laptop.clone();
+    let phone = fixture.key("phone@localhost");
+    fixture.install(virtual_store().with_file(
+        "team/.gpg-id",
+        format!("laptop@localhost\n0x{}\nunknown@localhost\n", &phone[24..]),
+    ));
+    let keys = get_keys(&mut fixture.backend).unwrap();
+
+    let resolved = RecipientResolver::new(&keys)
+        .resolve(Path::new("team/sub"))
+        .unwrap();
+    assert_eq!(resolved.get_source(), Some("team"));
+    assert_eq!(resolved.get_gpg_ids()[1], "laptop@localhost");
+    assert_eq!(resolved.get_keys(0), [phone]);
+    assert_eq!(resolved.get_keys(1), [laptop]);
+    assert!(resolved.get_keys(2).is_empty());
+}
+
+#[test]
+fn test_matches_key_user_ids() {
+    let mut backend = FakeBackend::new();
+    let laptop = backend.with_secret_key("alice@work.example");
+    backend.add_user_id(&laptop, "Alice Liddell <alice@home.example>");
+    let keys = get_keys(&mut backend).unwrap();
+    let key = &keys[0];
+
+    assert_eq!(key.get_identity(), "alice@work.example");
+    for recipient in [
+        "alice@home.example",
+        "ALICE@HOME.EXAMPLE",
+        "home.example",
+        "liddell",
+        "*Liddell",
+        "<alice@home.example>",
+        "@home.example",
+        "@alice",
+        "@ALICE@HOME",
+        "=Alice Liddell <alice@home.example>",
+        &laptop[24..],
+    ] {
+        assert!(matches_key(recipient, key), "{}", recipient);
+    }
+    for recipient in [
+        "bob@home.example",
+        "<home.example>",
+        "@liddell",
+        "@",
+        "=alice liddell <alice@home.example>",
+        "",
+    ] {
+        assert!(!matches_key(recipient, key), "{}", recipient);
+    }
+}
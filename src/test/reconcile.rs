@@ -0,0 +1,147 @@
+use super::script_answers;
+use super::virtual_store;
+use super::Fixture;
+use crate::command;
+use crate::command::CommandOutput;
+use crate::command::FakeRunner;
+use crate::config::Config;
+use crate::git;
+use crate::key;
+use crate::key::get_keys;
+use crate::key::reconcile::Reconciliation;
+use serde_json::Value;
+use std::sync::Arc;
+
+#[test]
+fn test_fake_reconcile() {
+    let mut fixture = Fixture::new();
+    let laptop = fixture.laptop.clone();
+    let phone = fixture.key("phone@localhost");
+    let old = fixture.key("old@localhost");
+    let tablet = fixture.key("tablet@localhost");
+    let unknown = "0123456789ABCDEF0123456789ABCDEF01234567";
+    let store = fixture.install(
+        virtual_store()
+            .with_file(".git/HEAD", "")
+            .with_file(".gpg-id", "laptop@localhost\nphone@localhost\n")
+            .with_file(
+                "team/.gpg-id",
+                format!("tablet@localhost\ngone@localhost\n{}\n", unknown),
+            )
+            .with_file(&format!(".keys/{}.asc", laptop), "")
+            .with_file(&format!(".keys/{}.asc", old), "")
+            .with_file(&format!(".keys/{}.asc", unknown), ""),
+    );
+    let log = format!(
+        ".keys/{}.asc\n.keys/retired/{}.asc\n.keys/{}.asc\n",
+        phone, old, phone
+    );
+    command::set_runner(Arc::new(FakeRunner::new(move |_| {
+        Ok(CommandOutput::new(
+            Some(0),
+            log.clone().into_bytes(),
+            Vec::new(),
+        ))
+    })));
+    let deleted = git::deleted_key_ids();
+    assert_eq!(deleted, vec![phone.clone()]);
+    let keys = get_keys(&mut fixture.backend).unwrap();
+
+    let reconciliation = Reconciliation::load(&keys, &key::get_key_ids(), &deleted, &laptop);
+    assert_eq!(reconciliation.get_orphaned(), vec![&old]);
+    assert!(reconciliation.needs_attention());
+    let value: Value = serde_json::from_str(&reconciliation.to_json()).unwrap();
+    assert_eq!(value["unexportable"], serde_json::json!(["gone@localhost"]));
+    assert_eq!(value["missing"][0]["fingerprint"], phone.as_str());
+    assert_eq!(value["missing"][0]["deleted_upstream"], true);
+    assert_eq!(value["missing"][1]["fingerprint"], tablet.as_str());
+    assert_eq!(value["missing"][1]["deleted_upstream"], false);
+    assert!(reconciliation
+        .describe()
+        .contains(&format!("  {} phone@localhost (deleted upstream)\n", phone)));
+
+    let own = Reconciliation::load(&keys, &key::get_key_ids(), &deleted, &old);
+    assert!(own.get_orphaned().is_empty());
+
+    key::retire_key(&old).unwrap();
+    assert!(store.file(&format!(".keys/{}.asc", old)).is_none());
+    assert!(store.file(&format!(".keys/retired/{}.asc", old)).is_some());
+    assert!(!key::get_key_ids().contains(&old));
+}
+
+#[test]
+fn test_fake_reconcile_matches_every_user_id() {
+    let mut fixture = Fixture::new();
+    let laptop = fixture.laptop.clone();
+    let phone = fixture.key("phone@work.example");
+    fixture
+        .backend
+        .add_user_id(&phone, "Alice <alice@home.example>");
+    let unknown = "0123456789ABCDEF0123456789ABCDEF01234567";
+    fixture.install(
+        virtual_store()
+            .with_file(
+                ".gpg-id",
+                format!(
+                    "laptop@localhost\nAlice@Home.Example\n0x{}\n",
+                    &unknown[24..]
+                ),
+            )
+            .with_file(&format!(".keys/{}.asc", laptop), "")
+            .with_file(&format!(".keys/{}.asc", phone), "")
+            .with_file(&format!(".keys/{}.asc", unknown), ""),
+    );
+    let keys = get_keys(&mut fixture.backend).unwrap();
+
+    let reconciliation = Reconciliation::load(&keys, &key::get_key_ids(), &[], &laptop);
+    assert!(reconciliation.get_orphaned().is_empty());
+    assert!(!reconciliation.needs_attention());
+}
+
+#[test]
+fn test_fake_retire_keys_commits() {
+    let mut fixture = Fixture::new();
+    let contents = format!(
+        "default-key = \"{}\"\n[git]\ncommit = true\n",
+        fixture.laptop
+    );
+    fixture.config = Config::from_toml(&contents, None, None).unwrap();
+    let old = fixture.key("old@localhost");
+    let store = fixture.install(
+        virtual_store()
+            .with_file(".git/HEAD", "")
+            .with_file(&format!(".keys/{}.asc", old), ""),
+    );
+    // Like git, refuse to stage a path that doesn't exist
+    let runner = Arc::new(FakeRunner::new(|invocation| {
+        let line = invocation.command_line();
+        let missing = line.contains(" add ")
+            && line
+                .split(" -- ")
+                .nth(1)
+                .unwrap()
+                .split(' ')
+                .any(|path| !crate::store::exists(&key::get_pass_dir().join(path)));
+        if missing {
+            return Ok(CommandOutput::new(Some(128), Vec::new(), Vec::new()));
+        }
+        let stdout = if line.contains(" status ") {
+            b"R  .keys/retired\n".to_vec()
+        } else {
+            Vec::new()
+        };
+        Ok(CommandOutput::new(Some(0), stdout, Vec::new()))
+    }));
+    command::set_runner(runner.clone());
+
+    script_answers(&["Yes"]);
+    crate::retire_keys(vec![&old], &fixture.config);
+
+    assert!(store.file(&format!(".keys/retired/{}.asc", old)).is_some());
+    let lines: Vec<String> = runner
+        .calls()
+        .iter()
+        .map(|call| call.command_line())
+        .collect();
+    assert!(lines.iter().any(|line| line.contains(" commit ")));
+}
@@ -1,27 +1,47 @@
 use super::clean_up_scenario;
+use super::encrypt_for;
 use super::get_recipients;
 use super::get_scenario_runtime_path;
+use super::get_virtual_recipients;
 use super::set_up;
+use super::set_up_virtual;
+use super::virtual_store;
+use super::Fixture;
+use crate::command;
+use crate::command::CommandOutput;
+use crate::command::FakeRunner;
+use crate::config::Config;
+use crate::key::backend::FakeBackend;
+use crate::key::backend::KeyBackend;
+use crate::key::get_keys;
+use crate::key::get_pass_dir;
+use crate::key::reencrypt::reencrypt_dir;
 use std::collections::HashSet;
 use std::iter::FromIterator;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 
 #[test]
 pub fn test_reencrypt_main() {
     let scenario_name = "reencrypt_main";
-    let mut context = set_up(scenario_name);
+    let mut backend = set_up(scenario_name);
 
-    let recipients = get_recipients(&mut context, scenario_name, "test.gpg");
+    let recipients = get_recipients(&mut backend, scenario_name, "test.gpg");
     if recipients.is_none() {
         panic!("Unable to decode test file");
     }
 
     let new_recipients = HashSet::from_iter(vec!["signer@pubkey.localhost".to_string()]);
     let gpg_path = get_scenario_runtime_path(scenario_name).join("pass");
-    crate::key::gpg_id::write_gpg_ids(&gpg_path, &new_recipients);
+    let conf = crate::config::Config::new("126DF511181C21E94E688C44AA8A6BE01EB30743".to_string());
+    crate::key::gpg_id::write_gpg_ids(&gpg_path, &new_recipients, &conf);
 
-    crate::reencrypt_cmd(&Vec::new());
+    crate::reencrypt(None, &conf);
 
-    let new_recipients = get_recipients(&mut context, scenario_name, "test.gpg");
+    let new_recipients = get_recipients(&mut backend, scenario_name, "test.gpg");
     if new_recipients.is_some() {
         panic!("Failed to reencrypt main directory");
     }
@@ -31,13 +51,13 @@ pub fn test_reencrypt_main() {
 #[test]
 pub fn test_reencrypt_subdir() {
     let scenario_name = "reencrypt_subdir";
-    let mut context = set_up(scenario_name);
+    let mut backend = set_up(scenario_name);
 
-    let recipients = get_recipients(&mut context, scenario_name, "test.gpg");
+    let recipients = get_recipients(&mut backend, scenario_name, "test.gpg");
     if recipients.is_none() {
         panic!("Unable to decode test file");
     }
-    let subdir_recipients = get_recipients(&mut context, scenario_name, "subdir/test.gpg");
+    let subdir_recipients = get_recipients(&mut backend, scenario_name, "subdir/test.gpg");
     if subdir_recipients.is_none() {
         panic!("Unable to decode test subdir file");
     }
@@ -46,18 +66,166 @@ pub fn test_reencrypt_subdir() {
     let gpg_path = get_scenario_runtime_path(scenario_name)
         .join("pass")
         .join("subdir");
-    crate::key::gpg_id::write_gpg_ids(&gpg_path, &new_recipients);
+    let conf = crate::config::Config::new("126DF511181C21E94E688C44AA8A6BE01EB30743".to_string());
+    crate::key::gpg_id::write_gpg_ids(&gpg_path, &new_recipients, &conf);
 
-    crate::reencrypt_cmd(&["subdir".to_string()]);
+    crate::reencrypt(Some("subdir".to_string()), &conf);
 
-    let new_recipients = get_recipients(&mut context, scenario_name, "test.gpg");
+    let new_recipients = get_recipients(&mut backend, scenario_name, "test.gpg");
     if new_recipients.is_none() {
         panic!("Reencrypt reencrypted main repo");
     }
 
-    let subdir_new_recipients = get_recipients(&mut context, scenario_name, "subdir/test.gpg");
+    let subdir_new_recipients = get_recipients(&mut backend, scenario_name, "subdir/test.gpg");
     if subdir_new_recipients.is_some() {
         panic!("Failed to reencrypt subdir");
     }
     clean_up_scenario(scenario_name);
 }
+
+#[test]
+pub fn test_fake_reencrypt_paths() {
+    let mut backend = FakeBackend::new();
+    let laptop = backend.with_secret_key("laptop@localhost");
+    let phone = backend.with_secret_key("phone@localhost");
+    let mut store = virtual_store().with_file(".gpg-id", "laptop@localhost\n");
+    for dir in &["work", "home", "shared"] {
+        let secret = encrypt_for(&mut backend, &["laptop@localhost"], dir);
+        store = store
+            .with_file(&format!("{}/.gpg-id", dir), "phone@localhost\n")
+            .with_file(&format!("{}/nested/site.gpg", dir), secret);
+    }
+    let top = encrypt_for(&mut backend, &["laptop@localhost"], "top");
+    let store = set_up_virtual(store.with_file("top.gpg", top.clone()), &backend);
+
+    let conf = crate::config::Config::new(laptop.clone());
+    let paths = vec!["work".to_string(), "home".to_string()];
+    crate::reencrypt_all(&paths, &conf);
+
+    let expected = HashSet::from_iter(vec![phone[24..].to_string()]);
+    for dir in &["work", "home"] {
+        let path = format!("{}/nested/site.gpg", dir);
+        let recipients = get_virtual_recipients(&mut backend, &store, &path);
+        assert_eq!(recipients, Some(expected.clone()));
+    }
+    let shared = get_virtual_recipients(&mut backend, &store, "shared/nested/site.gpg");
+    assert!(shared.unwrap().contains(&laptop[24..]));
+    assert_eq!(store.file("top.gpg"), Some(top));
+}
+
+#[test]
+fn test_fake_reencrypt_runs_pass_init_one_at_a_time() {
+    let backend = FakeBackend::new().named("gpgme");
+    let mut store = virtual_store().with_file(".gpg-id", "laptop@localhost\n");
+    for dir in &["work", "home", "shared"] {
+        store = store.with_file(&format!("{}/.gpg-id", dir), "phone@localhost\n");
+    }
+    set_up_virtual(store, &backend);
+    let running = Arc::new(AtomicUsize::new(0));
+    let most_running = Arc::new(AtomicUsize::new(0));
+    let (handler_running, handler_most) = (running.clone(), most_running.clone());
+    command::set_runner(Arc::new(FakeRunner::new(move |_| {
+        let now = handler_running.fetch_add(1, Ordering::SeqCst) + 1;
+        handler_most.fetch_max(now, Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(20));
+        handler_running.fetch_sub(1, Ordering::SeqCst);
+        Ok(CommandOutput::new(Some(0), Vec::new(), Vec::new()))
+    })));
+
+    let conf = Config::from_toml("[reencrypt]\njobs = 3\n", None, None).unwrap();
+    let paths = vec!["work".to_string(), "home".to_string(), "shared".to_string()];
+    crate::reencrypt_all(&paths, &conf);
+    assert_eq!(most_running.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_fake_reencrypt_commits_inherited_gpg_id_removal() {
+    let backend = FakeBackend::new().named("gpgme");
+    let store = set_up_virtual(
+        virtual_store()
+            .with_file(".git/HEAD", "")
+            .with_file(".gpg-id", "laptop@localhost\n")
+            .with_file("sub/a.gpg", ""),
+        &backend,
+    );
+    // pass init writes the folder's .gpg-id and commits it
+    let runner = Arc::new(FakeRunner::new(|invocation| {
+        let line = invocation.command_line();
+        if line.starts_with("pass init -p sub ") {
+            let gpg_id = crate::key::get_pass_dir().join("sub/.gpg-id");
+            crate::store::write(&gpg_id, "laptop@localhost\n").unwrap();
+        }
+        let stdout = if line.contains(" status ") {
+            b" D sub/.gpg-id\n".to_vec()
+        } else {
+            Vec::new()
+        };
+        Ok(CommandOutput::new(Some(0), stdout, Vec::new()))
+    }));
+    command::set_runner(runner.clone());
+
+    let conf = Config::from_toml("[git]\ncommit = true\n", None, None).unwrap();
+    crate::reencrypt(Some("sub".to_string()), &conf);
+
+    assert!(store.file("sub/.gpg-id").is_none());
+    let lines: Vec<String> = runner
+        .calls()
+        .iter()
+        .map(|call| call.command_line())
+        .collect();
+    assert!(lines
+        .iter()
+        .any(|line| line.contains(" commit ") && line.ends_with(" -- sub/.gpg-id")));
+}
+
+#[test]
+fn test_fake_reencrypt_covers_every_matching_key() {
+    let mut fixture = Fixture::new();
+    let laptop = fixture.laptop.clone();
+    let phone = fixture.device("Phone", "shared@localhost");
+    let tablet = fixture.device("Tablet", "shared@localhost");
+    let revoked = "1111111111111111111111111111111111111111";
+    let data = format!(
+        "-----BEGIN FAKE PGP PUBLIC KEY BLOCK-----\n\
+         key {} shared@localhost revoked\n\
+         key {} old@localhost revoked\n\
+         -----END FAKE PGP PUBLIC KEY BLOCK-----\n",
+        revoked, "2222222222222222222222222222222222222222"
+    );
+    fixture.backend.import(data.as_bytes()).unwrap();
+    let shared = fixture.encrypt_for(&[&laptop], "shared\n");
+    let old = fixture.encrypt_for(&[&laptop], "old\n");
+    let store = fixture.install(
+        virtual_store()
+            .with_file(".gpg-id", "laptop@localhost\n")
+            .with_file("team/.gpg-id", "shared@localhost\n")
+            .with_file("team/a.gpg", shared)
+            .with_file("old/.gpg-id", "old@localhost\n")
+            .with_file("old/b.gpg", old),
+    );
+    let keys = get_keys(&mut fixture.backend).unwrap();
+    let pass_dir = get_pass_dir();
+
+    reencrypt_dir(
+        &mut fixture.backend,
+        &keys,
+        &pass_dir,
+        &pass_dir.join("team"),
+    )
+    .unwrap();
+    let recipients = get_virtual_recipients(&mut fixture.backend, &store, "team/a.gpg").unwrap();
+    let expected = HashSet::from_iter(vec![phone[24..].to_string(), tablet[24..].to_string()]);
+    assert_eq!(recipients, expected);
+
+    let err = reencrypt_dir(
+        &mut fixture.backend,
+        &keys,
+        &pass_dir,
+        &pass_dir.join("old"),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        "old@localhost doesn't match any key usable for encryption"
+    );
+}
@@ -0,0 +1,138 @@
+use crate::key::backend::CertStatus;
+use crate::key::backend::ExportFilter;
+use crate::key::backend::KeyBackend;
+use crate::key::backend::SequoiaBackend;
+use crate::key::generate::KeySpec;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+fn keyring_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!(
+        "pass-provision-sequoia-{}-{}",
+        name,
+        std::process::id()
+    ));
+    let _clean = fs::remove_dir_all(&dir);
+    dir
+}
+
+fn generate(backend: &mut dyn KeyBackend, email: &str) -> String {
+    let spec = KeySpec::new(None, email, None, None, false).unwrap();
+    backend.generate_key(&spec).unwrap()
+}
+
+#[test]
+fn test_sequoia_keyring_persists() {
+    let dir = keyring_dir("persist");
+    let mut backend = SequoiaBackend::open(&dir).unwrap();
+    let fingerprint = generate(&mut backend, "laptop@localhost");
+
+    let mut reopened = SequoiaBackend::open(&dir).unwrap();
+    let key = reopened.get_secret_key(&fingerprint).unwrap();
+    assert_eq!(key.get_fingerprint(), fingerprint);
+    assert_eq!(key.user_ids()[0].get_email(), Some("laptop@localhost"));
+    assert!(key.has_secret());
+    assert!(!key.is_revoked());
+    assert!(!key.is_expired());
+    assert!(reopened.get_key("0000000000000000").is_none());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_sequoia_certify_and_export() {
+    let dir = keyring_dir("certify");
+    let mut backend = SequoiaBackend::open(&dir).unwrap();
+    let signer = generate(&mut backend, "laptop@localhost");
+    let signee = generate(&mut backend, "phone@localhost");
+    backend.add_signer(&signer).unwrap();
+    backend.certify(&signee).unwrap();
+
+    let signer_id = &signer[signer.len() - 16..];
+    let key = backend.get_key(&signee).unwrap();
+    let certified = key.user_ids()[0]
+        .certifications()
+        .iter()
+        .any(|certification| {
+            certification.get_signer() == Some(signer_id)
+                && certification.get_status() == CertStatus::Good
+        });
+    assert!(certified);
+
+    let mut staging = backend.staging().unwrap();
+    let full = backend.export(&[&signee], ExportFilter::Full).unwrap();
+    let summary = staging.import(&full).unwrap();
+    assert_eq!(summary.imports().len(), 1);
+    assert!(summary.imports()[0].is_new());
+    let staged = staging.get_key(&signee).unwrap();
+    let unknown = staged.user_ids()[0]
+        .certifications()
+        .iter()
+        .any(|certification| {
+            certification.get_signer() == Some(signer_id)
+                && certification.get_status() == CertStatus::Unknown
+        });
+    assert!(unknown);
+
+    let minimal = backend.export(&[&signee], ExportFilter::Minimal).unwrap();
+    let records = backend.read_keys(&minimal).unwrap();
+    assert!(records[0].user_ids()[0]
+        .certifications()
+        .iter()
+        .all(|certification| certification.get_signer() != Some(signer_id)));
+
+    let update_dir = keyring_dir("certify-update");
+    let mut updated = SequoiaBackend::open(&update_dir).unwrap();
+    let public_key = backend.export(&[&signer], ExportFilter::Full).unwrap();
+    updated.import(&public_key).unwrap();
+    updated.import(&minimal).unwrap();
+    let summary = updated.import(&full).unwrap();
+    assert!(!summary.imports()[0].is_new());
+    assert_eq!(summary.new_signatures(), 1);
+    fs::remove_dir_all(dir).unwrap();
+    fs::remove_dir_all(update_dir).unwrap();
+}
+
+#[test]
+fn test_sequoia_sign_and_verify() {
+    let dir = keyring_dir("sign");
+    let mut backend = SequoiaBackend::open(&dir).unwrap();
+    let signer = generate(&mut backend, "laptop@localhost");
+    assert!(backend.sign_detached(b"recipients\n").is_err());
+
+    backend.add_signer(&signer).unwrap();
+    assert_eq!(backend.signers(), vec![signer.clone()]);
+    let signature = backend.sign_detached(b"recipients\n").unwrap();
+    let signers = backend
+        .verify_detached(&signature, b"recipients\n")
+        .unwrap();
+    assert!(signers.contains(&signer));
+    let signers = backend.verify_detached(&signature, b"tampered\n").unwrap();
+    assert!(!signers.contains(&signer));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_sequoia_encrypt_and_decrypt() {
+    let dir = keyring_dir("encrypt");
+    let mut backend = SequoiaBackend::open(&dir).unwrap();
+    let laptop = generate(&mut backend, "laptop@localhost");
+    let phone = generate(&mut backend, "phone@localhost");
+
+    let ciphertext = backend
+        .encrypt(&[&laptop, "phone@localhost"], b"hunter2\n")
+        .unwrap();
+    let decrypted = backend.decrypt(&ciphertext).unwrap();
+    assert_eq!(decrypted.get_plaintext(), b"hunter2\n");
+    assert_eq!(decrypted.get_recipients().len(), 2);
+
+    let other_dir = keyring_dir("encrypt-other");
+    let mut other = SequoiaBackend::open(&other_dir).unwrap();
+    let public_key = backend.export(&[&phone], ExportFilter::Full).unwrap();
+    other.import(&public_key).unwrap();
+    assert!(other.get_secret_key(&phone).is_none());
+    assert!(other.decrypt(&ciphertext).is_err());
+    assert!(other.encrypt(&["nobody@localhost"], b"hunter2\n").is_err());
+    fs::remove_dir_all(dir).unwrap();
+    fs::remove_dir_all(other_dir).unwrap();
+}
@@ -1,29 +1,42 @@
 use super::clean_up_scenario;
+use super::get_scenario_runtime_path;
+use super::is_signed_by;
+use super::script_answers;
 use super::set_up;
-use super::write_to_stdin;
-use gpgme::Context;
+use super::set_up_virtual;
+use super::virtual_store;
+use crate::key::backend::ExportFilter;
+use crate::key::backend::FakeBackend;
+use crate::key::backend::KeyBackend;
+use crate::key::export::write_key_file;
+use crate::key::export::ExportStatus;
+use crate::key::ImportError;
+use crate::prompt::AssumePrompter;
+use std::fs;
 use std::path::PathBuf;
 use std::process::Command;
+use std::sync::Arc;
 
-fn is_signed_by(context: &mut Context, key_id: &str, signer_identity: &str) -> bool {
-    let key = context.get_key(key_id);
-    if key.is_err() {
-        return false;
-    }
-    let key = key.unwrap();
-
-    for user_id in key.user_ids() {
-        for sig in user_id.signatures() {
-            let signer_id = sig.signer_key_id();
-            if signer_id.is_ok() && !sig.is_invalid() {
-                let signer_id = signer_id.unwrap();
-                if signer_id == signer_identity {
-                    return true;
-                }
-            }
-        }
+const DEFAULT_KEY: &str = "126DF511181C21E94E688C44AA8A6BE01EB30743";
+
+fn assert_quarantined(scenario_name: &str, fingerprint: &str) {
+    let keys_dir = get_scenario_runtime_path(scenario_name)
+        .join("pass")
+        .join(".keys");
+    let fname = format!("{}.asc", fingerprint);
+    if keys_dir.join(&fname).exists() {
+        panic!("Key file {} was left in place", fname);
+    }
+    if !keys_dir.join("quarantine").join(&fname).exists() {
+        panic!("Key file {} wasn't quarantined", fname);
+    }
+    if !keys_dir
+        .join("quarantine")
+        .join(format!("{}.reason", fingerprint))
+        .exists()
+    {
+        panic!("No quarantine reason written for {}", fname);
     }
-    false
 }
 
 #[test]
@@ -32,32 +45,33 @@ fn test_key_import() {
     let newkey1_id = "A849644DA452281D2EB637EA9FEBCD1F791BC6B9";
     let newkey2_id = "D54BA99B7CD92448901306F46AD31A01C5E25FA2";
 
-    let mut context = set_up("import_keys");
-    let new_key1 = context.get_key(newkey1_id);
-    if new_key1.is_ok() {
+    let mut backend = set_up("import_keys");
+    let new_key1 = backend.get_key(newkey1_id);
+    if new_key1.is_some() {
         panic!("Newkey1 is already present in keyring");
     }
-    let new_key2 = context.get_key(newkey2_id);
-    if new_key2.is_ok() {
+    let new_key2 = backend.get_key(newkey2_id);
+    if new_key2.is_some() {
         panic!("Newkey2 is already present in keyring");
     }
-    write_to_stdin("\n\n");
-    crate::check_keys_to_import(&mut context);
-    let new_key1 = context.get_key(newkey1_id);
-    let new_key2 = context.get_key(newkey2_id);
+    script_answers(&["", ""]);
+    let conf = crate::config::Config::new(DEFAULT_KEY.to_string());
+    crate::check_keys_to_import(&mut backend, &conf);
+    let new_key1 = backend.get_key(newkey1_id);
+    let new_key2 = backend.get_key(newkey2_id);
 
-    if new_key1.is_err() {
+    if new_key1.is_none() {
         panic!("Newkey1 not imported");
     }
-    if !is_signed_by(&mut context, newkey1_id, testing_key_id) {
+    if !is_signed_by(&mut backend, newkey1_id, testing_key_id) {
         panic!("Newkey1 imported but it isn't trusted");
     }
 
-    if new_key2.is_err() {
+    if new_key2.is_none() {
         panic!("Newkey2 not imported");
     }
 
-    if !is_signed_by(&mut context, newkey2_id, testing_key_id) {
+    if !is_signed_by(&mut backend, newkey2_id, testing_key_id) {
         panic!("Newkey2 imported but it isn't trusted");
     }
 
@@ -69,16 +83,17 @@ fn test_key_import_no_sigs() {
     let newkey1_id = "A849644DA452281D2EB637EA9FEBCD1F791BC6B9";
     let testing_key_id = "AA8A6BE01EB30743";
 
-    let mut context = set_up("import_keys_no_sigs");
-    let new_key1 = context.get_key(newkey1_id);
-    if new_key1.is_ok() {
+    let mut backend = set_up("import_keys_no_sigs");
+    let new_key1 = backend.get_key(newkey1_id);
+    if new_key1.is_some() {
         panic!("Newkey1 is already present in keyring");
     }
-    write_to_stdin("\n");
-    crate::check_keys_to_import(&mut context);
-    let new_key1 = context.get_key(newkey1_id);
+    script_answers(&[""]);
+    let conf = crate::config::Config::new(DEFAULT_KEY.to_string());
+    crate::check_keys_to_import(&mut backend, &conf);
+    let new_key1 = backend.get_key(newkey1_id);
 
-    if new_key1.is_ok() && is_signed_by(&mut context, newkey1_id, testing_key_id) {
+    if new_key1.is_some() && is_signed_by(&mut backend, newkey1_id, testing_key_id) {
         panic!("Newkey1 imported but it is trusted");
     }
     clean_up_scenario("import_keys_no_sigs");
@@ -89,16 +104,19 @@ fn test_key_import_new_key() {
     let newkey1_id = "A849644DA452281D2EB637EA9FEBCD1F791BC6B9";
     let testing_key_id = "AA8A6BE01EB30743";
 
-    let mut context = set_up("import_keys_new_key");
-    let new_key1 = context.get_key(newkey1_id);
-    if new_key1.is_ok() {
+    let mut backend = set_up("import_keys_new_key");
+    let new_key1 = backend.get_key(newkey1_id);
+    if new_key1.is_some() {
         panic!("Newkey1 is already present in keyring");
     }
-    write_to_stdin("1\n");
-    crate::check_keys_to_import(&mut context);
-    let new_key1 = context.get_key(newkey1_id);
+    // Both the sign prompt and its confirmation; the old stdin stub replayed
+    // "1\n" for every prompt, so this is the same run as before
+    script_answers(&["1", "1"]);
+    let conf = crate::config::Config::new(DEFAULT_KEY.to_string());
+    crate::check_keys_to_import(&mut backend, &conf);
+    let new_key1 = backend.get_key(newkey1_id);
 
-    if new_key1.is_ok() && !is_signed_by(&mut context, newkey1_id, testing_key_id) {
+    if new_key1.is_some() && !is_signed_by(&mut backend, newkey1_id, testing_key_id) {
         panic!("Newkey1 imported but it is trusted");
     }
     clean_up_scenario("import_keys_new_key");
@@ -109,25 +127,59 @@ fn test_key_import_fraud_id() {
     let newkey1_id = "A849644DA452281D2EB637EA9FEBCD1F791BC6B9";
     let testing_key_id = "AA8A6BE01EB30743";
 
-    let mut context = set_up("import_keys_fraud_id");
-    let new_key1 = context.get_key(newkey1_id);
-    if new_key1.is_ok() {
+    let mut backend = set_up("import_keys_fraud_id");
+    let new_key1 = backend.get_key(newkey1_id);
+    if new_key1.is_some() {
         panic!("Newkey1 is already present in keyring");
     }
-    write_to_stdin("\n");
-    crate::check_keys_to_import(&mut context);
-    let new_key1 = context.get_key(newkey1_id);
+    script_answers(&[""]);
+    let conf = crate::config::Config::new(DEFAULT_KEY.to_string());
+    crate::check_keys_to_import(&mut backend, &conf);
+    let new_key1 = backend.get_key(newkey1_id);
 
-    if new_key1.is_ok() && is_signed_by(&mut context, newkey1_id, testing_key_id) {
+    if new_key1.is_some() && is_signed_by(&mut backend, newkey1_id, testing_key_id) {
         panic!("Newkey1 imported but it is trusted");
     }
+    assert_quarantined(
+        "import_keys_fraud_id",
+        "D54BA99B7CD92448901306F46AD31A01C5E25FA2",
+    );
     clean_up_scenario("import_keys_fraud_id");
 }
 
+#[test]
+fn test_key_import_multiple_keys() {
+    let newkey1_id = "A849644DA452281D2EB637EA9FEBCD1F791BC6B9";
+    let newkey2_id = "D54BA99B7CD92448901306F46AD31A01C5E25FA2";
+
+    let mut backend = set_up("import_keys_multiple");
+    script_answers(&["", ""]);
+    let conf = crate::config::Config::new(DEFAULT_KEY.to_string());
+    crate::check_keys_to_import(&mut backend, &conf);
+
+    if backend.get_key(newkey1_id).is_some() || backend.get_key(newkey2_id).is_some() {
+        panic!("Key file with multiple primary keys was imported");
+    }
+    assert_quarantined("import_keys_multiple", newkey1_id);
+    clean_up_scenario("import_keys_multiple");
+}
+
+#[test]
+fn test_key_import_secret_key() {
+    let mut backend = set_up("import_keys_secret");
+    script_answers(&[""]);
+    let conf = crate::config::Config::new(DEFAULT_KEY.to_string());
+    crate::check_keys_to_import(&mut backend, &conf);
+
+    assert_quarantined("import_keys_secret", DEFAULT_KEY);
+    clean_up_scenario("import_keys_secret");
+}
+
 #[test]
 fn test_fresh_sigs() {
-    let mut context = set_up("fresh_sigs");
-    crate::add_fresh_sigs(&mut context);
+    let mut backend = set_up("fresh_sigs");
+    let conf = crate::config::Config::new(DEFAULT_KEY.to_string());
+    crate::add_fresh_sigs(&mut backend, false, &conf);
 
     for (expected_name, actual_name) in &[
         (
@@ -153,13 +205,42 @@ fn test_fresh_sigs() {
     clean_up_scenario("fresh_sigs");
 }
 
+#[test]
+fn test_fresh_sigs_unchanged() {
+    let mut backend = set_up("fresh_sigs_unchanged");
+    let conf = crate::config::Config::new(DEFAULT_KEY.to_string());
+    crate::add_fresh_sigs(&mut backend, false, &conf);
+
+    let keys_dir = get_scenario_runtime_path("fresh_sigs_unchanged")
+        .join("pass")
+        .join(".keys");
+    let key_path = keys_dir.join("A849644DA452281D2EB637EA9FEBCD1F791BC6B9.asc");
+    let first_modified = fs::metadata(&key_path).unwrap().modified().unwrap();
+
+    crate::add_fresh_sigs(&mut backend, false, &conf);
+    let second_modified = fs::metadata(&key_path).unwrap().modified().unwrap();
+    if first_modified != second_modified {
+        panic!("Unchanged key was rewritten");
+    }
+    let status = write_key_file(
+        &mut backend,
+        "A849644DA452281D2EB637EA9FEBCD1F791BC6B9",
+        conf.get_export_mode(),
+        &key_path,
+        false,
+    );
+    assert!(matches!(status, Ok(ExportStatus::Unchanged)));
+    clean_up_scenario("fresh_sigs_unchanged");
+}
+
 #[test]
 fn test_write_keys() {
-    let mut context = set_up("write_keys");
-    crate::add_fresh_sigs(&mut context);
+    let mut backend = set_up("write_keys");
+    let conf = crate::config::Config::new(DEFAULT_KEY.to_string());
+    crate::add_fresh_sigs(&mut backend, false, &conf);
 
-    let keys = crate::key::get_keys(&mut context).unwrap();
-    crate::write_missing_keys(&mut context, &keys);
+    let keys = crate::key::get_keys(&mut backend).unwrap();
+    crate::write_missing_keys(&mut backend, &keys, &conf);
 
     let actual_key_path =
         "testing/write_keys_run/pass/.keys/A849644DA452281D2EB637EA9FEBCD1F791BC6B9.asc";
@@ -178,3 +259,261 @@ fn test_write_keys() {
 
     clean_up_scenario("write_keys");
 }
+
+fn fake_key_file(backend: &mut FakeBackend, fingerprint: &str) -> Vec<u8> {
+    backend.export(&[fingerprint], ExportFilter::Full).unwrap()
+}
+
+#[test]
+fn test_fake_key_import_trusted() {
+    let mut remote = FakeBackend::new();
+    let laptop = remote.with_secret_key("laptop@localhost");
+    let phone = remote.with_secret_key("phone@localhost");
+    remote.add_signer(&laptop).unwrap();
+    remote.certify(&phone).unwrap();
+
+    let mut backend = FakeBackend::new();
+    backend.with_secret_key("laptop@localhost");
+    let store = virtual_store().with_file(
+        &format!(".keys/{}.asc", phone),
+        fake_key_file(&mut remote, &phone),
+    );
+    set_up_virtual(store, &backend);
+    backend.add_signer(&laptop).unwrap();
+
+    script_answers(&[""]);
+    let conf = crate::config::Config::new(laptop.clone());
+    crate::check_keys_to_import(&mut backend, &conf);
+
+    assert!(is_signed_by(&mut backend, &phone, &laptop[24..]));
+}
+
+#[test]
+fn test_fake_key_import_untrusted_declined() {
+    let mut remote = FakeBackend::new();
+    let phone = remote.with_secret_key("phone@localhost");
+
+    let mut backend = FakeBackend::new();
+    let laptop = backend.with_secret_key("laptop@localhost");
+    let store = virtual_store().with_file(
+        &format!(".keys/{}.asc", phone),
+        fake_key_file(&mut remote, &phone),
+    );
+    set_up_virtual(store, &backend);
+    backend.add_signer(&laptop).unwrap();
+
+    script_answers(&[""]);
+    let conf = crate::config::Config::new(laptop.clone());
+    crate::check_keys_to_import(&mut backend, &conf);
+
+    assert!(backend.get_key(&phone).is_some());
+    assert!(!is_signed_by(&mut backend, &phone, &laptop[24..]));
+}
+
+#[test]
+fn test_fake_key_import_untrusted_accepted() {
+    let mut remote = FakeBackend::new();
+    let phone = remote.with_secret_key("phone@localhost");
+
+    let mut backend = FakeBackend::new();
+    let laptop = backend.with_secret_key("laptop@localhost");
+    let store = virtual_store().with_file(
+        &format!(".keys/{}.asc", phone),
+        fake_key_file(&mut remote, &phone),
+    );
+    set_up_virtual(store, &backend);
+    backend.add_signer(&laptop).unwrap();
+
+    script_answers(&["1", "1"]);
+    let conf = crate::config::Config::new(laptop.clone());
+    crate::check_keys_to_import(&mut backend, &conf);
+
+    assert!(is_signed_by(&mut backend, &phone, &laptop[24..]));
+}
+
+#[test]
+fn test_fake_key_import_untrusted_yes() {
+    let mut remote = FakeBackend::new();
+    let phone = remote.with_secret_key("phone@localhost");
+
+    let mut backend = FakeBackend::new();
+    let laptop = backend.with_secret_key("laptop@localhost");
+    let store = virtual_store().with_file(
+        &format!(".keys/{}.asc", phone),
+        fake_key_file(&mut remote, &phone),
+    );
+    set_up_virtual(store, &backend);
+    backend.add_signer(&laptop).unwrap();
+
+    crate::prompt::set_prompter(Arc::new(AssumePrompter::yes()));
+    let conf = crate::config::Config::new(laptop.clone());
+    crate::check_keys_to_import(&mut backend, &conf);
+
+    assert!(!is_signed_by(&mut backend, &phone, &laptop[24..]));
+}
+
+#[test]
+fn test_fake_key_import_forged_signature() {
+    let mut backend = FakeBackend::new();
+    let laptop = backend.with_secret_key("laptop@localhost");
+    let phone = "A849644DA452281D2EB637EA9FEBCD1F791BC6B9";
+    let key_file = format!(
+        "-----BEGIN FAKE PGP PUBLIC KEY BLOCK-----\n\
+         key {} phone@localhost\n\
+         cert {} bad\n\
+         -----END FAKE PGP PUBLIC KEY BLOCK-----\n",
+        phone,
+        &laptop[24..]
+    );
+    let store = set_up_virtual(
+        virtual_store().with_file(&format!(".keys/{}.asc", phone), key_file),
+        &backend,
+    );
+
+    let conf = crate::config::Config::new(laptop);
+    crate::check_keys_to_import(&mut backend, &conf);
+
+    assert!(backend.get_key(phone).is_none());
+    assert!(store.file(&format!(".keys/{}.asc", phone)).is_none());
+    assert!(store
+        .file(&format!(".keys/quarantine/{}.asc", phone))
+        .is_some());
+    let reason = store
+        .file(&format!(".keys/quarantine/{}.reason", phone))
+        .unwrap();
+    assert!(String::from_utf8(reason)
+        .unwrap()
+        .contains("forged signature"));
+}
+
+#[test]
+fn test_fake_key_import_staging_strips_unknown_signatures() {
+    let mut remote = FakeBackend::new();
+    let laptop = remote.with_secret_key("laptop@localhost");
+    let phone = remote.with_secret_key("phone@localhost");
+    remote.add_signer(&laptop).unwrap();
+    remote.certify(&phone).unwrap();
+    let stranger = "0123456789ABCDEF";
+    let key_file = String::from_utf8(fake_key_file(&mut remote, &phone))
+        .unwrap()
+        .replace("-----END", &format!("cert {} good\n-----END", stranger));
+
+    let mut backend = FakeBackend::new();
+    backend.with_secret_key("laptop@localhost");
+    set_up_virtual(
+        virtual_store().with_file(&format!(".keys/{}.asc", phone), key_file),
+        &backend,
+    );
+    backend.add_signer(&laptop).unwrap();
+
+    script_answers(&[""]);
+    let conf = crate::config::Config::new(laptop.clone());
+    crate::check_keys_to_import(&mut backend, &conf);
+
+    let imported = String::from_utf8(fake_key_file(&mut backend, &phone)).unwrap();
+    assert!(imported.contains(&format!("cert {} good", &laptop[24..])));
+    assert!(!imported.contains(stranger));
+}
+
+#[test]
+fn test_fake_key_import_rejections() {
+    let mut backend = FakeBackend::new();
+    let laptop = backend.with_secret_key("laptop@localhost");
+    let revoked = "1111111111111111111111111111111111111111";
+    let expired = "2222222222222222222222222222222222222222";
+    let forged = "3333333333333333333333333333333333333333";
+    let key_file = |fingerprint: &str, rest: &str| {
+        format!(
+            "-----BEGIN FAKE PGP PUBLIC KEY BLOCK-----\n\
+             key {} {}\n\
+             -----END FAKE PGP PUBLIC KEY BLOCK-----\n",
+            fingerprint, rest
+        )
+    };
+    set_up_virtual(
+        virtual_store()
+            .with_file(
+                &format!(".keys/{}.asc", revoked),
+                key_file(revoked, "old@localhost revoked"),
+            )
+            .with_file(
+                &format!(".keys/{}.asc", expired),
+                key_file(expired, "old@localhost expired"),
+            )
+            .with_file(
+                &format!(".keys/{}.asc", forged),
+                key_file(
+                    forged,
+                    &format!("phone@localhost\ncert {} bad", &laptop[24..]),
+                ),
+            ),
+        &backend,
+    );
+    let conf = crate::config::Config::new(laptop);
+
+    let result = crate::key::import_key(&mut backend, revoked.to_string(), &conf);
+    assert!(matches!(result, Err(ImportError::Revoked)));
+    let result = crate::key::import_key(&mut backend, expired.to_string(), &conf);
+    assert!(matches!(result, Err(ImportError::Expired)));
+    let result = crate::key::import_key(&mut backend, forged.to_string(), &conf);
+    assert!(matches!(result, Err(ImportError::BadSignature(_))));
+    for fingerprint in [revoked, expired, forged] {
+        assert!(backend.get_key(fingerprint).is_none());
+    }
+}
+
+#[test]
+fn test_fake_key_import_secret_key() {
+    let mut backend = FakeBackend::new();
+    let laptop = backend.with_secret_key("laptop@localhost");
+    let phone = "D54BA99B7CD92448901306F46AD31A01C5E25FA2";
+    let key_file = format!(
+        "-----BEGIN FAKE PGP PUBLIC KEY BLOCK-----\n\
+         key {} phone@localhost secret\n\
+         -----END FAKE PGP PUBLIC KEY BLOCK-----\n",
+        phone
+    );
+    let store = set_up_virtual(
+        virtual_store().with_file(&format!(".keys/{}.asc", phone), key_file),
+        &backend,
+    );
+
+    let conf = crate::config::Config::new(laptop);
+    crate::check_keys_to_import(&mut backend, &conf);
+
+    assert!(backend.get_key(phone).is_none());
+    assert!(store
+        .file(&format!(".keys/quarantine/{}.asc", phone))
+        .is_some());
+}
+
+#[test]
+fn test_fake_fresh_sigs_and_write_keys() {
+    let mut backend = FakeBackend::new();
+    let laptop = backend.with_secret_key("laptop@localhost");
+    let phone = backend.with_secret_key("phone@localhost");
+    let phone_file = fake_key_file(&mut backend, &phone);
+    let store = set_up_virtual(
+        virtual_store()
+            .with_file(&format!(".keys/{}.asc", phone), phone_file)
+            .with_file(".gpg-id", "laptop@localhost\nphone@localhost\n"),
+        &backend,
+    );
+    backend.add_signer(&laptop).unwrap();
+    backend.certify(&phone).unwrap();
+
+    let conf = crate::config::Config::new(laptop.clone());
+    crate::add_fresh_sigs(&mut backend, false, &conf);
+    let phone_file = store.file(&format!(".keys/{}.asc", phone)).unwrap();
+    let signed = format!("cert {} good", &laptop[24..]);
+    assert!(String::from_utf8(phone_file).unwrap().contains(&signed));
+
+    let laptop_path = format!(".keys/{}.asc", laptop);
+    assert!(store.file(&laptop_path).is_none());
+    let keys = crate::key::get_keys(&mut backend).unwrap();
+    crate::write_missing_keys(&mut backend, &keys, &conf);
+    assert_eq!(
+        store.file(&laptop_path),
+        Some(fake_key_file(&mut backend, &laptop))
+    );
+}